@@ -0,0 +1,170 @@
+//! Benchmarks of the per-record overhead.
+//!
+//! The loggers are called directly (not installed), so the benchmarks don't interfere with each
+//! other. The Python loggers have a `NullHandler`, so the numbers show our overhead plus the
+//! Python `logging` machinery, not the cost of any actual output.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use log::{Level, LevelFilter, Log, Record};
+use pyo3::prelude::*;
+use pyo3_log::{Caching, Logger};
+
+const MODES: [(&str, Caching); 3] = [
+    ("nothing", Caching::Nothing),
+    ("loggers", Caching::Loggers),
+    ("loggers_and_levels", Caching::LoggersAndLevels),
+];
+
+/// Sets up a Python logger that accepts everything and throws it away.
+fn null_logger(py: Python<'_>, name: &str) {
+    let logging = py.import("logging").unwrap();
+    let logger = logging
+        .getattr("getLogger")
+        .unwrap()
+        .call1((name,))
+        .unwrap();
+    let handler = logging.getattr("NullHandler").unwrap().call0().unwrap();
+    logger.call_method1("addHandler", (handler,)).unwrap();
+    logger.call_method1("setLevel", (1,)).unwrap();
+    logger.setattr("propagate", false).unwrap();
+}
+
+fn log_record(logger: &Logger, target: &str, level: Level) {
+    logger.log(
+        &Record::builder()
+            .target(target)
+            .level(level)
+            .args(format_args!("Hello {}", 42))
+            .build(),
+    );
+}
+
+/// A trace record, with trace disabled on the Rust side. Should not touch Python at all.
+fn filtered_out(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filtered out");
+    Python::with_gil(|py| null_logger(py, "bench_filtered"));
+    for (name, caching) in MODES {
+        let logger =
+            Python::with_gil(|py| Logger::new(py, caching).unwrap().filter(LevelFilter::Debug));
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| log_record(&logger, "bench_filtered", Level::Trace))
+        });
+    }
+    group.finish();
+}
+
+/// An info record, with the logger already cached (if caching is on).
+fn cache_hit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache hit");
+    Python::with_gil(|py| null_logger(py, "bench_hit"));
+    for (name, caching) in MODES {
+        let logger = Python::with_gil(|py| Logger::new(py, caching).unwrap());
+        // Populate the cache.
+        log_record(&logger, "bench_hit", Level::Info);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| log_record(&logger, "bench_hit", Level::Info))
+        });
+    }
+    group.finish();
+}
+
+/// An info record, with the cache reset before each one.
+fn cache_miss(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache miss");
+    Python::with_gil(|py| null_logger(py, "bench_miss"));
+    for (name, caching) in MODES {
+        let logger = Python::with_gil(|py| Logger::new(py, caching).unwrap());
+        let handle = logger.reset_handle();
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                handle.reset();
+                log_record(&logger, "bench_miss", Level::Info)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Storing a new deep target into a cache already full of siblings along the path.
+///
+/// The copy-on-write cache copies each node on the path, so this shows the cost of copying the
+/// nodes with many children.
+fn deep_cache_fill(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deep cache fill");
+    Python::with_gil(|py| null_logger(py, "bench_deep"));
+    for (name, caching) in &MODES[1..] {
+        let logger = Python::with_gil(|py| Logger::new(py, *caching).unwrap());
+        let mut path = String::from("bench_deep");
+        for level in 0..5 {
+            for sibling in 0..50 {
+                let target = format!("{}::sibling_{}", path, sibling);
+                log_record(&logger, &target, Level::Info);
+            }
+            path = format!("{}::level_{}", path, level);
+        }
+        let handle = logger.reset_handle();
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                handle.reset_target(&path);
+                log_record(&logger, &path, Level::Info)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Storing a new target into a cache already holding 10k siblings.
+fn wide_cache_fill(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wide cache fill");
+    Python::with_gil(|py| null_logger(py, "bench_wide"));
+    for (name, caching) in &MODES[1..] {
+        let logger = Python::with_gil(|py| Logger::new(py, *caching).unwrap());
+        for sibling in 0..10_000 {
+            let target = format!("bench_wide::sibling_{}", sibling);
+            log_record(&logger, &target, Level::Info);
+        }
+        let handle = logger.reset_handle();
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                handle.reset_target("bench_wide::new");
+                log_record(&logger, "bench_wide::new", Level::Info)
+            })
+        });
+    }
+    group.finish();
+}
+
+/// An info record, disabled on the Python side, with the logger already cached.
+fn disabled(c: &mut Criterion) {
+    let mut group = c.benchmark_group("disabled in python");
+    Python::with_gil(|py| null_logger(py, "bench_disabled"));
+    for (name, caching) in MODES {
+        let logger = Python::with_gil(|py| {
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("bench_disabled",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (50,)).unwrap();
+            Logger::new(py, caching).unwrap()
+        });
+        log_record(&logger, "bench_disabled", Level::Info);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| log_record(&logger, "bench_disabled", Level::Info))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    filtered_out,
+    cache_hit,
+    cache_miss,
+    deep_cache_fill,
+    wide_cache_fill,
+    disabled
+);
+criterion_main!(benches);
@@ -0,0 +1,252 @@
+//! The buffered mode.
+//!
+//! In this mode, the records are not sent to Python right away. They are copied and sent over a
+//! queue to a background thread, which acquires the GIL once in a while and sends all the queued
+//! records to Python in one go.
+
+use std::borrow::Cow;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, Thread};
+use std::time::{Duration, SystemTime};
+
+use log::{Level, Record};
+use pyo3::prelude::*;
+
+use crate::{context, Logger, Metrics};
+
+/// Where and when a record was logged on the Rust side.
+#[derive(Clone, Debug)]
+pub(crate) struct Origin {
+    pub(crate) created: SystemTime,
+    pub(crate) thread: Thread,
+    /// See [`thread_id`].
+    pub(crate) thread_id: u64,
+    /// The process the record was logged in, it may be a different one by the time the record
+    /// reaches Python (after a fork).
+    pub(crate) pid: u32,
+    pub(crate) context: Vec<context::Fields>,
+    /// The backtrace, if [requested][crate::Logger::capture_backtrace] and enabled.
+    #[cfg(feature = "backtrace")]
+    pub(crate) backtrace: Option<String>,
+}
+
+impl Origin {
+    pub(crate) fn current() -> Self {
+        Origin {
+            created: SystemTime::now(),
+            thread: thread::current(),
+            thread_id: thread_id(),
+            pid: process::id(),
+            context: context::current(),
+            #[cfg(feature = "backtrace")]
+            backtrace: None,
+        }
+    }
+}
+
+/// A numeric id of the current thread.
+///
+/// The [`ThreadId`][thread::ThreadId] has no stable way to get a number out of it, so we number
+/// the threads ourselves, in the order they first ask. The ids are unique within the process and
+/// never reused.
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
+
+/// An owned copy of a [`Record`].
+///
+/// Unlike the [`Record`], this one can be sent to another thread (or kept for later).
+#[derive(Debug)]
+pub(crate) struct OwnedRecord {
+    level: Level,
+    target: String,
+    msg: String,
+    module_path: Option<Cow<'static, str>>,
+    file: Option<Cow<'static, str>>,
+    line: Option<u32>,
+    /// When and where the record was logged.
+    origin: Origin,
+    #[cfg(feature = "kv")]
+    kvs: crate::kv::OwnedKvs,
+}
+
+/// Copies a string of the record, unless it is static.
+fn owned(static_str: Option<&'static str>, dynamic: Option<&str>) -> Option<Cow<'static, str>> {
+    static_str
+        .map(Cow::Borrowed)
+        .or_else(|| dynamic.map(|s| Cow::Owned(s.to_owned())))
+}
+
+impl OwnedRecord {
+    pub(crate) fn new(record: &Record, origin: Origin) -> Self {
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            msg: record.args().to_string(),
+            // The ones generated by the macros are static, no need to copy them.
+            module_path: owned(record.module_path_static(), record.module_path()),
+            file: owned(record.file_static(), record.file()),
+            line: record.line(),
+            origin,
+            #[cfg(feature = "kv")]
+            kvs: crate::kv::to_owned(record.key_values()),
+        }
+    }
+
+    /// When and where the original record was logged.
+    pub(crate) fn origin(&self) -> &Origin {
+        &self.origin
+    }
+
+    /// Reconstructs the borrowed [`Record`] and passes it to the closure.
+    pub(crate) fn with_record<R, F: FnOnce(&Record) -> R>(&self, f: F) -> R {
+        let mut builder = Record::builder();
+        builder
+            .level(self.level)
+            .target(&self.target)
+            .line(self.line);
+        // Keep the static ones static, for the sake of whoever looks at the record.
+        match self.module_path {
+            Some(Cow::Borrowed(module_path)) => builder.module_path_static(Some(module_path)),
+            ref module_path => builder.module_path(module_path.as_deref()),
+        };
+        match self.file {
+            Some(Cow::Borrowed(file)) => builder.file_static(Some(file)),
+            ref file => builder.file(file.as_deref()),
+        };
+        #[cfg(feature = "kv")]
+        builder.key_values(&self.kvs);
+        f(&builder.args(format_args!("{}", self.msg)).build())
+    }
+}
+
+enum Message {
+    /// A record to log, through the given logger.
+    ///
+    /// Boxed, as it's much larger than the other variant.
+    Record(Box<(Logger, OwnedRecord)>),
+    /// Confirm all the previous records were sent to Python.
+    Flush(SyncSender<()>),
+}
+
+/// The queue of the buffered mode, with the background thread on the other end.
+///
+/// The thread terminates once this (and all the clones of the logger holding it) is dropped and
+/// the queue is drained.
+#[derive(Debug)]
+pub(crate) struct Buffer {
+    sender: SyncSender<Message>,
+}
+
+impl Buffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        thread::Builder::new()
+            .name("pyo3-log".to_owned())
+            .spawn(move || drain(receiver, capacity))
+            .expect("Failed to start the pyo3-log background thread");
+        Buffer { sender }
+    }
+
+    /// Queues a record for logging by the given logger.
+    ///
+    /// The record is dropped (and counted in the metrics) if the queue is full.
+    pub(crate) fn send(&self, logger: Logger, record: &Record) {
+        let metrics = Arc::clone(&logger.metrics);
+        let record = OwnedRecord::new(record, logger.origin(record));
+        let msg = Message::Record(Box::new((logger, record)));
+        // Blocking here could stall the whole application (or deadlock if we hold the GIL), so we
+        // rather lose the record if the queue is full. Or if the thread is gone, but that happens
+        // only if it panicked.
+        if self.sender.try_send(msg).is_err() {
+            Metrics::inc(&metrics.dropped_overflow);
+        }
+    }
+
+    /// Queues a record and waits up to the timeout for it to reach Python.
+    ///
+    /// Returns `false` if the timeout elapsed. The record stays in the queue in such case.
+    pub(crate) fn send_wait(&self, logger: Logger, record: &Record, timeout: Duration) -> bool {
+        self.send(logger, record);
+        let (ack_sender, ack) = mpsc::sync_channel(1);
+        if self.sender.try_send(Message::Flush(ack_sender)).is_err() {
+            // Full queue, the record likely didn't make it in either. Nothing to wait for.
+            return true;
+        }
+        !matches!(ack.recv_timeout(timeout), Err(RecvTimeoutError::Timeout))
+    }
+
+    /// Waits for all the queued records to reach Python.
+    pub(crate) fn flush(&self) {
+        let (ack_sender, ack) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(ack_sender)).is_ok() {
+            // Error means the thread is gone, there's nothing to wait for then.
+            let wait = move || {
+                let _ = ack.recv();
+            };
+            if crate::interpreter_initialized() {
+                // The background thread needs the GIL to make progress. We may be holding it, so
+                // make sure we let go of it while waiting.
+                Python::with_gil(|py| py.allow_threads(wait));
+            } else {
+                wait();
+            }
+        }
+    }
+}
+
+fn drain(receiver: Receiver<Message>, batch: usize) {
+    while let Ok(msg) = receiver.recv() {
+        if !crate::interpreter_initialized() {
+            match msg {
+                Message::Record(msg) => {
+                    let (logger, record) = *msg;
+                    record.with_record(|record| logger.log_missing_interpreter(record))
+                }
+                Message::Flush(ack) => {
+                    let _ = ack.send(());
+                }
+            }
+            continue;
+        }
+
+        Python::with_gil(|py| {
+            let mut msg = Some(msg);
+            let mut processed = 0;
+            while let Some(current) = msg.take() {
+                match current {
+                    Message::Record(msg) => {
+                        let (logger, record) = *msg;
+                        logger.log_pending(py);
+                        record.with_record(|rust_record| {
+                            let cache = logger.lookup(rust_record.target());
+                            logger.log_locked(py, rust_record, &cache, Some(record.origin()));
+                        });
+                        // Nobody is going to pick up the exception in this thread.
+                        if let Some(e) = PyErr::take(py) {
+                            e.print(py);
+                        }
+                    }
+                    // The other thread might have given up on waiting already, that's fine.
+                    Message::Flush(ack) => {
+                        let _ = ack.send(());
+                    }
+                }
+
+                // Process whatever is already queued while we hold the GIL, but don't starve
+                // others by holding it for too long.
+                processed += 1;
+                if processed < batch {
+                    msg = receiver.try_recv().ok();
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,230 @@
+//! The map of the children of a cache node.
+//!
+//! The cache is updated in a copy-on-write manner, each update copies the nodes on the path to the
+//! changed entry. With an ordinary map, copying a node would copy all its children, so adding a
+//! target under a node with many of them would get expensive. This one is a persistent hash trie,
+//! so a copy shares everything with the original and an update copies only the few trie nodes on
+//! the way to the changed key.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::sync::Arc;
+
+/// Bits of the hash used on each level of the trie.
+const BITS: u32 = 4;
+/// Number of slots of a branch.
+const WIDTH: usize = 1 << BITS;
+/// Leaves with more entries are split into a branch.
+const LEAF_MAX: usize = 8;
+/// Below this depth there are no more bits in the hash, so the leaves grow without bounds.
+const MAX_DEPTH: u32 = u64::BITS / BITS;
+
+fn hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn slot(hash: u64, depth: u32) -> usize {
+    ((hash >> (depth * BITS)) as usize) & (WIDTH - 1)
+}
+
+#[derive(Clone)]
+enum Node<V> {
+    Leaf(Vec<(u64, Arc<str>, V)>),
+    Branch(Vec<Option<Arc<Node<V>>>>),
+}
+
+impl<V: Clone> Node<V> {
+    fn insert(node: &mut Arc<Self>, hash: u64, depth: u32, key: Arc<str>, value: V) -> bool {
+        match Arc::make_mut(node) {
+            Node::Leaf(entries) => {
+                if let Some(entry) = entries.iter_mut().find(|(h, k, _)| *h == hash && *k == key) {
+                    entry.2 = value;
+                    return false;
+                }
+                if entries.len() < LEAF_MAX || depth >= MAX_DEPTH {
+                    entries.push((hash, key, value));
+                    return true;
+                }
+                let mut branch = Arc::new(Node::Branch(vec![None; WIDTH]));
+                for (hash, key, value) in entries.drain(..) {
+                    Node::insert(&mut branch, hash, depth, key, value);
+                }
+                Node::insert(&mut branch, hash, depth, key, value);
+                *node = branch;
+                true
+            }
+            Node::Branch(slots) => match &mut slots[slot(hash, depth)] {
+                Some(child) => Node::insert(child, hash, depth + 1, key, value),
+                empty => {
+                    *empty = Some(Arc::new(Node::Leaf(vec![(hash, key, value)])));
+                    true
+                }
+            },
+        }
+    }
+
+    /// Removes the key, returning if it was there.
+    ///
+    /// Nodes left empty are replaced by `None`.
+    fn remove(node: &mut Option<Arc<Self>>, hash: u64, depth: u32, key: &str) -> bool {
+        let inner = match node {
+            Some(inner) if inner.contains(hash, depth, key) => inner,
+            _ => return false,
+        };
+        let empty = match Arc::make_mut(inner) {
+            Node::Leaf(entries) => {
+                entries.retain(|(h, k, _)| *h != hash || &**k != key);
+                entries.is_empty()
+            }
+            Node::Branch(slots) => {
+                Node::remove(&mut slots[slot(hash, depth)], hash, depth + 1, key);
+                slots.iter().all(Option::is_none)
+            }
+        };
+        if empty {
+            *node = None;
+        }
+        true
+    }
+}
+
+impl<V> Node<V> {
+    fn get(&self, hash: u64, depth: u32, key: &str) -> Option<(&Arc<str>, &V)> {
+        match self {
+            Node::Leaf(entries) => entries
+                .iter()
+                .find(|(h, k, _)| *h == hash && &**k == key)
+                .map(|(_, k, v)| (k, v)),
+            Node::Branch(slots) => slots[slot(hash, depth)]
+                .as_ref()
+                .and_then(|child| child.get(hash, depth + 1, key)),
+        }
+    }
+
+    fn contains(&self, hash: u64, depth: u32, key: &str) -> bool {
+        self.get(hash, depth, key).is_some()
+    }
+}
+
+/// A persistent map from target segments to the child nodes.
+///
+/// Cloning is cheap and the clones share all the data.
+pub(crate) struct Children<V> {
+    root: Option<Arc<Node<V>>>,
+    len: usize,
+}
+
+impl<V> Children<V> {
+    pub(crate) fn get_key_value(&self, key: &str) -> Option<(&Arc<str>, &V)> {
+        self.root.as_ref()?.get(hash(key), 0, key)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&V> {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            stack: self.root.iter().map(|root| &**root).collect(),
+            leaf: [].iter(),
+        }
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<V: Clone> Children<V> {
+    pub(crate) fn insert(&mut self, key: Arc<str>, value: V) {
+        let hash = hash(&key);
+        let new = match &mut self.root {
+            Some(root) => Node::insert(root, hash, 0, key, value),
+            root => {
+                *root = Some(Arc::new(Node::Leaf(vec![(hash, key, value)])));
+                true
+            }
+        };
+        if new {
+            self.len += 1;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) {
+        if Node::remove(&mut self.root, hash(key), 0, key) {
+            self.len -= 1;
+        }
+    }
+}
+
+impl<V> Clone for Children<V> {
+    fn clone(&self) -> Self {
+        Children {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<V> Default for Children<V> {
+    fn default() -> Self {
+        Children { root: None, len: 0 }
+    }
+}
+
+impl<V: Debug> Debug for Children<V> {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<V: Clone> FromIterator<(Arc<str>, V)> for Children<V> {
+    fn from_iter<I: IntoIterator<Item = (Arc<str>, V)>>(iter: I) -> Self {
+        let mut children = Children::default();
+        for (key, value) in iter {
+            children.insert(key, value);
+        }
+        children
+    }
+}
+
+impl<'a, V> IntoIterator for &'a Children<V> {
+    type Item = (&'a Arc<str>, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
+
+/// Iterator over the entries of [`Children`], in an unspecified order.
+pub(crate) struct Iter<'a, V> {
+    stack: Vec<&'a Node<V>>,
+    leaf: std::slice::Iter<'a, (u64, Arc<str>, V)>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a Arc<str>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((_, k, v)) = self.leaf.next() {
+                return Some((k, v));
+            }
+            match self.stack.pop()? {
+                Node::Leaf(entries) => self.leaf = entries.iter(),
+                Node::Branch(slots) => self
+                    .stack
+                    .extend(slots.iter().flatten().map(|child| &**child)),
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Contextual fields attached to all the records logged from a thread.
+//!
+//! Similar to Python's `LoggerAdapter`, but scoped by a guard instead of wrapping the logger.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::{RESERVED, RESERVED_PREFIX};
+
+/// One set of fields, pushed by one [`ContextGuard`].
+pub(crate) type Fields = Arc<HashMap<String, PyObject>>;
+
+thread_local! {
+    /// The fields of the current thread, the innermost last.
+    static CONTEXT: RefCell<Vec<(u64, Fields)>> = RefCell::new(Vec::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps contextual fields attached to the records of the current thread.
+///
+/// Created by [`Logger::push_context`][crate::Logger::push_context]. The fields are removed once
+/// this is dropped. It is bound to the thread it was created in and can't be sent to another.
+#[derive(Debug)]
+#[must_use = "The context is removed right away if the guard is not kept around"]
+pub struct ContextGuard {
+    id: u64,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        // The guards may be dropped out of order, so find the right one. It can fail only during
+        // the thread's destruction, when the fields are going away anyway.
+        let _ = CONTEXT.try_with(|context| {
+            context.borrow_mut().retain(|(id, _)| *id != self.id);
+        });
+    }
+}
+
+pub(crate) fn push(fields: HashMap<String, PyObject>) -> ContextGuard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    CONTEXT.with(|context| context.borrow_mut().push((id, Arc::new(fields))));
+    ContextGuard {
+        id,
+        _not_send: PhantomData,
+    }
+}
+
+/// Is there any context on the current thread?
+pub(crate) fn active() -> bool {
+    CONTEXT
+        .try_with(|context| !context.borrow().is_empty())
+        .unwrap_or(false)
+}
+
+/// Snapshot of the fields of the current thread.
+pub(crate) fn current() -> Vec<Fields> {
+    CONTEXT
+        .try_with(|context| {
+            context
+                .borrow()
+                .iter()
+                .map(|(_, fields)| Arc::clone(fields))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Puts the fields into the `extra` dict.
+///
+/// Keys already present (eg. from the key-value pairs of the record) are kept, and the inner
+/// contexts take precedence over the outer ones.
+///
+/// Keys Python refuses in the `extra` are prefixed (see [`RESERVED`]).
+pub(crate) fn merge(extra: &Bound<'_, PyDict>, context: &[Fields]) -> PyResult<()> {
+    for fields in context.iter().rev() {
+        for (key, value) in fields.iter() {
+            let key = if RESERVED.contains(&key.as_str()) {
+                Cow::Owned(format!("{}{}", RESERVED_PREFIX, key))
+            } else {
+                Cow::Borrowed(key.as_str())
+            };
+            if !extra.contains(&*key)? {
+                extra.set_item(&*key, value)?;
+            }
+        }
+    }
+    Ok(())
+}
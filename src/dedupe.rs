@@ -0,0 +1,145 @@
+//! Suppression of repeated messages, see [`Logger::dedupe_window`][crate::Logger::dedupe_window].
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{Level, Record};
+
+use crate::level_index;
+
+/// The last message of one target and level.
+#[derive(Debug)]
+struct Last {
+    hash: u64,
+    since: Instant,
+    repeats: usize,
+}
+
+#[derive(Debug)]
+struct State {
+    /// The last messages of each target, indexed by [`level_index`].
+    last: HashMap<String, [Option<Last>; 5]>,
+
+    /// When the expired entries were removed the last time.
+    pruned: Instant,
+}
+
+/// A summary of the suppressed repeats, to be logged.
+pub(crate) struct Summary {
+    target: String,
+    level: Level,
+    repeats: usize,
+}
+
+impl Summary {
+    fn new(target: &str, level: Level, last: &Last) -> Option<Self> {
+        if last.repeats == 0 {
+            return None;
+        }
+        Some(Summary {
+            target: target.to_owned(),
+            level,
+            repeats: last.repeats,
+        })
+    }
+
+    pub(crate) fn with_record<R, F: FnOnce(&Record) -> R>(&self, f: F) -> R {
+        f(&Record::builder()
+            .target(&self.target)
+            .level(self.level)
+            .args(format_args!("last message repeated {} times", self.repeats))
+            .build())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Dedupe {
+    window: Duration,
+    state: Mutex<State>,
+}
+
+impl Dedupe {
+    pub(crate) fn new(window: Duration) -> Self {
+        Dedupe {
+            window,
+            state: Mutex::new(State {
+                last: HashMap::new(),
+                pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Decides if the record gets logged.
+    ///
+    /// The summaries of the repeats that ended (either because the message changed or because the
+    /// window closed) are put into `summaries`, to be logged before the record.
+    pub(crate) fn check(&self, record: &Record, summaries: &mut Vec<Summary>) -> bool {
+        let mut hasher = DefaultHasher::new();
+        record.args().to_string().hash(&mut hasher);
+        let hash = hasher.finish();
+        let now = Instant::now();
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(state.pruned) >= self.window {
+            state.pruned = now;
+            let window = self.window;
+            state.last.retain(|target, slots| {
+                for (level, slot) in Level::iter().zip(slots.iter_mut()) {
+                    let expired = match slot {
+                        Some(last) => now.duration_since(last.since) >= window,
+                        None => false,
+                    };
+                    if expired {
+                        let last = slot.take().expect("Checked to be there");
+                        summaries.extend(Summary::new(target, level, &last));
+                    }
+                }
+                slots.iter().any(Option::is_some)
+            });
+        }
+
+        if !state.last.contains_key(record.target()) {
+            state
+                .last
+                .insert(record.target().to_owned(), Default::default());
+        }
+        let slots = state.last.get_mut(record.target()).expect("Inserted above");
+        let slot = &mut slots[level_index(record.level())];
+        match *slot {
+            Some(ref mut last)
+                if last.hash == hash && now.duration_since(last.since) < self.window =>
+            {
+                last.repeats += 1;
+                false
+            }
+            _ => {
+                if let Some(last) = slot.take() {
+                    summaries.extend(Summary::new(record.target(), record.level(), &last));
+                }
+                *slot = Some(Last {
+                    hash,
+                    since: now,
+                    repeats: 0,
+                });
+                true
+            }
+        }
+    }
+
+    /// Takes the summaries of all the currently suppressed repeats.
+    pub(crate) fn drain(&self) -> Vec<Summary> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut summaries = Vec::new();
+        for (target, slots) in state.last.drain() {
+            for (level, last) in Level::iter().zip(slots.iter()) {
+                if let Some(last) = last {
+                    summaries.extend(Summary::new(&target, level, last));
+                }
+            }
+        }
+        summaries
+    }
+}
@@ -0,0 +1,50 @@
+//! Parsing of the `env_logger`-style directives, see
+//! [`Logger::parse_filters`][crate::Logger::parse_filters].
+
+use log::LevelFilter;
+
+/// The filters described by the directives.
+#[derive(Debug, Default)]
+pub(crate) struct Directives {
+    /// The global level, if set.
+    pub(crate) top: Option<LevelFilter>,
+    /// Levels for specific targets, in the order of appearance.
+    pub(crate) targets: Vec<(String, LevelFilter)>,
+    /// Directives that couldn't be parsed.
+    pub(crate) invalid: Vec<String>,
+}
+
+/// Parses a comma separated list of directives, like `info,my_crate=debug,my_crate::db=off`.
+///
+/// Follows `env_logger`:
+///
+/// * A lone level sets the global level.
+/// * A lone name enables everything for that target.
+/// * `name=level` sets the level for the target, an empty level enables everything.
+///
+/// Anything after a `/` (a regex filter in `env_logger`) is ignored.
+pub(crate) fn parse(spec: &str) -> Directives {
+    let mut result = Directives::default();
+    let directives = spec.split('/').next().unwrap_or_default();
+    for directive in directives.split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        let mut parts = directive.split('=');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(part), None, None) => match part.parse() {
+                Ok(level) => result.top = Some(level),
+                Err(_) => result.targets.push((part.to_owned(), LevelFilter::max())),
+            },
+            (Some(target), Some(""), None) if !target.is_empty() => {
+                result.targets.push((target.to_owned(), LevelFilter::max()))
+            }
+            (Some(target), Some(level), None) if !target.is_empty() => match level.parse() {
+                Ok(level) => result.targets.push((target.to_owned(), level)),
+                Err(_) => result.invalid.push(directive.to_owned()),
+            },
+            _ => result.invalid.push(directive.to_owned()),
+        }
+    }
+    result
+}
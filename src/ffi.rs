@@ -0,0 +1,27 @@
+//! The raw calls into the Python C API.
+//!
+//! These are the only places with `unsafe` code in the crate. `pyo3` provides no safe way to ask
+//! these questions, as its API assumes an initialized interpreter (and knows about the GIL only
+//! through its tokens).
+
+#![allow(unsafe_code)]
+
+/// Checks the Python interpreter is initialized.
+///
+/// It stays initialized until late in the finalization, see [`LoggingModule::finalizing`] for the
+/// check done once we have the GIL.
+///
+/// [`LoggingModule::finalizing`]: crate::LoggingModule::finalizing
+pub(crate) fn interpreter_initialized() -> bool {
+    // SAFETY: This one is fine to call at any time, even without the interpreter (that's the
+    // whole point of the function).
+    unsafe { pyo3::ffi::Py_IsInitialized() != 0 }
+}
+
+/// Checks if the current thread holds the GIL.
+///
+/// Must be called only with an initialized interpreter.
+pub(crate) fn gil_held() -> bool {
+    // SAFETY: Fine to call with initialized interpreter, which the caller makes sure of.
+    unsafe { pyo3::ffi::PyGILState_Check() != 0 }
+}
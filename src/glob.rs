@@ -0,0 +1,48 @@
+//! Matching of targets against glob patterns, see [`Logger::filter_glob`][crate::Logger::filter_glob].
+
+/// Checks if the whole target matches the pattern.
+///
+/// Both are split into segments by the separator. A `**` segment matches any number (including
+/// zero) of segments, a `*` inside a segment matches any (possibly empty) part of one segment.
+pub(crate) fn matches(pattern: &str, target: &str, separator: &str) -> bool {
+    let pattern = pattern.split(separator).collect::<Vec<_>>();
+    let target = target.split(separator).collect::<Vec<_>>();
+    segments_match(&pattern, &target)
+}
+
+fn segments_match(pattern: &[&str], target: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => target.is_empty(),
+        Some((&"**", rest)) => (0..=target.len()).any(|skip| segments_match(rest, &target[skip..])),
+        Some((first, rest)) => match target.split_first() {
+            Some((segment, target_rest)) => {
+                segment_matches(first, segment) && segments_match(rest, target_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn segment_matches(pattern: &str, segment: &str) -> bool {
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    let (first, last) = match (parts.first(), parts.last()) {
+        (Some(first), Some(last)) if parts.len() > 1 => (*first, *last),
+        _ => return pattern == segment,
+    };
+    if segment.len() < first.len() + last.len()
+        || !segment.starts_with(first)
+        || !segment.ends_with(last)
+    {
+        return false;
+    }
+
+    // The leftmost match of each middle part leaves the most room for the rest.
+    let mut middle = &segment[first.len()..segment.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match middle.find(part) {
+            Some(pos) => middle = &middle[pos + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,430 @@
+//! Forwarding of the structured key-value pairs into Python.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use log::kv::{self, Key, Source, ToValue, Value, VisitSource, VisitValue};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+#[cfg(feature = "serde")]
+use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyTuple};
+use pyo3::IntoPyObjectExt;
+
+use crate::{RESERVED, RESERVED_PREFIX};
+
+/// The key under which an error is expected for the exception mapping.
+const ERROR_KEY: &str = "error";
+
+/// How the values are converted to Python.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Conversion {
+    /// Numbers, booleans and strings to their Python counterparts, anything else to `Display`.
+    Plain,
+
+    /// Anything serializable to the corresponding JSON-like Python structures.
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+/// Converts a single value into the closest Python type.
+///
+/// Numbers, booleans and strings are converted to their Python counterparts, anything else is
+/// passed as its `Display` representation.
+struct ToPython<'py> {
+    py: Python<'py>,
+    result: Option<PyResult<PyObject>>,
+}
+
+impl ToPython<'_> {
+    fn set<T>(&mut self, value: T) -> Result<(), kv::Error>
+    where
+        for<'py> T: IntoPyObject<'py>,
+    {
+        self.result = Some(value.into_py_any(self.py));
+        Ok(())
+    }
+}
+
+impl<'v> VisitValue<'v> for ToPython<'_> {
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        self.set(value.to_string())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        self.result = Some(Ok(self.py.None()));
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+
+    fn visit_char(&mut self, value: char) -> Result<(), kv::Error> {
+        self.set(value)
+    }
+}
+
+fn to_python(py: Python<'_>, value: &Value, conversion: Conversion) -> PyResult<PyObject> {
+    match conversion {
+        Conversion::Plain => plain_to_python(py, value),
+        #[cfg(feature = "serde")]
+        Conversion::Json => match serde_json::to_value(value) {
+            Ok(json) => json_to_python(py, &json),
+            Err(_) => value.to_string().into_py_any(py),
+        },
+    }
+}
+
+fn plain_to_python(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let mut visitor = ToPython { py, result: None };
+    match value.visit(&mut visitor) {
+        Ok(()) => visitor.result.unwrap_or_else(|| Ok(py.None())),
+        // We don't return errors from the visitor, but let's not make assumptions about what the
+        // value does.
+        Err(_) => value.to_string().into_py_any(py),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn json_to_python(py: Python<'_>, json: &serde_json::Value) -> PyResult<PyObject> {
+    use serde_json::Value as Json;
+
+    match json {
+        Json::Null => Ok(py.None()),
+        Json::Bool(value) => value.into_py_any(py),
+        Json::Number(number) => {
+            if let Some(value) = number.as_u64() {
+                value.into_py_any(py)
+            } else if let Some(value) = number.as_i64() {
+                value.into_py_any(py)
+            } else {
+                number.as_f64().unwrap_or(f64::NAN).into_py_any(py)
+            }
+        }
+        Json::String(value) => value.into_py_any(py),
+        Json::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(json_to_python(py, item)?)?;
+            }
+            Ok(list.into_any().unbind())
+        }
+        Json::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_python(py, value)?)?;
+            }
+            Ok(dict.into_any().unbind())
+        }
+    }
+}
+
+/// Collects the key-value pairs into a dict.
+struct DictVisitor<'a, 'py> {
+    dict: &'a Bound<'py, PyDict>,
+    /// Prefix the keys colliding with the [`RESERVED`] ones.
+    prefix_reserved: bool,
+    conversion: Conversion,
+    error: Option<PyErr>,
+}
+
+impl<'kvs> VisitSource<'kvs> for DictVisitor<'_, '_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let result = to_python(self.dict.py(), &value, self.conversion).and_then(|value| {
+            let key = key.as_str();
+            if self.prefix_reserved && RESERVED.contains(&key) {
+                self.dict
+                    .set_item(format!("{}{}", RESERVED_PREFIX, key), value)
+            } else {
+                self.dict.set_item(key, value)
+            }
+        });
+        result.map_err(|e| {
+            self.error = Some(e);
+            kv::Error::msg("Failed to convert the key-value pair to Python")
+        })
+    }
+}
+
+/// Builds the `extra` dict for `makeRecord` out of the record's key-value pairs.
+///
+/// Returns `None` if there are no key-value pairs at all, so we don't have to create the dict in
+/// the common case.
+pub(crate) fn extra<'py>(
+    py: Python<'py>,
+    source: &dyn Source,
+    conversion: Conversion,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    to_dict(py, source, true, conversion)
+}
+
+/// Builds the `args` mapping for `makeRecord` out of the record's key-value pairs.
+///
+/// Unlike with [`extra`], the keys are kept as they are. Returns `None` if there are no key-value
+/// pairs.
+pub(crate) fn args<'py>(
+    py: Python<'py>,
+    source: &dyn Source,
+    conversion: Conversion,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    to_dict(py, source, false, conversion)
+}
+
+fn to_dict<'py>(
+    py: Python<'py>,
+    source: &dyn Source,
+    prefix_reserved: bool,
+    conversion: Conversion,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    if source.count() == 0 {
+        return Ok(None);
+    }
+
+    let dict = PyDict::new(py);
+    let mut visitor = DictVisitor {
+        dict: &dict,
+        prefix_reserved,
+        conversion,
+        error: None,
+    };
+    if source.visit(&mut visitor).is_err() {
+        if let Some(e) = visitor.error {
+            return Err(e);
+        }
+    }
+
+    Ok(Some(dict))
+}
+
+/// Turns the `error` key-value pair into Python's `exc_info`.
+///
+/// If the value under the key is a Rust error, a `RuntimeError` carrying the whole chain of
+/// sources in its message is created. Returns `None` if there's no such error.
+pub(crate) fn exc_info<'py>(
+    py: Python<'py>,
+    source: &dyn Source,
+) -> PyResult<Option<Bound<'py, PyTuple>>> {
+    let value = match source.get(Key::from_str(ERROR_KEY)) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let error = match value.to_borrowed_error() {
+        Some(error) => error,
+        None => return Ok(None),
+    };
+
+    let mut msg = error.to_string();
+    let mut cause = error.source();
+    while let Some(c) = cause {
+        msg.push_str(": ");
+        msg.push_str(&c.to_string());
+        cause = c.source();
+    }
+
+    let exception = PyRuntimeError::new_err(msg).into_value(py);
+    let exception = exception.bind(py);
+    let exc_info = PyTuple::new(
+        py,
+        [
+            exception.get_type().into_any(),
+            exception.clone().into_any(),
+            py.None().into_bound(py),
+        ],
+    )?;
+    Ok(Some(exc_info))
+}
+
+/// An owned copy of an error, preserving the chain of sources.
+#[derive(Clone, Debug)]
+pub(crate) struct OwnedError {
+    msg: String,
+    source: Option<Box<OwnedError>>,
+}
+
+impl OwnedError {
+    pub(crate) fn new(error: &(dyn Error + 'static)) -> Self {
+        OwnedError {
+            msg: error.to_string(),
+            source: error.source().map(|s| Box::new(OwnedError::new(s))),
+        }
+    }
+}
+
+impl Display for OwnedError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.write_str(&self.msg)
+    }
+}
+
+impl Error for OwnedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|s| s as _)
+    }
+}
+
+/// An owned copy of a value, for sending the records to another thread.
+#[derive(Clone, Debug)]
+pub(crate) enum OwnedValue {
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    U128(u128),
+    I128(i128),
+    F64(f64),
+    Char(char),
+    Str(String),
+    Error(OwnedError),
+    /// A structured value, kept so it can still be passed as such.
+    #[cfg(feature = "serde")]
+    Json(serde_json::Value),
+}
+
+impl ToValue for OwnedValue {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            OwnedValue::Null => Value::null(),
+            OwnedValue::Bool(v) => v.to_value(),
+            OwnedValue::U64(v) => v.to_value(),
+            OwnedValue::I64(v) => v.to_value(),
+            OwnedValue::U128(v) => v.to_value(),
+            OwnedValue::I128(v) => v.to_value(),
+            OwnedValue::F64(v) => v.to_value(),
+            OwnedValue::Char(v) => v.to_value(),
+            OwnedValue::Str(v) => v.as_str().to_value(),
+            OwnedValue::Error(e) => Value::from_dyn_error(e),
+            #[cfg(feature = "serde")]
+            OwnedValue::Json(v) => Value::from_serde(v),
+        }
+    }
+}
+
+struct ToOwned(Option<OwnedValue>);
+
+impl<'v> VisitValue<'v> for ToOwned {
+    #[cfg(not(feature = "serde"))]
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::Str(value.to_string()));
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+        use serde_json::Value as Json;
+
+        let owned = match serde_json::to_value(&value) {
+            Ok(json @ Json::Array(_)) | Ok(json @ Json::Object(_)) => OwnedValue::Json(json),
+            _ => OwnedValue::Str(value.to_string()),
+        };
+        self.0 = Some(owned);
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::Null);
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::U64(value));
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::I64(value));
+        Ok(())
+    }
+
+    fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::U128(value));
+        Ok(())
+    }
+
+    fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::I128(value));
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::F64(value));
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::Bool(value));
+        Ok(())
+    }
+
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::Str(value.to_owned()));
+        Ok(())
+    }
+
+    fn visit_char(&mut self, value: char) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::Char(value));
+        Ok(())
+    }
+
+    fn visit_error(&mut self, err: &(dyn Error + 'static)) -> Result<(), kv::Error> {
+        self.0 = Some(OwnedValue::Error(OwnedError::new(err)));
+        Ok(())
+    }
+
+    fn visit_borrowed_error(&mut self, err: &'v (dyn Error + 'static)) -> Result<(), kv::Error> {
+        self.visit_error(err)
+    }
+}
+
+/// Owned copy of the key-value pairs of a record.
+///
+/// It is a [`Source`] itself, so it can be attached to a reconstructed record.
+pub(crate) type OwnedKvs = Vec<(String, OwnedValue)>;
+
+struct OwnedVisitor(OwnedKvs);
+
+impl<'kvs> VisitSource<'kvs> for OwnedVisitor {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let mut visitor = ToOwned(None);
+        if value.visit(&mut visitor).is_err() {
+            visitor.0 = None;
+        }
+        let value = visitor
+            .0
+            .unwrap_or_else(|| OwnedValue::Str(value.to_string()));
+        self.0.push((key.as_str().to_owned(), value));
+        Ok(())
+    }
+}
+
+/// Makes an owned copy of the key-value pairs.
+pub(crate) fn to_owned(source: &dyn Source) -> OwnedKvs {
+    let mut visitor = OwnedVisitor(Vec::new());
+    // We never fail in our visitor.
+    let _ = source.visit(&mut visitor);
+    visitor.0
+}
@@ -1,4 +1,4 @@
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 #![doc(
     html_root_url = "https://docs.rs/pyo3-log/0.2.1/pyo3-log/",
     test(attr(deny(warnings))),
@@ -18,6 +18,14 @@
 //! still bring the dependency in, so this might be considered if the module doesn't want to use
 //! it.
 //!
+//! For builds that don't have Python at all (building documentation or stubs in a mixed build
+//! pipeline, for example), the dependency can be turned off by `default-features = false` and
+//! enabling the `no-python` feature instead. The crate then contains only a stub with the same
+//! public API, where the Python types in the signatures are replaced by uninhabited stand-ins and
+//! the [`Error`] is `NoPython` instead of `PyErr`. The stub [`Logger`] can't be constructed (the
+//! constructors return the error) and it logs nothing, but it allows the code using the crate to
+//! compile.
+//!
 //! # Simple usage
 //!
 //! Each extension module has its own global variables, therefore the used logger is also
@@ -60,12 +68,26 @@
 //! First, level filters are applied before consulting the Python side. By default, only the
 //! [`Debug`][Level::Debug] level and more severe is considered to be sent over to Python. This can
 //! be overridden using the [`filter`][Logger::filter] and [`filter_target`][Logger::filter_target]
-//! methods.
+//! methods. For more dynamic needs, a custom function can be set by
+//! [`filter_fn`][Logger::filter_fn]. The filters can also be changed after the logger is
+//! installed, through the [`ResetHandle`].
+//!
+//! The compile-time maximum level of the `log` crate (its `max_level_*` and
+//! `release_max_level_*` features) comes even before that ‒ the more verbose records are compiled
+//! out and never reach this crate. The maximum level set on [install][Logger::install] is capped
+//! by it, and in debug builds, setting a more verbose filter prints a note to the standard error
+//! output (once).
+//!
+//! Similarly, the `min-level-info` feature of this crate drops the handling of the
+//! [`Debug`][Level::Debug] and [`Trace`][Level::Trace] records altogether (they are never sent to
+//! Python and the Python loggers are not probed for these levels), for the cases where every bit
+//! of the code size counts. The more severe levels work the same either way.
 //!
 //! Second, the Python loggers and their effective log levels are cached on the Rust side on the
 //! first use of the given module. This means that on a disabled level, only the first logging
 //! attempt in the given module will acquire GIL while the future ones will short-circuit before
-//! ever reaching Python.
+//! ever reaching Python. The first use may also be a [`log_enabled`][log::log_enabled] check (or
+//! other call to [`enabled`][Log::enabled]) on a thread already holding the GIL.
 //!
 //! This is good for performance, but could lead to the incorrect messages to be logged or not
 //! logged in certain situations ‒ if Rust logs before the Python logging system is set up properly
@@ -74,7 +96,13 @@
 //! For these reasons it is possible to turn caching off on construction of the logger (at the cost
 //! of performance) and to clear the cache manually through the [`ResetHandle`].
 //!
-//! To tune the caching and filtering, the logger needs to be created manually:
+//! The cache and the filters are updated atomically with the help of the `arc-swap` crate. The
+//! `std-cache` feature replaces it with a [`RwLock`][std::sync::RwLock] (the default `arc-swap`
+//! feature can then be turned off, to have one dependency less). This costs a bit of performance,
+//! mostly when many threads log at once.
+//!
+//! To tune the caching and filtering, the logger needs to be created manually (or configured
+//! through [`init_with`]):
 //!
 //! ```rust
 //! # use log::LevelFilter;
@@ -85,7 +113,7 @@
 //! # Python::with_gil(|py| {
 //! let handle = Logger::new(py, Caching::LoggersAndLevels)?
 //!     .filter(LevelFilter::Trace)
-//!     .filter_target("my_module::verbose_submodule".to_owned(), LevelFilter::Warn)
+//!     .filter_target("my_module::verbose_submodule", LevelFilter::Warn)
 //!     .install()
 //!     .expect("Someone installed a logger before us :-(");
 //!
@@ -99,10 +127,58 @@
 //! # Mapping
 //!
 //! The logging `target` is mapped into the name of the logger on the Python side, replacing all
-//! `::` occurrences with `.` (both form hierarchy in their respective language).
+//! `::` occurrences with `.` (both form hierarchy in their respective language). The separator on
+//! the Rust side can be changed by [`target_separator`][Logger::target_separator]. If that's not
+//! enough, the name of the Python logger can be computed by a custom function set through
+//! [`map_target`][Logger::map_target]. A common prefix for all the Python loggers can be set by
+//! [`set_prefix`][Logger::set_prefix].
+//!
+//! The Rust module path of the record (if known) is passed to Python as both the `module` and the
+//! `funcName` attributes of the `LogRecord` (Rust doesn't provide the name of the function), so
+//! the `%(module)s` and `%(funcName)s` formatters show it. The file and line are passed as
+//! `pathname` and `lineno`. The original Rust target is available as the `rust_target` attribute
+//! (so `%(rust_target)s` can be used in the formatters).
+//!
+//! The message is formatted on the Rust side and passed as the `msg` of the `LogRecord`, with an
+//! empty `args` tuple. The [`Record`] doesn't carry the original format string (only
+//! [`Arguments`] that can't be taken apart), so it can't be passed as a template. Python handlers
+//! calling `getMessage()` still get the right message (Python doesn't apply the `%` substitution
+//! with no `args`), but handlers expecting to re-render `msg` with `args` see only the final
+//! text. See [`preserve_template`][Logger::preserve_template] for
+//! passing at least the key-value pairs as `args`.
 //!
 //! Log levels are mapped to the same-named ones. The [`Trace`][Level::Trace] doesn't exist on the
-//! Python side, but is mapped to a level with value 5.
+//! Python side, but is mapped to a level with value 5. The values can be changed through
+//! [`level_map`][Logger::level_map].
+//!
+//! # Key-value pairs
+//!
+//! With the `kv` feature enabled, the structured key-value pairs of the log records are passed to
+//! Python as the `extra` argument, so they become attributes of the `LogRecord` and can be used by
+//! handlers and formatters (eg. `%(request_id)s`).
+//!
+//! Numbers, booleans and strings are converted to their Python counterparts, other values are
+//! passed as their `Display` representation (or, with the `serde` feature, as nested structures,
+//! see [`json_extra`][Logger::json_extra]). Keys colliding with the attributes the `LogRecord`
+//! already has (like `msg` or `name`) are prefixed by `kv_` (so `msg` becomes `kv_msg`). They can
+//! also be passed as the `args` of the record, see [`preserve_template`][Logger::preserve_template].
+//!
+//! If [exception mapping][Logger::with_exception_mapping] is turned on, a Rust error stored under
+//! the `error` key is also converted into a Python `RuntimeError` and passed as `exc_info`, so the
+//! Python formatters show it the same way as exceptions logged by `logging.exception`. The
+//! [`log_error`] macro logs an error in this way.
+//!
+//! # Using with `tracing`
+//!
+//! With the `tracing` feature, the [`Logger`] can be turned into a `tracing` layer by
+//! `Logger::tracing_layer`. The events then go through the same code as the `log` records (the
+//! filters, caching, etc. apply the same way), while the fields of the event and of the spans it
+//! happened in are passed in the `extra` of the Python record. The feature needs Rust 1.65.
+//!
+//! Without the feature, the events of the `tracing` crate can still reach Python through the
+//! `log`-based [`Logger`], either by enabling the `log` feature of `tracing` (the events are then
+//! emitted as `log` records too) or through the `LogTracer` of the `tracing-log` crate. The
+//! records converted this way don't carry the fields of the enclosing spans, though.
 //!
 //! # Interaction with Python GIL
 //!
@@ -155,24 +231,196 @@
 //! }
 //! # let _ = dont_deadlock;
 //! ```
+//!
+//! Alternatively, the logger can be switched to the [buffered mode][Logger::buffered]. Then the
+//! records are queued and sent to Python from a background thread, so logging itself never waits
+//! for the GIL.
+//!
+//! Records logged while there's no Python interpreter at all (before it is initialized or after
+//! it is finalized) are not sent to Python, see
+//! [`on_missing_interpreter`][Logger::on_missing_interpreter].
+//!
+//! # Sub-interpreters
+//!
+//! Sub-interpreters are not supported. The [`Logger`] holds on to the `logging` module (and the
+//! cached Python loggers) of the interpreter it was created in and these must not be used from
+//! another interpreter, each of which has its own `logging` module. As the Rust global logger is
+//! shared by all of them, the records logged while another interpreter is the current one are
+//! not sent to Python but handled the same way as if there was [no
+//! interpreter][Logger::on_missing_interpreter] at all. To tell the interpreters apart, the crate
+//! tags the `sys` module of each with the private `_pyo3_log_interpreter` attribute.
+
+#[cfg(not(any(feature = "pyo3", feature = "no-python")))]
+compile_error!("One of the pyo3 (default) and no-python features must be enabled");
 
+#[cfg(feature = "pyo3")]
+use std::any::Any;
+#[cfg(feature = "pyo3")]
+use std::borrow::Cow;
+#[cfg(feature = "pyo3")]
+use std::cell::Cell;
+#[cfg(feature = "pyo3")]
 use std::cmp;
+#[cfg(feature = "pyo3")]
 use std::collections::HashMap;
-use std::sync::Arc;
+#[cfg(feature = "pyo3")]
+use std::fmt::{Arguments, Debug, Formatter, Result as FmtResult};
+#[cfg(feature = "pyo3")]
+use std::iter;
+#[cfg(feature = "pyo3")]
+use std::mem;
+#[cfg(feature = "pyo3")]
+use std::panic;
+#[cfg(feature = "pyo3")]
+use std::process;
+#[cfg(feature = "pyo3")]
+use std::ptr;
+#[cfg(feature = "pyo3")]
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "pyo3")]
+use std::sync::{Arc, Mutex, Weak};
+#[cfg(feature = "pyo3")]
+use std::thread;
+#[cfg(feature = "pyo3")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use arc_swap::ArcSwap;
-use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+#[cfg(all(feature = "pyo3", feature = "arc-swap", not(feature = "std-cache")))]
+use arc_swap::{ArcSwap, ArcSwapOption};
+#[cfg(feature = "pyo3")]
+use buffer::{Buffer, Origin, OwnedRecord};
+#[cfg(feature = "pyo3")]
+use children::Children;
+#[cfg(feature = "pyo3")]
+pub use context::ContextGuard;
+#[cfg(feature = "pyo3")]
+use dedupe::Dedupe;
+#[cfg(feature = "pyo3")]
+use ffi::{gil_held, interpreter_initialized};
+use log::Level;
+#[cfg(feature = "pyo3")]
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+#[cfg(not(feature = "pyo3"))]
+pub use no_python::*;
+#[cfg(feature = "pyo3")]
+use pyo3::exceptions::{PyAttributeError, PyKeyError, PyRuntimeError, PyTypeError};
+#[cfg(feature = "pyo3")]
 use pyo3::prelude::*;
-use pyo3::types::PyTuple;
+#[cfg(feature = "pyo3")]
+use pyo3::sync::GILOnceCell;
+#[cfg(feature = "pyo3")]
+use pyo3::types::{PyDict, PyString, PyTuple};
+#[cfg(feature = "pyo3")]
+use rate_limit::RateLimit;
+#[cfg(feature = "pyo3")]
+use sample::Sample;
+pub use snapshot::LoggerConfig;
+#[cfg(all(feature = "pyo3", feature = "std-cache"))]
+use swap::{ArcSwap, ArcSwapOption};
+#[cfg(all(feature = "pyo3", feature = "tracing"))]
+pub use tracing_layer::TracingLayer;
+
+#[cfg(all(
+    feature = "pyo3",
+    not(any(feature = "arc-swap", feature = "std-cache"))
+))]
+compile_error!("One of the arc-swap (default) and std-cache features must be enabled");
+
+#[cfg(feature = "pyo3")]
+mod buffer;
+#[cfg(feature = "pyo3")]
+mod children;
+#[cfg(feature = "pyo3")]
+mod context;
+#[cfg(feature = "pyo3")]
+mod dedupe;
+#[cfg(feature = "pyo3")]
+mod directives;
+#[cfg(feature = "pyo3")]
+mod ffi;
+#[cfg(feature = "pyo3")]
+mod glob;
+#[cfg(all(feature = "pyo3", feature = "kv"))]
+mod kv;
+#[cfg(not(feature = "pyo3"))]
+mod no_python;
+#[cfg(feature = "pyo3")]
+mod rate_limit;
+#[cfg(feature = "pyo3")]
+mod sample;
+mod snapshot;
+#[cfg(all(feature = "pyo3", feature = "std-cache"))]
+mod swap;
+#[cfg(all(feature = "pyo3", any(test, feature = "testing")))]
+pub mod testing;
+#[cfg(all(feature = "pyo3", feature = "tracing"))]
+mod tracing_layer;
+
+#[doc(hidden)]
+pub use log as __log;
+
+/// Logs an error together with the error value itself.
+///
+/// The error is stored under the `error` key, where it is picked up by the
+/// [exception mapping][Logger::with_exception_mapping]. The rest of the arguments are the same as
+/// with the [`error`][log::error] macro.
+///
+/// The error must implement [`std::error::Error`] and be `'static`.
+///
+/// ```rust
+/// # use std::io::{Error, ErrorKind};
+/// # use pyo3::prelude::*;
+/// # use pyo3_log::{Caching, Logger};
+/// # fn main() -> PyResult<()> {
+/// # Python::with_gil(|py| {
+/// Logger::new(py, Caching::LoggersAndLevels)?
+///     .with_exception_mapping(true)
+///     .install()
+///     .expect("Someone installed a logger before us :-(");
+///
+/// let err = Error::new(ErrorKind::Other, "Disk on fire");
+/// pyo3_log::log_error!(err, "Failed to save the data");
+/// # Ok(())
+/// # })
+/// # }
+/// ```
+#[cfg(feature = "kv")]
+#[macro_export]
+macro_rules! log_error {
+    ($err: expr, $($arg: tt)+) => {
+        $crate::__log::error!(error:err = $err; $($arg)+)
+    };
+}
+
+/// The error of the fallible operations, the same as [`PyResult`] uses.
+///
+/// The stub built with the `no-python` feature has its own error type here, so code naming the
+/// error through this alias compiles in both builds.
+#[cfg(feature = "pyo3")]
+pub type Error = PyErr;
 
 /// A handle into a [`Logger`], able to reset its caches.
 ///
 /// This handle can be used to manipulate a [`Logger`] even after it has been installed. It's main
 /// purpose is to reset the internal caches, for example if the logging settings on the Python side
 /// changed.
+#[cfg(feature = "pyo3")]
 #[derive(Clone, Debug)]
-pub struct ResetHandle(Arc<ArcSwap<CacheNode>>);
+pub struct ResetHandle {
+    cache: Arc<ArcSwap<CacheNode>>,
+    filters: Arc<ArcSwap<Filters>>,
+    config: Arc<Config>,
+    logging: Arc<LoggingModule>,
+    metrics: Arc<Metrics>,
+    watch: Arc<ReconfigurationWatch>,
+    /// The logger this handle was returned for by [`Logger::install`], if any.
+    ///
+    /// Only such handles may [reconfigure][ResetHandle::reconfigure] or
+    /// [uninstall][ResetHandle::uninstall] the logger. Other loggers may share the rest of the
+    /// fields with the installed one (eg. [`CacheSharing::Shared`]), so these can't tell.
+    installed: Option<Weak<Logger>>,
+}
 
+#[cfg(feature = "pyo3")]
 impl ResetHandle {
     /// Reset the internal logger caches.
     ///
@@ -181,12 +429,494 @@ impl ResetHandle {
     pub fn reset(&self) {
         // Overwrite whatever is in the cache directly. This must win in case of any collisions
         // (the caching uses compare_and_swap to let the reset win).
-        self.0.store(Default::default());
+        self.cache.store(Default::default());
+    }
+
+    /// The counters of the records lost on the way to Python.
+    ///
+    /// The counters are shared by all the copies of the logger (and their handles) and never
+    /// reset.
+    pub fn metrics(&self) -> LoggerMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Sets the filter for a specific target at runtime.
+    ///
+    /// This works like [`Logger::filter_target`], but can be used after the logger has been
+    /// installed. It also updates the [maximum level][log::set_max_level] (like
+    /// [`install`][Logger::install] does, unless turned off by
+    /// [`manage_max_level`][Logger::manage_max_level]), so this is meant for the handle of the
+    /// installed logger.
+    ///
+    /// The caches hold only the Python side of the decision, so they don't need to be reset.
+    pub fn set_filter(&self, target: &str, filter: LevelFilter) {
+        check_static_max_level(filter);
+        let target = self.config.filter_key(target);
+        self.update_filters(|filters| {
+            filters.targets.insert(target.clone().into_owned(), filter);
+        });
+    }
+
+    /// Sets the default filter at runtime.
+    ///
+    /// This works like [`Logger::filter`], but can be used after the logger has been installed.
+    /// See [`set_filter`][ResetHandle::set_filter] for details.
+    pub fn set_default_filter(&self, filter: LevelFilter) {
+        check_static_max_level(filter);
+        self.update_filters(|filters| filters.top = filter);
+    }
+
+    fn update_filters<F: Fn(&mut Filters)>(&self, update: F) {
+        self.filters.rcu(|filters| {
+            let mut filters = Filters::clone(filters);
+            update(&mut filters);
+            filters
+        });
+        if self.config.manage_max_level {
+            log::set_max_level(self.filters.load().max_level());
+        }
+    }
+
+    /// Reset the internal logger caches if the Python logging configuration changed.
+    ///
+    /// Returns if the caches were reset. This detects changes of the logger levels (including the
+    /// ones done through `logging.config`) and changes of the handlers since the previous check.
+    /// The first check always reports a change. It relies on undocumented internals of the
+    /// Python `logging` module; if they are not available (eg. with a custom
+    /// [module][Logger::from_module]), no change is ever detected and a warning is printed (once).
+    ///
+    /// This acquires the GIL. It is relatively cheap, so it can be called periodically (or from
+    /// places where reconfiguration might have happened). See also
+    /// [`watch_reconfiguration`][Logger::watch_reconfiguration].
+    pub fn reset_if_changed(&self) -> bool {
+        // Another interpreter has its own logging module, we can't tell anything about ours.
+        let changed = Python::with_gil(|py| {
+            current_interpreter(py) == self.config.interpreter
+                && config_changed(py, &self.logging, &self.watch)
+        });
+        if changed {
+            self.reset();
+        }
+        changed
+    }
+
+    /// Reset the internal logger caches and resynchronize the Rust side with Python.
+    ///
+    /// This is for the case when the Python logging configuration changed and the Rust-side
+    /// settings derived from it need to follow (eg. the filters or the [maximum
+    /// level][log::set_max_level] computed from the effective levels of some Python loggers). The
+    /// `sync` closure does the latter, usually through [`set_filter`][ResetHandle::set_filter] and
+    /// [`set_default_filter`][ResetHandle::set_default_filter] of this handle.
+    ///
+    /// The ordering guarantees are:
+    ///
+    /// * The caches are reset before `sync` runs, so `sync` (and anything else) sees only the new
+    ///   Python configuration.
+    /// * The GIL is held for the whole call. Filling the caches needs the GIL, so no other thread
+    ///   can cache anything until `sync` finishes (unless `sync` releases the GIL itself, eg. by
+    ///   running Python code for a long time). Records filtered out on the Rust side don't need
+    ///   the GIL, so these can still be decided by the old filters until `sync` updates them.
+    /// * The Python configuration is expected to be already changed when this is called. If
+    ///   `sync` changes it, the caches filled in the meantime (see above) may be stale.
+    ///
+    /// The error of `sync` is returned, the caches are reset anyway.
+    pub fn reset_and_sync<F>(&self, py: Python<'_>, sync: F) -> PyResult<()>
+    where
+        F: FnOnce(Python<'_>) -> PyResult<()>,
+    {
+        self.reset();
+        sync(py)
+    }
+
+    /// The filter effective for the given target.
+    ///
+    /// This combines the Rust-side filters with the effective level of the Python logger, the
+    /// same way the logger decides if a record is enabled. The cached Python level is used if
+    /// there's one, otherwise Python is asked (and the GIL is taken). It doesn't store anything
+    /// into the cache.
+    ///
+    /// If Python can't be asked (there's no interpreter or the logger belongs to a different one),
+    /// only the Rust-side filters are taken into account.
+    ///
+    /// A [filter function][Logger::filter_fn] is consulted with the [`Trace`][Level::Trace]
+    /// level, as its decision might depend on the level.
+    pub fn effective_filter(&self, target: &str) -> LevelFilter {
+        let target = &*self.config.normalize(target);
+        let metadata = Metadata::builder()
+            .target(target)
+            .level(Level::Trace)
+            .build();
+        let rust = self
+            .config
+            .filter_for_metadata(&self.filters.load(), &metadata);
+        if rust == LevelFilter::Off {
+            return rust;
+        }
+
+        let cached = match self.config.caching_of(target) {
+            Caching::LoggersAndLevels => self
+                .config
+                .lookup(&self.cache, target)
+                .and_then(|node| node.local.as_ref().map(|local| local.filter)),
+            _ => None,
+        };
+        let python = cached.unwrap_or_else(|| {
+            if !interpreter_initialized() {
+                return LevelFilter::max();
+            }
+            Python::with_gil(|py| {
+                if current_interpreter(py) != self.config.interpreter {
+                    return LevelFilter::max();
+                }
+                let name = self.config.python_name(target);
+                self.logging
+                    .bind(py)
+                    .and_then(|logging| logging.getattr("getLogger"))
+                    .and_then(|get_logger| get_logger.call1((name,)))
+                    .and_then(|logger| self.config.python_max_level(&logger))
+                    // We can't tell, let the Rust side decide alone.
+                    .unwrap_or_else(|_| LevelFilter::max())
+            })
+        });
+
+        cmp::min(rust, python)
+    }
+
+    /// Number of the currently cached loggers.
+    ///
+    /// This is meant for debugging and observability (eg. to check the cache is actually being
+    /// used). It doesn't need the GIL.
+    pub fn cache_len(&self) -> usize {
+        self.cache.load().len()
+    }
+
+    /// The (Rust) targets that currently have a cached logger.
+    ///
+    /// The order is unspecified. Like [`cache_len`][ResetHandle::cache_len], this is meant for
+    /// debugging and doesn't need the GIL.
+    pub fn cached_targets(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        self.cache
+            .load()
+            .targets(&mut Vec::new(), &self.config.separator, &mut result);
+        result
+    }
+
+    /// Reset the internal logger caches for a single target.
+    ///
+    /// This removes the cached logger and level of the given (Rust) target and of all the targets
+    /// below it in the hierarchy (eg. resetting `my_module` also resets `my_module::sub`). The
+    /// children are reset too, because they inherit the level from the parent on the Python side.
+    /// The rest of the cache is left intact.
+    ///
+    /// Use this instead of [`reset`][ResetHandle::reset] if only the settings of a single Python
+    /// logger changed, so not everything needs to be cached again.
+    ///
+    /// The target is split by the [separator][Logger::target_separator] the logger had at the
+    /// time the handle was created.
+    pub fn reset_target(&self, target: &str) {
+        let target = self.config.normalize(target);
+        let path = target
+            .split(self.config.separator.as_str())
+            .collect::<Vec<_>>();
+        // Unlike storing new entries, the reset must win in case of collisions, therefore we
+        // retry until we succeed.
+        self.cache.rcu(|root| {
+            root.remove_recursive(&path)
+                .unwrap_or_else(|| Arc::clone(root))
+        });
+    }
+
+    /// Replaces the installed logger by another one.
+    ///
+    /// The `log` crate doesn't allow replacing the global logger, but a
+    /// [reconfigurable][Logger::reconfigurable] logger is installed behind a thin shim forwarding
+    /// to the actual [`Logger`], which can be swapped. This allows changing the whole configuration at runtime (for example in tests or on
+    /// hot reload), not only the filters. The new logger is set up as if it was installed (the
+    /// maximum level, the [registered handle][Logger::register_handle_as], etc.) and its handle is
+    /// returned.
+    ///
+    /// This works only with the handle returned when the currently installed logger was installed
+    /// (or its clones, like the one from [`global_reset_handle`]). Otherwise (if this handle is of
+    /// a logger that was never installed, wasn't reconfigurable, was replaced already or only
+    /// shares the cache with the installed one), nothing happens and the new logger is returned
+    /// back.
+    pub fn reconfigure(&self, logger: Logger) -> Result<ResetHandle, Logger> {
+        let logger = Arc::new(logger);
+        if self.replace_installed(Some(Arc::clone(&logger))) {
+            Ok(logger.activate())
+        } else {
+            Err(Arc::try_unwrap(logger).unwrap_or_else(|logger| logger.share()))
+        }
+    }
+
+    /// Removes the installed logger.
+    ///
+    /// The records are then dropped, as if no logger was installed, and a new one can be
+    /// [installed][Logger::install] later. The registration in the `log` crate itself persists,
+    /// though, so no other logger can be installed in the `log` crate (and the
+    /// [maximum level][log::set_max_level] is set to [`Off`][LevelFilter::Off] to save the work,
+    /// unless the logger [doesn't manage it][Logger::manage_max_level]).
+    ///
+    /// Like [`reconfigure`][ResetHandle::reconfigure], this works only with the handle returned
+    /// when the currently installed logger was installed and returns if it did so.
+    pub fn uninstall(&self) -> bool {
+        if !self.replace_installed(None) {
+            return false;
+        }
+        INSTALLED_HANDLE.store(None);
+        if self.config.manage_max_level {
+            log::set_max_level(LevelFilter::Off);
+        }
+        true
+    }
+
+    /// Replaces the installed logger, if it is the one of this handle.
+    fn replace_installed(&self, new: Option<Arc<Logger>>) -> bool {
+        let current = INSTALLED_LOGGER.load();
+        let ours = match (current.as_ref(), self.installed.as_ref()) {
+            (Some(current), Some(installed)) => ptr::eq(Arc::as_ptr(current), installed.as_ptr()),
+            _ => false,
+        };
+        if !ours {
+            return false;
+        }
+        let prev = INSTALLED_LOGGER.compare_and_swap(&current, new);
+        same_logger(&prev, &current)
+    }
+
+    /// Turns the handle into an opaque [`CacheToken`].
+    ///
+    /// See [`from_token`][ResetHandle::from_token] for the way back.
+    pub fn into_token(self) -> CacheToken {
+        CacheToken(Arc::new(self))
+    }
+
+    /// Reconstructs the handle from a [`CacheToken`].
+    pub fn from_token(token: CacheToken) -> Self {
+        Arc::try_unwrap(token.0).unwrap_or_else(|handle| ResetHandle::clone(&handle))
+    }
+}
+
+/// An opaque form of a [`ResetHandle`].
+///
+/// This is meant for frameworks managing many loggers, which want to store their handles in
+/// their own structures without depending on the details of the [`ResetHandle`]. The token is a
+/// single pointer, cheap to clone, and can be turned back into the handle by
+/// [`ResetHandle::from_token`].
+///
+/// Tokens compare equal if they belong to loggers sharing the same cache (eg. the handles of the
+/// same logger, or of copies made by [`Logger::clone_ref`] with [`CacheSharing::Shared`]).
+///
+/// ```rust
+/// # use pyo3_log::{Logger, ResetHandle};
+/// let logger = Logger::default();
+/// let token = logger.reset_handle().into_token();
+/// assert_eq!(token, logger.reset_handle().into_token());
+/// ResetHandle::from_token(token).reset();
+/// ```
+#[cfg(feature = "pyo3")]
+#[derive(Clone, Debug)]
+pub struct CacheToken(Arc<ResetHandle>);
+
+#[cfg(feature = "pyo3")]
+impl PartialEq for CacheToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.cache, &other.0.cache)
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Eq for CacheToken {}
+
+/// What to do with records logged when there's no Python interpreter.
+///
+/// This happens when Rust logs before the interpreter is initialized or after it has started
+/// finalizing. Calling into Python at such time would crash.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MissingInterpreter {
+    /// Throw the records away.
+    Drop,
+
+    /// Keep the records and send them to Python once it is available.
+    ///
+    /// The records are sent the next time the logger uses Python with the interpreter available
+    /// (when logging another record, on [`flush`][Log::flush], etc.). Until then, they stay
+    /// queued, so call `flush` once the interpreter is up if nothing else may be logged.
+    ///
+    /// At most [`MISSING_INTERPRETER_CAPACITY`] records are kept, newer ones are dropped.
+    Buffer,
+
+    /// Write the records to the standard error output.
+    Stderr,
+}
+
+impl Default for MissingInterpreter {
+    fn default() -> Self {
+        MissingInterpreter::Drop
+    }
+}
+
+/// How many records can be queued at most with [`Logger::gil_timeout`].
+#[cfg(feature = "pyo3")]
+const GIL_TIMEOUT_CAPACITY: usize = 1024;
+
+/// Configuration and the worker for [`Logger::gil_timeout`].
+#[cfg(feature = "pyo3")]
+#[derive(Debug)]
+struct GilTimeout {
+    timeout: Duration,
+    worker: Buffer,
+    /// Did we already warn about the timeout?
+    warned: AtomicBool,
+}
+
+/// What [`flush`][Log::flush] does.
+///
+/// Independently of this, the flush always waits for the records queued on the Rust side (eg. in
+/// the [buffered mode][Logger::buffered]) to reach Python.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FlushPolicy {
+    /// Don't do anything on the Python side.
+    Nothing,
+
+    /// Flush all the handlers of all the Python loggers.
+    Handlers,
+
+    /// Call `logging.shutdown()`.
+    ///
+    /// This flushes and closes all the handlers. Meant for flushing once at the end of the
+    /// program.
+    Shutdown,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy::Handlers
+    }
+}
+
+/// Which method of the Python logger gets the records, see [`Logger::dispatch_method`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DispatchMethod {
+    /// `Logger.handle`, after checking the level of the logger by `isEnabledFor`.
+    ///
+    /// Both the level and the filters of the Python logger apply.
+    Handle,
+
+    /// `Logger.callHandlers`, without checking the level of the logger.
+    ///
+    /// Neither the level nor the filters of the Python logger apply (the handlers still have
+    /// their own), so the Rust-side filters decide alone.
+    CallHandlers,
+}
+
+impl Default for DispatchMethod {
+    fn default() -> Self {
+        DispatchMethod::Handle
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl DispatchMethod {
+    /// The name of the Python method.
+    fn method_name(self) -> &'static str {
+        match self {
+            DispatchMethod::Handle => "handle",
+            DispatchMethod::CallHandlers => "callHandlers",
+        }
+    }
+}
+
+/// What the copy made by [`Logger::clone_ref`] does with the cache.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CacheSharing {
+    /// Both loggers use the same cache.
+    ///
+    /// The cached loggers are looked up only once for both of them and a [`ResetHandle`] of
+    /// either resets the cache of both. The counters of the [lost records][LoggerMetrics] are
+    /// shared too.
+    Shared,
+
+    /// The copy starts with an empty cache of its own.
+    ///
+    /// The loggers are fully independent, including the counters of the [lost
+    /// records][LoggerMetrics].
+    Forked,
+}
+
+/// Counters of the records lost on the way to Python, see [`ResetHandle::metrics`].
+///
+/// This is a snapshot, it doesn't change when more records get lost.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct LoggerMetrics {
+    /// Records that failed on the Python side (an exception was raised while logging them).
+    ///
+    /// Some of the handlers might have gotten the record before the failure.
+    pub dropped_errors: u64,
+
+    /// Records dropped because a queue was full.
+    ///
+    /// Either the queue of the [buffered mode][Logger::buffered] or the one for the records
+    /// logged [without an interpreter][MissingInterpreter::Buffer].
+    pub dropped_overflow: u64,
+
+    /// Records dropped because there was no interpreter (with [`MissingInterpreter::Drop`]).
+    pub dropped_missing_interpreter: u64,
+
+    /// Records dropped by the [rate limiting][Logger::rate_limit].
+    pub dropped_rate_limited: u64,
+
+    /// Records not admitted by the [sampling][Logger::sample].
+    pub dropped_sampled: u64,
+}
+
+/// The live counters behind [`LoggerMetrics`].
+#[cfg(feature = "pyo3")]
+#[derive(Debug, Default)]
+struct Metrics {
+    dropped_errors: AtomicU64,
+    dropped_overflow: AtomicU64,
+    dropped_missing_interpreter: AtomicU64,
+    dropped_rate_limited: AtomicU64,
+    dropped_sampled: AtomicU64,
+}
+
+#[cfg(feature = "pyo3")]
+impl Metrics {
+    fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> LoggerMetrics {
+        LoggerMetrics {
+            dropped_errors: self.dropped_errors.load(Ordering::Relaxed),
+            dropped_overflow: self.dropped_overflow.load(Ordering::Relaxed),
+            dropped_missing_interpreter: self.dropped_missing_interpreter.load(Ordering::Relaxed),
+            dropped_rate_limited: self.dropped_rate_limited.load(Ordering::Relaxed),
+            dropped_sampled: self.dropped_sampled.load(Ordering::Relaxed),
+        }
     }
 }
 
+/// The queue capacity of the [`async_friendly`][Logger::async_friendly] mode.
+pub const ASYNC_FRIENDLY_CAPACITY: usize = 1024;
+
+/// How many records are kept at most with [`MissingInterpreter::Buffer`].
+pub const MISSING_INTERPRETER_CAPACITY: usize = 1024;
+
 /// What the [`Logger`] can cache.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate")
+)]
 #[non_exhaustive]
 pub enum Caching {
     /// Disables caching.
@@ -201,6 +931,9 @@ pub enum Caching {
     /// are cached. However, the log levels are not. This means there's some amount of calling of
     /// Python code saved during a logging call, but the GIL still needs to be acquired even if the
     /// message doesn't eventually get output anywhere.
+    ///
+    /// Only loggers that actually handled a record are cached. Loggers of targets that are
+    /// disabled on the Python side stay out of the cache (and are looked up again each time).
     Loggers,
 
     /// Caches both the Python `Logger` and their respective effective log levels.
@@ -217,76 +950,485 @@ impl Default for Caching {
     }
 }
 
+/// A function to compute the Python logger name from a Rust target.
+#[cfg(feature = "pyo3")]
+type MapTarget = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+
+/// A function deciding the filter for targets without a specific filter.
+#[cfg(feature = "pyo3")]
+type FilterFn = dyn Fn(&Metadata) -> Option<LevelFilter> + Send + Sync;
+
+/// A function deciding which levels of a target pass.
+#[cfg(feature = "pyo3")]
+type LevelPredicate = dyn Fn(Level) -> bool + Send + Sync;
+
+/// The most verbose level the predicate lets through.
+#[cfg(feature = "pyo3")]
+fn predicate_max_level(predicate: &LevelPredicate) -> LevelFilter {
+    Level::iter()
+        .filter(|level| predicate(*level))
+        .max()
+        .map(|level| level.to_level_filter())
+        .unwrap_or(LevelFilter::Off)
+}
+
+/// The rule of the most specific matching target filter.
+#[cfg(feature = "pyo3")]
+#[derive(Clone, Copy)]
+enum TargetRule<'a> {
+    Threshold(LevelFilter),
+    Levels(&'a LevelPredicate),
+}
+
+#[cfg(feature = "pyo3")]
+impl TargetRule<'_> {
+    fn enabled(self, level: Level) -> bool {
+        match self {
+            TargetRule::Threshold(filter) => level <= filter,
+            TargetRule::Levels(predicate) => predicate(level),
+        }
+    }
+
+    /// The threshold letting through (at least) everything the rule does.
+    fn max_level(self) -> LevelFilter {
+        match self {
+            TargetRule::Threshold(filter) => filter,
+            TargetRule::Levels(predicate) => predicate_max_level(predicate),
+        }
+    }
+}
+
+/// A function handling errors from the Python side.
+#[cfg(feature = "pyo3")]
+type ErrorHandler = dyn Fn(&PyErr) + Send + Sync;
+
+/// A function producing the message passed to Python out of a record.
+#[cfg(feature = "pyo3")]
+type MessageFormatter = dyn Fn(&Record) -> String + Send + Sync;
+
+/// A function computing the name of the Python record from a Rust target.
+#[cfg(feature = "pyo3")]
+type RecordName = dyn Fn(&str) -> String + Send + Sync;
+
+/// A function scrubbing sensitive parts of the messages.
+#[cfg(feature = "pyo3")]
+type Redactor = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+
+/// A function deciding if a whole record passes.
+#[cfg(feature = "pyo3")]
+type RecordFilter = dyn Fn(&Record) -> bool + Send + Sync;
+
+/// A function observing the records on their way to Python.
+#[cfg(feature = "pyo3")]
+type Tap = dyn Fn(&Record) + Send + Sync;
+
+/// Python loggers found on a cache miss: the logger, its name and the additional (tee) loggers.
+#[cfg(feature = "pyo3")]
+type FoundLoggers = (PyObject, Py<PyString>, Vec<PyObject>);
+
+/// A Python handler all the records go to, see [`Logger::route_to_handler`].
+#[cfg(feature = "pyo3")]
+struct Route {
+    handler: PyObject,
+
+    /// A logger outside of the hierarchy, used only to create the records.
+    logger: PyObject,
+}
+
+/// A Python logger pinned to a Rust target, see [`Logger::pin_logger`].
+#[cfg(feature = "pyo3")]
+struct PinnedLogger {
+    logger: PyObject,
+    name: Py<PyString>,
+}
+
+#[cfg(feature = "pyo3")]
 #[derive(Debug)]
 struct CacheEntry {
     filter: LevelFilter,
     logger: PyObject,
+    /// The name of the Python logger, so we don't have to compute it again.
+    name: Py<PyString>,
+    /// Additional loggers the records are sent to, see [`Logger::tee_target`].
+    tees: Vec<PyObject>,
+    /// If the logger has any handlers, see [`Logger::fallback_stderr`].
+    ///
+    /// Checked only with that turned on, assumed to be true otherwise.
+    has_handlers: bool,
+    /// The value of the logger's clock when the entry was last used.
+    ///
+    /// For the eviction with [`Logger::max_cache_entries`].
+    last_used: AtomicU64,
 }
 
+#[cfg(feature = "pyo3")]
 impl CacheEntry {
-    fn clone_ref(&self, py: Python<'_>) -> Self {
-        CacheEntry {
-            filter: self.filter,
-            logger: self.logger.clone_ref(py),
+    fn touch(&self, now: u64) {
+        // Avoid writing (and bouncing the cache line between CPUs) if it's already up to date.
+        if self.last_used.load(Ordering::Relaxed) != now {
+            self.last_used.store(now, Ordering::Relaxed);
         }
     }
 }
 
+#[cfg(feature = "pyo3")]
 #[derive(Debug, Default)]
 struct CacheNode {
-    local: Option<CacheEntry>,
-    children: HashMap<String, Arc<CacheNode>>,
+    // Shared, so copying the nodes on the path doesn't need the GIL.
+    local: Option<Arc<CacheEntry>>,
+    // A persistent map, so copying a node on the path doesn't copy all its children.
+    children: Children<Arc<CacheNode>>,
 }
 
+#[cfg(feature = "pyo3")]
 impl CacheNode {
-    fn store_to_cache_recursive<'a, P>(
-        &self,
-        py: Python<'_>,
-        mut path: P,
-        entry: CacheEntry,
-    ) -> Arc<Self>
+    fn store_to_cache_recursive<'a, P>(&self, mut path: P, entry: CacheEntry) -> Arc<Self>
     where
         P: Iterator<Item = &'a str>,
     {
         let mut me = CacheNode {
             children: self.children.clone(),
-            local: self.local.as_ref().map(|e| e.clone_ref(py)),
+            local: self.local.clone(),
         };
         match path.next() {
             Some(segment) => {
-                let child = me.children.entry(segment.to_owned()).or_default();
-                *child = child.store_to_cache_recursive(py, path, entry);
+                let empty;
+                let (segment, child) = match self.children.get_key_value(segment) {
+                    Some((segment, child)) => (Arc::clone(segment), &**child),
+                    None => {
+                        empty = CacheNode::default();
+                        (Arc::from(segment), &empty)
+                    }
+                };
+                let child = child.store_to_cache_recursive(path, entry);
+                me.children.insert(segment, child);
             }
-            None => me.local = Some(entry),
+            None => me.local = Some(Arc::new(entry)),
         }
         Arc::new(me)
     }
+
+    /// Counts the nodes with a cached entry in this subtree.
+    fn len(&self) -> usize {
+        let local = usize::from(self.local.is_some());
+        local + self.children.values().map(|c| c.len()).sum::<usize>()
+    }
+
+    /// Collects the targets of the cached entries in this subtree.
+    fn targets(&self, prefix: &mut Vec<Arc<str>>, separator: &str, result: &mut Vec<String>) {
+        if self.local.is_some() {
+            result.push(prefix.join(separator));
+        }
+        for (segment, child) in &self.children {
+            prefix.push(Arc::clone(segment));
+            child.targets(prefix, separator, result);
+            prefix.pop();
+        }
+    }
+
+    /// Collects the last use times of all the entries in this subtree.
+    fn last_used(&self, result: &mut Vec<u64>) {
+        if let Some(local) = &self.local {
+            result.push(local.last_used.load(Ordering::Relaxed));
+        }
+        for child in self.children.values() {
+            child.last_used(result);
+        }
+    }
+
+    /// Creates a copy of the tree without the entries last used before the cutoff.
+    ///
+    /// Nodes left with no entry and no children are removed completely. Returns `None` if this
+    /// whole node is to be removed.
+    fn evict_recursive(&self, cutoff: u64) -> Option<Arc<Self>> {
+        let local = self
+            .local
+            .as_ref()
+            .filter(|local| local.last_used.load(Ordering::Relaxed) >= cutoff)
+            .cloned();
+        let children = self
+            .children
+            .iter()
+            .filter_map(|(segment, child)| {
+                child
+                    .evict_recursive(cutoff)
+                    .map(|child| (Arc::clone(segment), child))
+            })
+            .collect::<Children<_>>();
+        if local.is_none() && children.is_empty() {
+            None
+        } else {
+            Some(Arc::new(CacheNode { local, children }))
+        }
+    }
+
+    /// Creates a copy of the tree without the subtree on the given path.
+    ///
+    /// Returns `None` if there's no such subtree (and therefore nothing to change).
+    fn remove_recursive(&self, path: &[&str]) -> Option<Arc<Self>> {
+        let (segment, rest) = path.split_first()?;
+        let (key, child) = self.children.get_key_value(segment)?;
+        let mut children = self.children.clone();
+        if rest.is_empty() {
+            children.remove(segment);
+        } else {
+            children.insert(Arc::clone(key), child.remove_recursive(rest)?);
+        }
+        Some(Arc::new(CacheNode {
+            local: self.local.clone(),
+            children,
+        }))
+    }
 }
 
-/// The `Logger`
+/// The Rust-side filters of a [`Logger`].
 ///
-/// The actual `Logger` that can be installed into the Rust side and will send messages over to
-/// Python.
-///
-/// It can be either created directly and then installed, passed to other aggregating log systems,
-/// or the [`init`] or [`try_init`] functions may be used if defaults are good enough.
-#[derive(Debug)]
-pub struct Logger {
-    /// Filter used as a fallback if none of the `filters` match.
-    top_filter: LevelFilter,
+/// Unlike the rest of the [`Config`], these can be changed at runtime through the
+/// [`ResetHandle`].
+#[cfg(feature = "pyo3")]
+#[derive(Clone)]
+struct Filters {
+    /// Filter used as a fallback if none of the `targets` match.
+    top: LevelFilter,
 
     /// Mapping of filters to modules.
     ///
-    /// The most specific one will be used, falling back to `top_filter` if none matches. Stored as
-    /// full paths, with `::` separaters (eg. before converting them from Rust to Python).
-    filters: HashMap<String, LevelFilter>,
+    /// The most specific one will be used, falling back to `top` if none matches. Stored as full
+    /// paths, with the Rust-side separators (eg. before converting them from Rust to Python).
+    targets: HashMap<String, LevelFilter>,
 
-    /// The imported Python `logging` module.
-    logging: Py<PyModule>,
+    /// Level predicates of targets.
+    ///
+    /// Matched the same way as `targets`. If both have the same target, the predicate wins.
+    level_predicates: HashMap<String, Arc<LevelPredicate>>,
+
+    /// Filters of targets matching glob patterns, consulted if none of the `targets` match.
+    ///
+    /// The first matching one is used.
+    globs: Vec<(String, LevelFilter)>,
+
+    /// Custom filter consulted if none of the `targets` match.
+    filter_fn: Option<Arc<FilterFn>>,
+}
+
+#[cfg(feature = "pyo3")]
+impl Filters {
+    fn max_level(&self) -> LevelFilter {
+        // Anything more verbose is compiled out of the log macros, so there's no point in letting
+        // it through.
+        cmp::min(self.configured_max_level(), static_max_level())
+    }
+
+    fn configured_max_level(&self) -> LevelFilter {
+        if self.filter_fn.is_some() {
+            // We have no idea what the function may return.
+            return LevelFilter::max();
+        }
+
+        let predicates = self
+            .level_predicates
+            .values()
+            .map(|predicate| predicate_max_level(&**predicate));
+        let globs = self.globs.iter().map(|(_, filter)| *filter);
+        cmp::max(
+            self.top,
+            self.targets
+                .values()
+                .copied()
+                .chain(predicates)
+                .chain(globs)
+                .max()
+                .unwrap_or(LevelFilter::Off),
+        )
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Debug for Filters {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct("Filters")
+            .field("top", &self.top)
+            .field("targets", &self.targets)
+            .field(
+                "level_predicates",
+                &self.level_predicates.keys().collect::<Vec<_>>(),
+            )
+            .field("globs", &self.globs)
+            .field("filter_fn", &self.filter_fn.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+/// The configuration of a [`Logger`].
+#[cfg(feature = "pyo3")]
+#[derive(Clone)]
+struct Config {
+    /// The separator of the hierarchy levels in the Rust targets (`::` by default).
+    separator: String,
+
+    /// Custom mapping of Rust targets to Python logger names.
+    ///
+    /// If not set, the `separator` is replaced by `.`.
+    map_target: Option<Arc<MapTarget>>,
+
+    /// Prefix prepended to all Python logger names (empty for no prefix).
+    prefix: String,
+
+    /// Python logger name for the records with an empty target (the root logger if not set).
+    empty_target: Option<String>,
+
+    /// Custom name of the Python records, if it should differ from the logger name.
+    record_name: Option<Arc<RecordName>>,
+
+    /// Additional Python loggers the records of the Rust targets are sent to.
+    tees: HashMap<String, Vec<String>>,
+
+    /// Python loggers used for the exact Rust targets, bypassing the lookup and the cache.
+    pinned: HashMap<String, Arc<PinnedLogger>>,
+
+    /// Send the records directly to this handler instead of the Python loggers.
+    route: Option<Arc<Route>>,
+
+    /// Custom construction of the message.
+    ///
+    /// If not set, the `args` of the record are used.
+    message_formatter: Option<Arc<MessageFormatter>>,
+
+    /// Applied to the message right before it's passed to Python.
+    redact: Option<Arc<Redactor>>,
+
+    /// Drop the records this returns false for.
+    record_filter: Option<Arc<RecordFilter>>,
+
+    /// Called with each record about to be sent to Python, in the order of registration.
+    taps: Vec<Arc<Tap>>,
 
     /// Caching configuration.
     caching: Caching,
 
+    /// Caching configuration overrides for specific targets (and their children).
+    caching_for: HashMap<String, Caching>,
+
+    /// Limit of the number of cached entries.
+    max_cache_entries: Option<usize>,
+
+    /// Check for Python logging reconfiguration when logging.
+    watch_reconfiguration: bool,
+
+    /// Check for Python logging reconfiguration before trusting a cached disabled level.
+    #[cfg(feature = "validate-cache")]
+    validate_cache: bool,
+
+    /// Pass the time the record was logged to Python.
+    timestamps: bool,
+
+    /// Pass the Rust thread the record was logged from to Python.
+    thread_info: bool,
+
+    /// Put into the [`SOURCE_ID`] attribute of each record.
+    source_id: Option<String>,
+
+    /// Lowercase all the targets.
+    case_insensitive: bool,
+
+    /// Capture backtraces of records at least this severe.
+    #[cfg(feature = "backtrace")]
+    backtrace_level: Option<Level>,
+
+    /// Compute the levels of the Python loggers from `getEffectiveLevel`.
+    use_effective_level: bool,
+
+    /// Attach a `NullHandler` to the Python loggers without any handlers.
+    ensure_null_handler: bool,
+
+    /// What to do when there's no interpreter.
+    missing_interpreter: MissingInterpreter,
+
+    /// Write the records to stderr if the Python logger has no handlers.
+    fallback_stderr: bool,
+
+    /// What to do on flush.
+    flush_policy: FlushPolicy,
+
+    /// The method of the Python loggers to send the records to.
+    dispatch_method: DispatchMethod,
+
+    /// What to do with errors from the Python side.
+    on_error: Option<Arc<ErrorHandler>>,
+
+    /// The Python level values of the Rust levels, indexed by [`level_index`].
+    levels: [usize; 5],
+
+    /// Convert errors under the `error` key into Python exceptions.
+    #[cfg(feature = "kv")]
+    exception_mapping: bool,
+
+    /// Pass the currently handled Python exception with error records.
+    current_exception: bool,
+
+    /// Pass the key-value pairs as the `args` of the Python record.
+    #[cfg(feature = "kv")]
+    preserve_template: bool,
+
+    /// Pass the structured key-value pairs as nested Python structures.
+    #[cfg(all(feature = "kv", feature = "serde"))]
+    json_extra: bool,
+
+    /// The queue to the background thread, if in the buffered mode.
+    buffer: Option<Arc<Buffer>>,
+
+    /// Suppress repeated messages.
+    dedupe: Option<Arc<Dedupe>>,
+
+    /// Limits of records per second, by target prefixes.
+    rate_limits: HashMap<String, Arc<RateLimit>>,
+
+    /// Sampling rates, by target prefixes.
+    samples: HashMap<String, Arc<Sample>>,
+
+    /// Acquire the GIL through a background thread, with a timeout.
+    gil_timeout: Option<Arc<GilTimeout>>,
+
+    /// Set the global max level on install and filter changes.
+    manage_max_level: bool,
+
+    /// Install through the [`Installed`] shim, see [`Logger::reconfigurable`].
+    reconfigurable: bool,
+
+    /// The interpreter the logger was created in, see [`current_interpreter`].
+    interpreter: u64,
+
+    /// Register the reset handle under this name on install.
+    handle_name: Option<String>,
+
+    /// Install a panic hook logging the panics on install.
+    log_panics: bool,
+}
+
+/// The `Logger`
+///
+/// The actual `Logger` that can be installed into the Rust side and will send messages over to
+/// Python.
+///
+/// It can be either created directly and then installed, passed to other aggregating log systems,
+/// or the [`init`] or [`try_init`] functions may be used if defaults are good enough.
+#[cfg(feature = "pyo3")]
+#[derive(Debug)]
+pub struct Logger {
+    /// The configuration.
+    ///
+    /// Shared between the loggers installed by [`install_ref`][Logger::install_ref], but modified
+    /// in a copy-on-write manner.
+    config: Arc<Config>,
+
+    /// The Rust-side filters.
+    ///
+    /// Shared with the [`ResetHandle`], which can change them at runtime.
+    filters: Arc<ArcSwap<Filters>>,
+
+    /// The imported Python `logging` module.
+    logging: Arc<LoggingModule>,
+
     /// The cache with loggers and level filters.
     ///
     /// The nodes form a tree ‒ each one potentially holding a cache entry (or not) and might have
@@ -296,40 +1438,594 @@ pub struct Logger {
     /// here is switched. In case of collisions (eg. someone already replaced the root since
     /// starting the update), the update is just thrown away.
     cache: Arc<ArcSwap<CacheNode>>,
+
+    /// Records logged while there was no interpreter, see [`MissingInterpreter::Buffer`].
+    pending: Arc<Mutex<Vec<OwnedRecord>>>,
+
+    /// A logical clock for tracking the least recently used cache entries.
+    ///
+    /// Ticks each time a new entry is stored.
+    clock: Arc<AtomicU64>,
+
+    /// Counters of the lost records.
+    metrics: Arc<Metrics>,
+
+    /// What the last check for the Python logging reconfiguration saw.
+    ///
+    /// Goes together with the cache, which is what gets reset on a change.
+    watch: Arc<ReconfigurationWatch>,
 }
 
+#[cfg(feature = "pyo3")]
 impl Logger {
     /// Creates a new logger.
     ///
     /// It defaults to having a filter for [`Debug`][LevelFilter::Debug].
+    ///
+    /// The Python `logging` module is imported lazily, on the first use.
     pub fn new(py: Python<'_>, caching: Caching) -> PyResult<Self> {
-        let logging = py.import("logging")?;
-        Ok(Self {
-            top_filter: LevelFilter::Debug,
-            filters: HashMap::new(),
-            logging: logging.into(),
+        Self::with_logging(py, LoggingModule::default(), caching)
+    }
+
+    /// Creates a new logger with the default settings, without the need to hold the GIL.
+    ///
+    /// This is the fallible version of [`Logger::default`]. It acquires the GIL internally and
+    /// returns the error if the logger can't be set up, instead of panicking.
+    pub fn try_default() -> PyResult<Self> {
+        Python::with_gil(|py| Self::new(py, Caching::LoggersAndLevels))
+    }
+
+    /// Creates a new logger using a custom logging module.
+    ///
+    /// This is like [`new`][Logger::new], but instead of the standard `logging` module, the
+    /// provided one is used. It must be compatible with `logging` ‒ it needs to have the
+    /// `getLogger` function and the loggers returned by it need to support the `makeRecord`,
+    /// `handle` and `isEnabledFor` methods.
+    ///
+    /// Returns an error if the module doesn't have the `getLogger` function.
+    ///
+    /// ```rust
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{Caching, Logger};
+    /// # fn main() -> PyResult<()> {
+    /// # Python::with_gil(|py| {
+    /// let logger = Logger::from_module(py.import("logging")?, Caching::LoggersAndLevels)?;
+    /// # let _ = logger;
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub fn from_module(logging: Bound<'_, PyModule>, caching: Caching) -> PyResult<Self> {
+        if !logging.hasattr("getLogger")? {
+            return Err(PyAttributeError::new_err(format!(
+                "Logging module {} doesn't have the getLogger function",
+                logging.name()?
+            )));
+        }
+        Self::with_logging(logging.py(), LoggingModule::from(logging), caching)
+    }
+
+    fn with_logging(py: Python<'_>, logging: LoggingModule, caching: Caching) -> PyResult<Self> {
+        let filters = Filters {
+            top: LevelFilter::Debug,
+            targets: HashMap::new(),
+            level_predicates: HashMap::new(),
+            globs: Vec::new(),
+            filter_fn: None,
+        };
+        let config = Config {
+            separator: "::".to_owned(),
+            map_target: None,
+            record_name: None,
+            empty_target: None,
+            prefix: String::new(),
+            tees: HashMap::new(),
+            pinned: HashMap::new(),
+            route: None,
+            message_formatter: None,
+            redact: None,
+            record_filter: None,
+            taps: Vec::new(),
             caching,
+            caching_for: HashMap::new(),
+            max_cache_entries: None,
+            watch_reconfiguration: false,
+            #[cfg(feature = "validate-cache")]
+            validate_cache: false,
+            timestamps: false,
+            thread_info: false,
+            source_id: None,
+            case_insensitive: false,
+            #[cfg(feature = "backtrace")]
+            backtrace_level: None,
+            use_effective_level: false,
+            ensure_null_handler: false,
+            missing_interpreter: MissingInterpreter::default(),
+            fallback_stderr: false,
+            flush_policy: FlushPolicy::default(),
+            dispatch_method: DispatchMethod::default(),
+            on_error: None,
+            levels: DEFAULT_LEVELS,
+            #[cfg(feature = "kv")]
+            exception_mapping: false,
+            current_exception: false,
+            #[cfg(feature = "kv")]
+            preserve_template: false,
+            #[cfg(all(feature = "kv", feature = "serde"))]
+            json_extra: false,
+            buffer: None,
+            dedupe: None,
+            rate_limits: HashMap::new(),
+            samples: HashMap::new(),
+            gil_timeout: None,
+            manage_max_level: true,
+            reconfigurable: false,
+            interpreter: current_interpreter(py),
+            handle_name: None,
+            log_panics: false,
+        };
+        Ok(Self {
+            config: Arc::new(config),
+            filters: Arc::new(ArcSwap::from_pointee(filters)),
+            logging: Arc::new(logging),
             cache: Default::default(),
+            pending: Default::default(),
+            clock: Default::default(),
+            metrics: Default::default(),
+            watch: Default::default(),
         })
     }
 
+    fn config_mut(&mut self) -> &mut Config {
+        Arc::make_mut(&mut self.config)
+    }
+
+    /// Modifies the filters.
+    ///
+    /// The filters stay shared with the [`ResetHandle`]s (and with the loggers installed through
+    /// [`install_ref`][Logger::install_ref]), so they see the change.
+    fn update_filters<F: Fn(&mut Filters)>(&mut self, update: F) {
+        self.filters.rcu(|filters| {
+            let mut filters = Filters::clone(filters);
+            update(&mut filters);
+            filters
+        });
+    }
+
     /// Installs this logger as the global one.
     ///
     /// When installing, it also sets the corresponding [maximum level][log::set_max_level],
-    /// constructed using the filters in this logger.
+    /// constructed using the filters in this logger (unless turned off by
+    /// [`manage_max_level`][Logger::manage_max_level]).
+    ///
+    /// If the logger is [reconfigurable][Logger::reconfigurable], it can be replaced by
+    /// [`ResetHandle::reconfigure`] or removed by [`ResetHandle::uninstall`]. After the latter,
+    /// another logger can be installed again.
     pub fn install(self) -> Result<ResetHandle, SetLoggerError> {
-        let handle = self.reset_handle();
-        let level = cmp::max(
-            self.top_filter,
-            self.filters
-                .values()
-                .copied()
-                .max()
-                .unwrap_or(LevelFilter::Off),
+        if self.config.reconfigurable || SHIM_REGISTERED.load(Ordering::Acquire) {
+            return self.install_shim();
+        }
+        let logger = self.share();
+        match log::set_boxed_logger(Box::new(self)) {
+            Ok(()) => Ok(logger.set_up_global(logger.reset_handle())),
+            // Someone registered the shim in the meantime, it may have no logger in it.
+            Err(_) if SHIM_REGISTERED.load(Ordering::Acquire) => logger.install_shim(),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Installs the logger behind the [`Installed`] shim, registering the shim if needed.
+    fn install_shim(self) -> Result<ResetHandle, SetLoggerError> {
+        let logger = Arc::new(self);
+        match log::set_logger(&INSTALLED_SHIM) {
+            Ok(()) => {
+                INSTALLED_LOGGER.store(Some(Arc::clone(&logger)));
+                SHIM_REGISTERED.store(true, Ordering::Release);
+            }
+            Err(e) => {
+                // Our shim may be the one registered, just with no logger in it (after uninstall).
+                if !SHIM_REGISTERED.load(Ordering::Acquire) {
+                    return Err(e);
+                }
+                let prev = INSTALLED_LOGGER
+                    .compare_and_swap(&None::<Arc<Logger>>, Some(Arc::clone(&logger)));
+                if prev.is_some() {
+                    return Err(e);
+                }
+            }
+        }
+        Ok(logger.activate())
+    }
+
+    /// Does the global setup for the logger just put into the [`Installed`] shim.
+    fn activate(self: &Arc<Self>) -> ResetHandle {
+        let mut handle = self.reset_handle();
+        handle.installed = Some(Arc::downgrade(self));
+        self.set_up_global(handle)
+    }
+
+    /// Does the global setup for the just installed logger.
+    fn set_up_global(&self, handle: ResetHandle) -> ResetHandle {
+        if self.config.manage_max_level {
+            log::set_max_level(self.max_level());
+        }
+        if self.config.log_panics {
+            install_panic_hook();
+        }
+        INSTALLED_HANDLE.store(Some(Arc::new(handle.clone())));
+        if let Some(name) = &self.config.handle_name {
+            register_handle(name, handle.clone());
+        }
+        handle
+    }
+
+    /// Configures if the logger manages the global [maximum level][log::set_max_level].
+    ///
+    /// By default, [`install`][Logger::install] sets the maximum level according to the filters
+    /// and the [`ResetHandle`] updates it when the filters change. When combining this logger with
+    /// others (see [`build`][Logger::build]), the combined maximum level is better managed by
+    /// whatever combines them and this logger overwriting it would get in the way. With this
+    /// turned off, the caller is responsible for setting the maximum level, taking this logger's
+    /// [`max_level`][Logger::max_level] into account.
+    pub fn manage_max_level(mut self, manage: bool) -> Self {
+        self.config_mut().manage_max_level = manage;
+        self
+    }
+
+    /// Makes the installed logger replaceable.
+    ///
+    /// By default, [`install`][Logger::install] registers the logger in the `log` crate directly.
+    /// With this turned on, a thin shim forwarding to the logger is registered instead, so the
+    /// logger can later be replaced by [`ResetHandle::reconfigure`] or removed by
+    /// [`ResetHandle::uninstall`]. The shim costs an extra atomic load (a lock with the
+    /// `std-cache` feature) on each logging call.
+    ///
+    /// Once the shim is registered, all the following installs go through it (as nothing else can
+    /// be registered in the `log` crate anymore), whatever this is set to.
+    pub fn reconfigurable(mut self, enabled: bool) -> Self {
+        self.config_mut().reconfigurable = enabled;
+        self
+    }
+
+    /// Logs the panics through this logger.
+    ///
+    /// With this turned on, [`install`][Logger::install] also installs a panic hook that logs
+    /// each panic (its message and location) as an [`Error`][Level::Error] record with the
+    /// `panic` target, so it shows in the Python logs. The previously installed hook (by default
+    /// the one printing the panic to the standard error output) is called afterwards.
+    ///
+    /// Panics happening inside the logger itself (eg. in a [mapping
+    /// function][Logger::map_target]) are not logged, as logging them would most likely panic
+    /// again. Like any other record, the panic is not sent to Python when the interpreter is not
+    /// available (see [`on_missing_interpreter`][Logger::on_missing_interpreter]).
+    ///
+    /// Off by default.
+    pub fn log_panics(mut self, enabled: bool) -> Self {
+        self.config_mut().log_panics = enabled;
+        self
+    }
+
+    /// Installs this logger as the global one, without consuming it.
+    ///
+    /// This is similar to [`install`][Logger::install], but the installed logger is a copy of
+    /// this one and this one can still be used (for example for introspection in tests or to
+    /// install the same configuration into some other logging frontend). The copy shares the
+    /// caches with the original, so the [`ResetHandle`] works for both of them. Further
+    /// configuration of the original (through the builder methods) doesn't affect the installed
+    /// copy.
+    pub fn install_ref(&self) -> Result<ResetHandle, SetLoggerError> {
+        self.share().install()
+    }
+
+    /// Registers the [`ResetHandle`] under the given name when installed.
+    ///
+    /// The handle can then be retrieved by [`get_handle`] anywhere in the program. The
+    /// [`try_init`] and other shortcut functions register it under [`DEFAULT_HANDLE_NAME`].
+    pub fn register_handle_as(mut self, name: impl Into<String>) -> Self {
+        self.config_mut().handle_name = Some(name.into());
+        self
+    }
+
+    /// Creates a copy of the logger, sharing the caches.
+    ///
+    /// This is cheap, it only clones bunch of `Arc`s.
+    fn share(&self) -> Self {
+        Logger {
+            config: Arc::clone(&self.config),
+            filters: Arc::clone(&self.filters),
+            logging: Arc::clone(&self.logging),
+            cache: Arc::clone(&self.cache),
+            pending: Arc::clone(&self.pending),
+            clock: Arc::clone(&self.clock),
+            metrics: Arc::clone(&self.metrics),
+            watch: Arc::clone(&self.watch),
+        }
+    }
+
+    /// Creates a copy of the logger.
+    ///
+    /// The copy can be configured further and installed (or [built][Logger::build]) independently
+    /// of the original, for example to keep a template configuration around. The filters are
+    /// copied, so changing them through the [`ResetHandle`] of one logger doesn't affect the other.
+    /// Whether the cache is shared is decided by the `cache` parameter.
+    ///
+    /// ```rust
+    /// # use log::LevelFilter;
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{CacheSharing, Caching, Logger};
+    /// Python::with_gil(|py| {
+    ///     let template = Logger::new(py, Caching::LoggersAndLevels)
+    ///         .unwrap()
+    ///         .filter(LevelFilter::Warn);
+    ///     let verbose = template
+    ///         .clone_ref(py, CacheSharing::Forked)
+    ///         .filter(LevelFilter::Info);
+    ///     assert_eq!(template.max_level(), LevelFilter::Warn);
+    ///     assert_eq!(verbose.max_level(), LevelFilter::Info);
+    /// });
+    /// ```
+    pub fn clone_ref(&self, py: Python<'_>, cache: CacheSharing) -> Self {
+        let filters = Filters::clone(&self.filters.load());
+        let mut logger = Logger {
+            config: Arc::clone(&self.config),
+            filters: Arc::new(ArcSwap::from_pointee(filters)),
+            logging: Arc::new(self.logging.clone_ref(py)),
+            ..self.share()
+        };
+        if cache == CacheSharing::Forked {
+            logger.cache = Default::default();
+            logger.pending = Default::default();
+            logger.clock = Default::default();
+            logger.metrics = Default::default();
+            logger.watch = Default::default();
+        }
+        logger
+    }
+
+    /// Finishes the configuration, without installing the logger.
+    ///
+    /// This is for passing the logger to some other logging system that connects multiple loggers
+    /// together. It behaves the same as if it was [installed][Logger::install] directly. Get the
+    /// [`reset_handle`][Logger::reset_handle] and the [`max_level`][Logger::max_level] before
+    /// calling this, if they are needed. Building never touches the global maximum level, but
+    /// consider turning off [`manage_max_level`][Logger::manage_max_level] so the
+    /// [`ResetHandle`] doesn't touch it either.
+    ///
+    /// ```rust
+    /// use log::{Log, Metadata, Record};
+    /// use pyo3_log::Logger;
+    ///
+    /// /// Sends all the records to multiple loggers.
+    /// struct Fanout(Vec<Box<dyn Log>>);
+    ///
+    /// impl Log for Fanout {
+    ///     fn enabled(&self, metadata: &Metadata) -> bool {
+    ///         self.0.iter().any(|l| l.enabled(metadata))
+    ///     }
+    ///
+    ///     fn log(&self, record: &Record) {
+    ///         for l in &self.0 {
+    ///             l.log(record);
+    ///         }
+    ///     }
+    ///
+    ///     fn flush(&self) {
+    ///         for l in &self.0 {
+    ///             l.flush();
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// /// Writes the records to stderr.
+    /// struct Stderr;
+    ///
+    /// impl Log for Stderr {
+    ///     fn enabled(&self, _: &Metadata) -> bool {
+    ///         true
+    ///     }
+    ///
+    ///     fn log(&self, record: &Record) {
+    ///         eprintln!("{}: {}", record.level(), record.args());
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// let logger = Logger::default();
+    /// let handle = logger.reset_handle();
+    /// log::set_boxed_logger(Box::new(Fanout(vec![logger.build(), Box::new(Stderr)]))).unwrap();
+    /// // Stderr takes everything, so allow everything through (otherwise, the max_level of the
+    /// // Python logger would be used here).
+    /// log::set_max_level(log::LevelFilter::max());
+    /// # let _ = handle;
+    /// log::info!("This goes to both Python and stderr");
+    /// ```
+    pub fn build(self) -> Box<dyn Log> {
+        Box::new(self)
+    }
+
+    /// The most verbose level any of the filters may let through.
+    ///
+    /// This is what [`install`][Logger::install] passes to [`log::set_max_level`]. It is useful
+    /// when installing the logger in some other way (see [`build`][Logger::build]). It is capped
+    /// by the [`log::STATIC_MAX_LEVEL`] (and by the `min-level-info` feature), as more verbose
+    /// records can't be logged at all.
+    pub fn max_level(&self) -> LevelFilter {
+        self.filters.load().max_level()
+    }
+
+    /// The Python `logging` module this logger sends the records to.
+    ///
+    /// This is the module imported on construction (or the one passed to
+    /// [`from_module`][Logger::from_module]), so it can be configured further (eg. by
+    /// `basicConfig` or `addLevelName`) without importing it again.
+    ///
+    /// ```rust
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{Caching, Logger};
+    /// # fn main() -> PyResult<()> {
+    /// Python::with_gil(|py| {
+    ///     let logger = Logger::new(py, Caching::LoggersAndLevels)?;
+    ///     logger
+    ///         .logging_module_ref(py)?
+    ///         .call_method1("addLevelName", (5, "TRACE"))?;
+    ///     Ok(())
+    /// })
+    /// # }
+    /// ```
+    ///
+    /// Fails only if the module was not imported yet and the import fails.
+    pub fn logging_module_ref<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyModule>> {
+        Ok(self.logging.bind(py)?.clone())
+    }
+
+    /// Sends an already constructed Python `LogRecord` to the logger of a Rust target.
+    ///
+    /// This is an escape hatch for code that already has Python records (or needs to build them
+    /// with custom attributes). The Python logger is chosen the same way as for the usual
+    /// records (including the [mapping][Logger::map_target], [pinning][Logger::pin_logger],
+    /// [routing][Logger::route_to_handler] and [additional loggers][Logger::tee_target]) and
+    /// cached. The record itself is passed as it is, without `makeRecord` or any processing on
+    /// the Rust side. The Rust-side filters don't apply either, only the level of the Python
+    /// logger (or handler) is checked against the `levelno` of the record.
+    ///
+    /// Returns an error if the object doesn't look like a `LogRecord` (doesn't have `levelno`),
+    /// if the current interpreter is not the one the logger was created in, or if the Python
+    /// side raises.
+    ///
+    /// ```rust
+    /// # use pyo3::prelude::*;
+    /// # use pyo3::types::PyDict;
+    /// # use pyo3_log::{Caching, Logger};
+    /// # fn main() -> PyResult<()> {
+    /// Python::with_gil(|py| {
+    ///     let logger = Logger::new(py, Caching::LoggersAndLevels)?;
+    ///     let attrs = PyDict::new(py);
+    ///     attrs.set_item("levelno", 20)?;
+    ///     attrs.set_item("msg", "Hello")?;
+    ///     attrs.set_item("custom", 42)?;
+    ///     let record = py.import("logging")?.call_method1("makeLogRecord", (attrs,))?;
+    ///     logger.emit_record(py, "my_ext::bridge", record)
+    /// })
+    /// # }
+    /// ```
+    pub fn emit_record(
+        &self,
+        py: Python<'_>,
+        target: &str,
+        record: Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        if !record.hasattr("levelno")? {
+            return Err(PyTypeError::new_err(format!(
+                "{} is not a LogRecord",
+                record.repr()?
+            )));
+        }
+        if !self.own_interpreter(py) {
+            return Err(PyRuntimeError::new_err(
+                "The logger belongs to another interpreter",
+            ));
+        }
+        self.log_pending(py);
+        let levelno = record.getattr("levelno")?;
+        let target = self.config.normalize(target);
+        let method = self.config.dispatch_method.method_name();
+        let takes = |logger: &Bound<'_, PyAny>| -> PyResult<bool> {
+            match self.config.dispatch_method {
+                DispatchMethod::Handle => logger
+                    .call_method1("isEnabledFor", (&levelno,))?
+                    .is_truthy(),
+                DispatchMethod::CallHandlers => Ok(true),
+            }
+        };
+
+        let cache = self.lookup(&target);
+        let cached = cache.as_ref().and_then(|node| node.local.as_ref());
+        let pinned = self.config.pinned.get(&*target);
+        let route = self.config.route.as_deref();
+        // As in log_inner, the pinned and routing loggers are never cached.
+        let (logger, tees, found) = match (pinned, cached, route) {
+            (Some(pinned), _, _) => (
+                Some(pinned.logger.bind(py).clone()),
+                self.uncached_tees(py, &target)?,
+                None,
+            ),
+            (None, _, Some(_)) => (None, self.uncached_tees(py, &target)?, None),
+            (None, Some(cached), None) => (
+                Some(cached.logger.bind(py).clone()),
+                cached
+                    .tees
+                    .iter()
+                    .map(|t| Cow::Owned(t.bind(py).clone()))
+                    .collect(),
+                None,
+            ),
+            (None, None, None) => {
+                let get_logger = self.logging.bind(py)?.getattr("getLogger")?;
+                let name = PyString::new(py, &self.python_name(&target));
+                let logger = self.python_logger(&get_logger, &name)?;
+                let tees = self.tee_loggers(&get_logger, &target)?;
+                let found = (
+                    logger.clone().unbind(),
+                    name.unbind(),
+                    tees.iter()
+                        .map(|t| t.clone().into_owned().unbind())
+                        .collect(),
+                );
+                (Some(logger), tees, Some(found))
+            }
+        };
+
+        match (logger, route) {
+            (Some(logger), _) => {
+                if takes(&logger)? {
+                    logger.call_method1(method, (&record,))?;
+                }
+            }
+            (None, Some(route)) => {
+                let handler = route.handler.bind(py);
+                if levelno.ge(handler.getattr("level")?)? {
+                    handler.call_method1("handle", (&record,))?;
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+        for tee in &tees {
+            if takes(tee)? {
+                tee.call_method1(method, (&record,))?;
+            }
+        }
+        if let Some(found) = found {
+            if self.config.caching_of(&target) != Caching::Nothing {
+                self.cache_found(py, &target, found);
+            }
+        }
+        Ok(())
+    }
+
+    /// Logs a message through this logger directly, bypassing the [`log`] facade.
+    ///
+    /// This is useful when the logger is not installed globally (eg. another one is), but some
+    /// records should still go to Python. The record goes through exactly the same filtering,
+    /// caching and other processing as if it was logged by the [`log`] macros with this logger
+    /// installed (except for the global [`log::max_level`], which isn't consulted).
+    ///
+    /// To log a pre-built [`Record`], use the [`Log::log`] method of the logger.
+    ///
+    /// ```rust
+    /// # use log::Level;
+    /// # use pyo3_log::Logger;
+    /// let logger = Logger::default();
+    /// logger.log_record(Level::Info, "my_ext::audit", format_args!("User {} logged in", "alice"));
+    /// ```
+    pub fn log_record(&self, level: Level, target: &str, args: Arguments<'_>) {
+        self.log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(args)
+                .build(),
         );
-        log::set_boxed_logger(Box::new(self))?;
-        log::set_max_level(level);
-        Ok(handle)
     }
 
     /// Provides the reset handle of this logger.
@@ -338,7 +2034,57 @@ impl Logger {
     /// for example, the logger will be passed to some other logging system that connects multiple
     /// loggers together.
     pub fn reset_handle(&self) -> ResetHandle {
-        ResetHandle(Arc::clone(&self.cache))
+        ResetHandle {
+            cache: Arc::clone(&self.cache),
+            filters: Arc::clone(&self.filters),
+            config: Arc::clone(&self.config),
+            logging: Arc::clone(&self.logging),
+            metrics: Arc::clone(&self.metrics),
+            watch: Arc::clone(&self.watch),
+            installed: None,
+        }
+    }
+
+    /// Changes what is cached.
+    ///
+    /// This allows deciding about the [caching][Caching] later than at the construction (eg. from
+    /// a configuration). The cache is emptied, so switching the mode is safe at any time.
+    ///
+    /// ```rust
+    /// # use pyo3_log::{Caching, Logger};
+    /// let caching = if std::env::var_os("NO_LOG_CACHE").is_some() {
+    ///     Caching::Nothing
+    /// } else {
+    ///     Caching::LoggersAndLevels
+    /// };
+    /// Logger::default().caching(caching);
+    /// ```
+    pub fn caching(mut self, caching: Caching) -> Self {
+        self.config_mut().caching = caching;
+        self.cache.store(Default::default());
+        self
+    }
+
+    /// Changes what is cached for a specific target.
+    ///
+    /// The mode applies to the given target and all its children in the module hierarchy,
+    /// overriding the one set at construction (or by [`caching`][Logger::caching]). If multiple
+    /// match, the most specific one wins. This allows, for example, turning the caching off for a
+    /// subsystem with dynamically generated targets (which would make the cache grow without
+    /// bounds) while the rest still benefits from it.
+    ///
+    /// Like with [`caching`][Logger::caching], the cache is emptied.
+    ///
+    /// ```rust
+    /// # use pyo3_log::{Caching, Logger};
+    /// Logger::default().caching_for("my_crate::sessions", Caching::Nothing);
+    /// ```
+    pub fn caching_for(mut self, target_prefix: impl Into<String>, caching: Caching) -> Self {
+        let target_prefix = target_prefix.into();
+        let target = self.config.filter_key(&target_prefix).into_owned();
+        self.config_mut().caching_for.insert(target, caching);
+        self.cache.store(Default::default());
+        self
     }
 
     /// Configures the default logging filter.
@@ -349,7 +2095,8 @@ impl Logger {
     ///
     /// The default filter if none set is [`Debug`][LevelFilter::Debug].
     pub fn filter(mut self, filter: LevelFilter) -> Self {
-        self.top_filter = filter;
+        check_static_max_level(filter);
+        self.update_filters(|filters| filters.top = filter);
         self
     }
 
@@ -366,275 +2113,6563 @@ impl Logger {
     ///
     /// Logger::default()
     ///     .filter(LevelFilter::Warn)
-    ///     .filter_target("xy".to_owned(), LevelFilter::Debug)
-    ///     .filter_target("xy::aa".to_owned(), LevelFilter::Trace);
+    ///     .filter_target("xy", LevelFilter::Debug)
+    ///     .filter_target("xy::aa", LevelFilter::Trace);
     /// ```
     ///
     /// * `whatever` => `Warn`
     /// * `xy` => `Debug`
     /// * `xy::aa` => `Trace`
     /// * `xy::aabb` => `Debug`
-    pub fn filter_target(mut self, target: String, filter: LevelFilter) -> Self {
-        self.filters.insert(target, filter);
+    ///
+    /// Trailing separators are ignored, so `xy::` is the same as `xy`.
+    ///
+    /// The target can be anything convertible to a `String`, like `&str`, `String` or `Cow<str>`.
+    pub fn filter_target(mut self, target: impl Into<String>, filter: LevelFilter) -> Self {
+        check_static_max_level(filter);
+        let target = target.into();
+        let target = self.config.filter_key(&target).into_owned();
+        self.update_filters(|filters| {
+            filters.targets.insert(target.clone(), filter);
+        });
         self
     }
 
-    /// Finds a node in the cache.
+    /// Sets the filters from `env_logger`-style directives.
     ///
-    /// The hierarchy separator is `::`.
-    fn lookup(&self, target: &str) -> Option<Arc<CacheNode>> {
-        if self.caching == Caching::Nothing {
-            return None;
+    /// The directives are separated by commas. A lone level (`info`) sets the default
+    /// [`filter`][Logger::filter], `target=level` sets a [`filter_target`][Logger::filter_target]
+    /// and a lone target enables everything for it (same as `target=trace`). Levels are case
+    /// insensitive and `off` turns the logging off. Later directives override earlier ones.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// // The same as .filter(Info).filter_target("my_crate", Debug).filter_target("my_crate::db", Off)
+    /// Logger::default().parse_filters("info,my_crate=debug,my_crate::db=off");
+    /// ```
+    ///
+    /// The regex filtering of the messages (anything after a `/`) is not supported and ignored.
+    /// Invalid directives are skipped, with a single warning on the standard error output.
+    pub fn parse_filters(mut self, directives: &str) -> Self {
+        let directives = directives::parse(directives);
+        if !directives.invalid.is_empty() {
+            eprintln!(
+                "pyo3-log: ignoring invalid logging directives: {}",
+                directives.invalid.join(", ")
+            );
         }
+        if let Some(top) = directives.top {
+            self = self.filter(top);
+        }
+        for (target, filter) in directives.targets {
+            self = self.filter_target(target, filter);
+        }
+        self
+    }
 
-        let root = self.cache.load();
+    /// Sets the filters from `env_logger`-style directives in an environment variable.
+    ///
+    /// This is [`parse_filters`][Logger::parse_filters] with the content of the variable (usually
+    /// `RUST_LOG`). If the variable is not set (or is not valid unicode), the filters are left
+    /// untouched.
+    ///
+    /// ```rust
+    /// # use log::LevelFilter;
+    /// # use pyo3_log::Logger;
+    /// Logger::default()
+    ///     .filter(LevelFilter::Warn)
+    ///     .parse_env("RUST_LOG");
+    /// ```
+    pub fn parse_env(self, var: &str) -> Self {
+        self.parse_env_value(std::env::var(var).ok())
+    }
+
+    /// The part of [`parse_env`][Logger::parse_env] after the variable is read.
+    fn parse_env_value(self, directives: Option<String>) -> Self {
+        match directives {
+            Some(directives) => self.parse_filters(&directives),
+            None => self,
+        }
+    }
+
+    /// Sets a level predicate for a specific target.
+    ///
+    /// Unlike [`filter_target`][Logger::filter_target], which lets through everything up to a
+    /// threshold, the predicate decides about each level separately. That allows rules like
+    /// "`Info` and `Error`, but not `Warn`".
+    ///
+    /// The targets are matched the same way as with [`filter_target`][Logger::filter_target] and
+    /// the two kinds of filters share the hierarchy ‒ the most specific match wins, whichever kind
+    /// it is. If both a predicate and a threshold filter are set for the very same target, the
+    /// predicate wins.
+    ///
+    /// ```rust
+    /// # use log::{Level, LevelFilter};
+    /// # use pyo3_log::Logger;
+    ///
+    /// Logger::default()
+    ///     .filter_target_levels("xy", |level| level != Level::Warn)
+    ///     .filter_target("xy::aa", LevelFilter::Trace);
+    /// ```
+    ///
+    /// * `xy` => everything up to `Trace`, except for `Warn`
+    /// * `xy::aa` => `Trace`
+    pub fn filter_target_levels<P>(mut self, target: impl Into<String>, predicate: P) -> Self
+    where
+        P: Fn(Level) -> bool + Send + Sync + 'static,
+    {
+        let target = target.into();
+        let target = self.config.filter_key(&target).into_owned();
+        let predicate: Arc<LevelPredicate> = Arc::new(predicate);
+        self.update_filters(|filters| {
+            filters
+                .level_predicates
+                .insert(target.clone(), Arc::clone(&predicate));
+        });
+        self
+    }
+
+    /// Sets a filter for the targets matching a glob pattern.
+    ///
+    /// The pattern is matched against the whole target, segment by segment (split by the
+    /// [separator][Logger::target_separator]). A `**` segment matches any number of segments
+    /// (including none) and `*` inside a segment matches any part of a single segment. To match
+    /// the children in the module hierarchy too, end the pattern with `::**`.
+    ///
+    /// The glob filters are consulted only if none of the [target
+    /// filters][Logger::filter_target] (including the ones for the parents in the hierarchy)
+    /// matches, so the order of precedence is: the most specific target filter, then a glob
+    /// filter, then the [filter function][Logger::filter_fn] and the [default filter][Logger::filter].
+    /// If multiple glob filters match, the first one set wins. There's no cost if no glob filters
+    /// are set.
+    ///
+    /// ```rust
+    /// # use log::LevelFilter;
+    /// # use pyo3_log::Logger;
+    /// Logger::default()
+    ///     // Any `tests` module, anywhere.
+    ///     .filter_glob("**::tests", LevelFilter::Trace)
+    ///     // Anything in the `internal` modules and their children.
+    ///     .filter_glob("**::internal::**", LevelFilter::Warn)
+    ///     // The `my_crate_v1`, `my_crate_v2`, ... crates.
+    ///     .filter_glob("my_crate_v*::**", LevelFilter::Info);
+    /// ```
+    pub fn filter_glob(mut self, pattern: &str, filter: LevelFilter) -> Self {
+        check_static_max_level(filter);
+        let pattern = self.config.normalize(pattern).into_owned();
+        self.update_filters(|filters| filters.globs.push((pattern.clone(), filter)));
+        self
+    }
+
+    /// Sets a custom filter function.
+    ///
+    /// The function is consulted for targets for which no [`filter_target`][Logger::filter_target]
+    /// matches. If it returns `None` (no opinion), the [default filter][Logger::filter] is used.
+    ///
+    /// ```rust
+    /// # use log::LevelFilter;
+    /// # use pyo3_log::Logger;
+    /// Logger::default().filter_fn(Box::new(|metadata| {
+    ///     if metadata.target().contains("sqlx") {
+    ///         Some(LevelFilter::Warn)
+    ///     } else {
+    ///         None
+    ///     }
+    /// }));
+    /// ```
+    ///
+    /// # Performance
+    ///
+    /// The function runs on every logging call (and every [`enabled`][Log::enabled] check) that
+    /// is not decided by the target filters, before the caches are even consulted. The caches
+    /// store only the Python side of the decision, so the result of the function is never cached
+    /// and it should be fast.
+    ///
+    /// Also, as it is impossible to know what levels the function may allow, installing a logger
+    /// with a filter function sets the [maximum level][log::set_max_level] to `Trace`.
+    pub fn filter_fn(mut self, filter: Box<FilterFn>) -> Self {
+        let filter: Arc<FilterFn> = Arc::from(filter);
+        self.update_filters(|filters| filters.filter_fn = Some(Arc::clone(&filter)));
+        self
+    }
+
+    /// Sets the separator of the hierarchy levels in targets.
+    ///
+    /// The Rust targets are module paths by default and use `::` to separate the levels of the
+    /// hierarchy. If the targets follow a different convention, the separator can be changed here.
+    ///
+    /// The separator is used both for matching the [`filter_target`][Logger::filter_target]
+    /// filters, the internal caches and it is replaced by `.` when constructing the name of the
+    /// Python logger.
+    ///
+    /// Set it before the [`filter_target`][Logger::filter_target] filters, they are normalized
+    /// using the separator at the time they are set.
+    ///
+    /// # Panics
+    ///
+    /// If the separator is empty.
+    pub fn target_separator(mut self, separator: &str) -> Self {
+        assert!(!separator.is_empty(), "Empty target separator");
+        self.config_mut().separator = separator.to_owned();
+        self
+    }
+
+    /// Makes the targets case-insensitive.
+    ///
+    /// With this turned on, all the targets are lowercased before anything else happens, so the
+    /// records are handled as if they were logged with the lowercased target. That includes the
+    /// filters, the internal caches, the name of the Python logger (and the `rust_target`
+    /// attribute of the Python record). This helps if the casing of the targets is inconsistent,
+    /// or if the Python side uses lowercased logger names.
+    ///
+    /// The targets passed to the other configuration methods (eg.
+    /// [`filter_target`][Logger::filter_target] or [`tee_target`][Logger::tee_target]) are
+    /// lowercased too, so set this before them. Same goes for the targets passed to the methods
+    /// of the [`ResetHandle`].
+    ///
+    /// The default is case-sensitive.
+    ///
+    /// ```rust
+    /// # use log::LevelFilter;
+    /// # use pyo3_log::Logger;
+    /// Logger::default()
+    ///     .case_insensitive_targets(true)
+    ///     // Also applies to `My_Crate::Net`
+    ///     .filter_target("my_crate::net", LevelFilter::Trace);
+    /// ```
+    pub fn case_insensitive_targets(mut self, enabled: bool) -> Self {
+        self.config_mut().case_insensitive = enabled;
+        self
+    }
+
+    /// Sets a custom mapping from Rust targets to Python logger names.
+    ///
+    /// The function gets the original Rust target (eg. `my_crate::internal::worker`) and returns
+    /// the full name of the Python logger to use (eg. `myapp.worker`). This replaces the default
+    /// mapping of [separators][Logger::target_separator] to `.`.
+    ///
+    /// The filtering and caching is still done according to the original Rust target. The result
+    /// of the mapping is cached together with the Python logger, therefore the function is called
+    /// only on cache misses (and on every message that passes the filters if caching is turned
+    /// off).
+    ///
+    /// ```rust
+    /// # use std::borrow::Cow;
+    /// # use pyo3_log::Logger;
+    /// Logger::default().map_target(Box::new(|target| {
+    ///     match target.rsplit("::").next() {
+    ///         Some(last) => Cow::Owned(format!("myapp.{}", last)),
+    ///         None => Cow::Borrowed("myapp"),
+    ///     }
+    /// }));
+    /// ```
+    pub fn map_target(mut self, map: Box<MapTarget>) -> Self {
+        self.config_mut().map_target = Some(Arc::from(map));
+        self
+    }
+
+    /// Sets a custom name of the Python records.
+    ///
+    /// The function gets the original Rust target and returns the `name` of the Python
+    /// `LogRecord`. By default, it's the name of the Python logger the record is sent to. With
+    /// this set, the logger is still chosen the usual way (by the [mapping][Logger::map_target]
+    /// and [prefix][Logger::set_prefix]), only the name the record shows differs, for example to
+    /// group records by something else than their routing.
+    ///
+    /// The function is called for each record sent to Python.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().record_name_fn(Box::new(|target| {
+    ///     let krate = target.split("::").next().unwrap_or_default();
+    ///     format!("rust.{}", krate)
+    /// }));
+    /// ```
+    pub fn record_name_fn(mut self, name: Box<RecordName>) -> Self {
+        self.config_mut().record_name = Some(Arc::from(name));
+        self
+    }
+
+    /// Sets a prefix of all the Python logger names.
+    ///
+    /// The prefix is prepended to the name of each Python logger, separated by a `.`. Therefore,
+    /// a Rust target `foo::bar` with a prefix `myext` is logged into the Python logger
+    /// `myext.foo.bar`. The prefix is applied after a [custom mapping][Logger::map_target], if
+    /// any.
+    ///
+    /// The filtering and caching still work with the original Rust targets (the filters don't
+    /// contain the prefix). An empty prefix (the default) means no prefix.
+    pub fn set_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.config_mut().prefix = prefix.into();
+        self
+    }
+
+    /// Sets the name of the Python logger for records with an empty target.
+    ///
+    /// The `log` macros always set the target (to the module path by default), but records built
+    /// by hand or coming from other logging systems may have an empty one. Such target maps to an
+    /// empty name, which is the root logger in Python. With this set, they go to the logger of
+    /// the given name instead. The name replaces the [mapping][Logger::map_target] of the empty
+    /// target, the [prefix][Logger::set_prefix] still applies.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().empty_target_name("rust.unknown");
+    /// ```
+    pub fn empty_target_name(mut self, name: impl Into<String>) -> Self {
+        self.config_mut().empty_target = Some(name.into());
+        self
+    }
+
+    /// Limits the number of cached loggers.
+    ///
+    /// By default, the cache grows with each new target and nothing is ever evicted. That's fine
+    /// for the usual, static, targets. If the targets are generated dynamically (eg. containing
+    /// some IDs), the cache would grow without bounds.
+    ///
+    /// With this set, whenever the number of cached loggers exceeds the limit, the least recently
+    /// used ones are evicted, so only 3/4 of the limit remains (evicting in batches makes the
+    /// eviction cheaper on average). The tracking of the recent use is approximate.
+    pub fn max_cache_entries(mut self, max: usize) -> Self {
+        self.config_mut().max_cache_entries = Some(max);
+        self
+    }
+
+    /// Resets the caches automatically when the Python logging configuration changes.
+    ///
+    /// If turned on, the logger checks for changes (the same way as
+    /// [`reset_if_changed`][ResetHandle::reset_if_changed]) each time it sends a record to
+    /// Python and resets the caches if needed. This makes the caching safe for most cases of
+    /// runtime reconfiguration, for a small cost on each record sent to Python.
+    ///
+    /// Note that records filtered out by the cached levels never reach Python, so a change that
+    /// enables a previously disabled level is detected only after some other record is logged.
+    pub fn watch_reconfiguration(mut self, watch: bool) -> Self {
+        self.config_mut().watch_reconfiguration = watch;
+        self
+    }
+
+    /// Checks the cached levels are still valid before dropping a record because of them.
+    ///
+    /// With [`Caching::LoggersAndLevels`], a record disabled by the cached level of the Python
+    /// logger never reaches Python. If the Python level was lowered in the meantime, the record
+    /// is lost. With this turned on, before dropping such a record, the logger checks if the
+    /// Python logging configuration changed (the same way as
+    /// [`reset_if_changed`][ResetHandle::reset_if_changed]) and if so, resets the caches and asks
+    /// Python again.
+    ///
+    /// This needs the GIL for each record disabled by the cache, so it takes away a big part of
+    /// the performance benefit of caching the levels (the Python loggers are still cached). It
+    /// also means the [`enabled`][Log::enabled] check may need the GIL.
+    ///
+    /// Available with the `validate-cache` feature.
+    #[cfg(feature = "validate-cache")]
+    pub fn validate_cache(mut self, enabled: bool) -> Self {
+        self.config_mut().validate_cache = enabled;
+        self
+    }
+
+    /// Configures what happens with records logged when there's no Python interpreter.
+    ///
+    /// Rust code may log before the interpreter is initialized or after it is finalized (eg. from
+    /// a background thread during the interpreter shutdown). Calling into Python in such case
+    /// would crash, so the records are handled by the given policy instead. The default is
+    /// [`MissingInterpreter::Drop`].
+    ///
+    /// Once the interpreter starts finalizing, it counts as missing too. Therefore, records logged
+    /// during `Py_Finalize` are dropped by default instead of calling into a half torn-down
+    /// interpreter. This is checked by `sys.is_finalizing()` right before the records are sent to
+    /// Python, with the GIL held. Note that getting the GIL during the finalization may block a
+    /// thread other than the one finalizing the interpreter.
+    pub fn on_missing_interpreter(mut self, policy: MissingInterpreter) -> Self {
+        self.config_mut().missing_interpreter = policy;
+        self
+    }
+
+    /// Writes the records to the standard error output if Python logging is not configured.
+    ///
+    /// If there are no handlers on the Python logger and the loggers it propagates to (usually
+    /// because `logging.basicConfig` or similar wasn't called yet), Python prints only the warnings
+    /// and errors (through `logging.lastResort`) and drops the rest silently. With this turned on,
+    /// such records are written to the standard error output instead (in the same format as with
+    /// [`MissingInterpreter::Stderr`]) and not passed to the Python logger at all, so nothing is
+    /// printed twice. Once there are handlers, the records go to Python as usual.
+    ///
+    /// If the logger is [cached][Caching], whether it has handlers is cached with it. Handlers
+    /// added later are therefore noticed only once the cache is [reset][ResetHandle::reset] (or
+    /// by [`reset_if_changed`][ResetHandle::reset_if_changed]). Records that don't pass the Python
+    /// level are still dropped, as are the records sent to a [routed][Logger::route_to_handler]
+    /// handler or to the [tee loggers][Logger::tee_target]. Note that with
+    /// [`ensure_null_handler`][Logger::ensure_null_handler], there's always a handler.
+    ///
+    /// Off by default.
+    pub fn fallback_stderr(mut self, enabled: bool) -> Self {
+        self.config_mut().fallback_stderr = enabled;
+        self
+    }
+
+    /// Sets what happens with errors raised on the Python side during logging.
+    ///
+    /// As the [`Log`] trait has no way to return errors, by default they are left as the current
+    /// Python exception, to be picked up by whatever Python code runs next in the thread (or
+    /// printed to Python's stderr, in the [buffered mode][Logger::buffered]). Depending on the
+    /// environment, this may be noisy, confusing or simply invisible. With this set, the function
+    /// is called instead (eg. to count the failures in a metric or to print them with
+    /// `eprintln!`).
+    ///
+    /// The function is called with the GIL held.
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// use pyo3_log::Logger;
+    ///
+    /// static FAILURES: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// let logger = Logger::default().on_error(Box::new(|e| {
+    ///     FAILURES.fetch_add(1, Ordering::Relaxed);
+    ///     eprintln!("Failed to log: {}", e);
+    /// }));
+    /// # let _ = logger;
+    /// ```
+    pub fn on_error(mut self, handler: Box<ErrorHandler>) -> Self {
+        self.config_mut().on_error = Some(Arc::from(handler));
+        self
+    }
+
+    /// Chooses how the records are passed to the Python loggers.
+    ///
+    /// By default, the level of the Python logger is checked (by `isEnabledFor`) and the record
+    /// is passed to its `handle` method, which applies the filters of the logger. With
+    /// [`DispatchMethod::CallHandlers`], the record is passed to the `callHandlers` method
+    /// instead, bypassing both the level and the filters of the logger. This makes the Rust-side
+    /// [filters][Logger::filter_target] authoritative, eg. when the Python loggers are configured
+    /// by someone else. The levels of the handlers still apply.
+    ///
+    /// This applies to the [additional loggers][Logger::tee_target] too, but not to the
+    /// [routing][Logger::route_to_handler] directly to a handler.
+    ///
+    /// As the cached levels depend on this, the cache is emptied (like with
+    /// [`caching`][Logger::caching]).
+    pub fn dispatch_method(mut self, method: DispatchMethod) -> Self {
+        self.config_mut().dispatch_method = method;
+        self.cache.store(Default::default());
+        self
+    }
+
+    /// Configures what [`flush`][Log::flush] does on the Python side.
+    ///
+    /// By default, all the handlers of all the Python loggers are flushed (which is useful eg. with
+    /// a `MemoryHandler`). See [`FlushPolicy`] for the other options.
+    pub fn flush_policy(mut self, policy: FlushPolicy) -> Self {
+        self.config_mut().flush_policy = policy;
+        self
+    }
+
+    /// Runs a setup of the Python side.
+    ///
+    /// The closure gets the `logging` module the logger sends the records to (see
+    /// [`from_module`][Logger::from_module]), so things like `logging.basicConfig` or registering
+    /// custom level names can be done in the same place the logger is configured. Errors from the
+    /// closure are passed through.
+    ///
+    /// The closure runs right away, before the logger is [installed][Logger::install]. The cache
+    /// is reset afterwards, so nothing cached before can hide the changes.
+    ///
+    /// ```rust
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{Caching, Logger};
+    /// # fn main() -> PyResult<()> {
+    /// Python::with_gil(|py| -> PyResult<()> {
+    ///     let logger = Logger::new(py, Caching::LoggersAndLevels)?
+    ///         .init_python(py, |_py, logging| {
+    ///             logging.call_method1("addLevelName", (5, "TRACE"))?;
+    ///             Ok(())
+    ///         })?;
+    ///     # let _ = logger;
+    ///     Ok(())
+    /// })
+    /// # }
+    /// ```
+    pub fn init_python<F>(self, py: Python<'_>, setup: F) -> PyResult<Self>
+    where
+        F: FnOnce(Python<'_>, &Bound<'_, PyModule>) -> PyResult<()>,
+    {
+        setup(py, self.logging.bind(py)?)?;
+        self.cache.store(Default::default());
+        Ok(self)
+    }
+
+    /// Sets the numeric Python level a Rust level is mapped to.
+    ///
+    /// By default, the levels are mapped to the same-named Python ones and [`Trace`][Level::Trace]
+    /// (which doesn't exist in Python) is mapped to 5. This allows overriding it, for example if
+    /// the application defines its own `TRACE` level through `logging.addLevelName` with a
+    /// different value.
+    ///
+    /// The mapping is used both for the records sent to Python and when checking (and caching)
+    /// whether the Python logger is enabled for the level.
+    ///
+    /// ```rust
+    /// # use log::Level;
+    /// # use pyo3_log::Logger;
+    /// Logger::default().level_map(Level::Trace, 7);
+    /// ```
+    pub fn level_map(mut self, level: Level, python_level: usize) -> Self {
+        self.config_mut().levels[level_index(level)] = python_level;
+        self
+    }
+
+    /// The Rust filter corresponding to a Python level, as understood by this logger.
+    ///
+    /// This is [`level_filter_from_python`], except the names are looked up in the logging module
+    /// of this logger (see [`from_module`][Logger::from_module]) and the levels are compared
+    /// according to its [level mapping][Logger::level_map]. The filter therefore lets through the
+    /// same records this logger would send to a Python logger with that level.
+    ///
+    /// ```rust
+    /// # use log::{Level, LevelFilter};
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{Caching, Logger};
+    /// Python::with_gil(|py| {
+    ///     let logger = Logger::new(py, Caching::LoggersAndLevels)
+    ///         .unwrap()
+    ///         .level_map(Level::Trace, 7);
+    ///     let level = 7_u32.into_pyobject(py).unwrap();
+    ///     let filter = logger.level_filter_from_python(py, level.as_any()).unwrap();
+    ///     assert_eq!(filter, LevelFilter::Trace);
+    /// });
+    /// ```
+    pub fn level_filter_from_python(
+        &self,
+        py: Python<'_>,
+        value: &Bound<'_, PyAny>,
+    ) -> PyResult<LevelFilter> {
+        python_level_filter(self.logging.bind(py)?, &self.config.levels, value)
+    }
+
+    /// Sets a custom function to construct the message passed to Python.
+    ///
+    /// By default, the message is the formatted [`args`][Record::args] of the record. This allows
+    /// changing it, for example to add some context or to truncate huge messages before they are
+    /// passed to Python. The function is called only for records that are actually sent to
+    /// Python.
+    ///
+    /// In the [buffered mode][Logger::buffered], the function is called in the background thread
+    /// and the `args` of the record are already formatted.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().message_formatter(Box::new(|record| {
+    ///     let mut msg = record.args().to_string();
+    ///     msg.truncate(1000);
+    ///     msg
+    /// }));
+    /// ```
+    pub fn message_formatter(mut self, formatter: Box<MessageFormatter>) -> Self {
+        self.config_mut().message_formatter = Some(Arc::from(formatter));
+        self
+    }
+
+    /// Sets a function to redact the messages.
+    ///
+    /// The function gets the message (after the [formatting][Logger::message_formatter]) and
+    /// returns the one passed to Python, with the sensitive parts scrubbed (eg. tokens or email
+    /// addresses replaced). It's called only for records that are actually sent to Python, so the
+    /// cost is bounded. It should return [`Cow::Borrowed`] if there's nothing to redact, to save an
+    /// allocation.
+    ///
+    /// Note that only the message is redacted, not the key-value pairs. To drop some records
+    /// altogether, use [`record_filter`][Logger::record_filter].
+    ///
+    /// ```rust
+    /// # use std::borrow::Cow;
+    /// # use pyo3_log::Logger;
+    /// Logger::default().redact(Box::new(|msg| {
+    ///     if msg.contains("hunter2") {
+    ///         Cow::Owned(msg.replace("hunter2", "*******"))
+    ///     } else {
+    ///         Cow::Borrowed(msg)
+    ///     }
+    /// }));
+    /// ```
+    pub fn redact(mut self, redactor: Box<Redactor>) -> Self {
+        self.config_mut().redact = Some(Arc::from(redactor));
+        self
+    }
+
+    /// Sets a filter over the whole records.
+    ///
+    /// Unlike the [other filters][Logger::filter_fn], which see only the target and level, this
+    /// one gets the whole [`Record`], including the message and the key-value pairs. Records for
+    /// which it returns `false` are dropped. This allows filtering by the content, for example to
+    /// make sure records with some secrets never reach Python.
+    ///
+    /// The `log` crate asks about [enabled][Log::enabled] with the metadata only, so the function
+    /// is called when the record is logged, after the records are filtered by the level (only on
+    /// the Rust side, the Python levels are checked later). It runs before anything else happens
+    /// to the record (eg. [deduplication][Logger::dedupe_window] or
+    /// [sampling][Logger::sample]) and before Python is touched, without holding the GIL. It
+    /// should be fast.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().record_filter(Box::new(|record| {
+    ///     !record.args().to_string().contains("password")
+    /// }));
+    /// ```
+    pub fn record_filter(mut self, filter: Box<RecordFilter>) -> Self {
+        self.config_mut().record_filter = Some(Arc::from(filter));
+        self
+    }
+
+    /// Adds a callback seeing the records sent to Python.
+    ///
+    /// The callback is called with each record that passes all the filters (including
+    /// [sampling][Logger::sample] and [rate limiting][Logger::rate_limit]), right before it's
+    /// sent to Python, without holding the GIL. This allows feeding the records to some Rust-side
+    /// system too, like metrics or an in-memory ring buffer. It must not block, as it delays the
+    /// logging thread.
+    ///
+    /// Multiple taps can be added by calling this repeatedly, they are called in the order they
+    /// were added.
+    ///
+    /// Note that the Python levels are known only if they are [cached][Caching::LoggersAndLevels]
+    /// (and already in the cache), so the tap may see a record that is then dropped by the Python
+    /// logger.
+    ///
+    /// ```rust
+    /// # use std::sync::atomic::{AtomicUsize, Ordering};
+    /// # use std::sync::Arc;
+    /// # use pyo3_log::Logger;
+    /// let errors = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&errors);
+    /// Logger::default().tap(Box::new(move |record| {
+    ///     if record.level() == log::Level::Error {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// }));
+    /// ```
+    pub fn tap(mut self, tap: Box<Tap>) -> Self {
+        self.config_mut().taps.push(Arc::from(tap));
+        self
+    }
+
+    /// Computes the name of the Python logger for the given Rust target.
+    fn python_name<'t>(&self, target: &'t str) -> Cow<'t, str> {
+        self.config.python_name(target)
+    }
+
+    /// Sends the records of a target to an additional Python logger.
+    ///
+    /// The records of the given Rust target (and all its children in the module hierarchy) are
+    /// sent both to the usual Python logger and to the one with the given name (eg. an audit
+    /// logger). The name is used as it is, without any [mapping][Logger::map_target] or
+    /// [prefix][Logger::set_prefix]. Multiple additional loggers may be added for the same
+    /// target.
+    ///
+    /// Each of the Python loggers decides on its own level whether to take the record. The
+    /// `name` of the Python record is the one of the usual logger in both cases.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().tee_target("my_module::payments", "audit".to_owned());
+    /// ```
+    pub fn tee_target(mut self, rust_target: &str, extra_python_logger: String) -> Self {
+        let rust_target = self.config.normalize(rust_target).into_owned();
+        self.config_mut()
+            .tees
+            .entry(rust_target)
+            .or_default()
+            .push(extra_python_logger);
+        self
+    }
+
+    /// Uses the given Python logger for a Rust target.
+    ///
+    /// The records of exactly this target (not its children) go to the pinned logger, without
+    /// looking it up through `getLogger` and without caching it. This saves the lookup on the first
+    /// record of known hot targets. The level of the pinned logger is still checked for each record,
+    /// and the [filters][Logger::filter_target] and [additional loggers][Logger::tee_target] of the
+    /// target apply as usual. The `name` of the Python record is the name of the pinned logger, the
+    /// [mapping][Logger::map_target] and [prefix][Logger::set_prefix] don't apply to it.
+    ///
+    /// Fails if the object doesn't look like a Python logger.
+    ///
+    /// ```rust
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{Caching, Logger};
+    /// # fn main() -> PyResult<()> {
+    /// # Python::with_gil(|py| {
+    /// let hot = py.import("logging")?.getattr("getLogger")?.call1(("hot",))?;
+    /// let logger = Logger::new(py, Caching::LoggersAndLevels)?
+    ///     .pin_logger("my_crate::hot_loop", hot)?;
+    /// # let _ = logger;
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub fn pin_logger(
+        mut self,
+        target: impl Into<String>,
+        logger: Bound<'_, PyAny>,
+    ) -> PyResult<Self> {
+        for method in ["isEnabledFor", "makeRecord", "handle"] {
+            if !logger.hasattr(method)? {
+                return Err(PyAttributeError::new_err(format!(
+                    "Logger {} doesn't have the {} method",
+                    logger.repr()?,
+                    method
+                )));
+            }
+        }
+        let name = logger.getattr("name")?.downcast_into::<PyString>()?;
+        let pinned = PinnedLogger {
+            logger: logger.unbind(),
+            name: name.unbind(),
+        };
+        let target = target.into();
+        let target = self.config.normalize(&target).into_owned();
+        self.config_mut().pinned.insert(target, Arc::new(pinned));
+        Ok(self)
+    }
+
+    /// Sends the records directly to the given Python handler.
+    ///
+    /// Instead of going through the Python loggers, the records are passed straight to the handler
+    /// (for example a `QueueHandler` feeding a bespoke pipeline). The logger hierarchy, including
+    /// the levels of the loggers and the propagation to the parent loggers, is skipped in this
+    /// mode. The records still have the usual `name`, but whether they are passed on is decided
+    /// only by the level (and the filters) of the handler. As there are no loggers to cache, the
+    /// level of the handler is checked on each record.
+    ///
+    /// The [pinned loggers][Logger::pin_logger] and [additional loggers][Logger::tee_target] are
+    /// still used as usual.
+    ///
+    /// Fails if the object doesn't look like a Python handler.
+    ///
+    /// ```rust
+    /// # use pyo3::prelude::*;
+    /// # use pyo3_log::{Caching, Logger};
+    /// # fn main() -> PyResult<()> {
+    /// # Python::with_gil(|py| {
+    /// let handler = py.import("logging")?.getattr("StreamHandler")?.call0()?;
+    /// let logger = Logger::new(py, Caching::LoggersAndLevels)?.route_to_handler(handler)?;
+    /// # let _ = logger;
+    /// # Ok(())
+    /// # })
+    /// # }
+    /// ```
+    pub fn route_to_handler(mut self, handler: Bound<'_, PyAny>) -> PyResult<Self> {
+        for attr in ["handle", "level"] {
+            if !handler.hasattr(attr)? {
+                return Err(PyAttributeError::new_err(format!(
+                    "Handler {} doesn't have the {} attribute",
+                    handler.repr()?,
+                    attr
+                )));
+            }
+        }
+        let logger = self
+            .logging
+            .bind(handler.py())?
+            .getattr("Logger")?
+            .call1(("pyo3_log",))?;
+        let route = Route {
+            handler: handler.unbind(),
+            logger: logger.unbind(),
+        };
+        self.config_mut().route = Some(Arc::new(route));
+        Ok(self)
+    }
+
+    /// Passes the currently handled Python exception with the error records.
+    ///
+    /// If enabled, an [`Error`][Level::Error] record logged while Python handles an exception
+    /// (eg. Rust called from an `except` block) gets it as its `exc_info` (as returned by
+    /// `sys.exc_info()`), so the Python handlers show its traceback. Without an exception being
+    /// handled, the record is passed without `exc_info` as usual.
+    ///
+    /// The exception is per thread, so this works only for records sent to Python from the thread
+    /// that logged them, not in the [buffered mode][Logger::buffered]. An exception from the
+    /// [exception mapping][Logger::with_exception_mapping] takes precedence.
+    ///
+    /// Off by default.
+    pub fn use_current_exception(mut self, enabled: bool) -> Self {
+        self.config_mut().current_exception = enabled;
+        self
+    }
+
+    /// Turns on passing Rust errors as Python exceptions.
+    ///
+    /// If enabled, a record carrying a Rust error under the `error` key (for example one logged by
+    /// [`log_error`]) gets the error chain converted into a Python `RuntimeError` and passed as
+    /// the `exc_info` of the Python record. Python handlers then show it as an exception.
+    ///
+    /// This is off by default, in which case the records are not inspected for errors at all.
+    #[cfg(feature = "kv")]
+    pub fn with_exception_mapping(mut self, enabled: bool) -> Self {
+        self.config_mut().exception_mapping = enabled;
+        self
+    }
+
+    /// Passes the record to Python as a template with arguments, where possible.
+    ///
+    /// Python's `logging` keeps the message template (`msg`) and its arguments (`args`)
+    /// separately and formats them only when needed. The Rust format strings, on the other hand,
+    /// are compiled into the code and the [`Record`] carries only the pre-captured [`Arguments`],
+    /// so the original template is not available at runtime and the message is always formatted
+    /// on the Rust side.
+    ///
+    /// What can be preserved are the structured key-value pairs. With this turned on, a record
+    /// with key-value pairs gets them as a mapping in the `args` of the Python record (in addition
+    /// to the `extra`). The `msg` is then a template (with any `%` escaped as `%%`), so Python
+    /// handlers formatting it (or inspecting `msg` and `args` separately, eg. for grouping
+    /// similar records) still get the same message. Records without key-value pairs are passed
+    /// the usual way.
+    ///
+    /// Off by default.
+    #[cfg(feature = "kv")]
+    pub fn preserve_template(mut self, enabled: bool) -> Self {
+        self.config_mut().preserve_template = enabled;
+        self
+    }
+
+    /// Passes the structured key-value pairs as nested Python structures.
+    ///
+    /// By default, only numbers, booleans and strings are converted to their Python
+    /// counterparts, anything else is passed as its `Display` representation. With this turned
+    /// on, values serializable by `serde` (eg. captured by `log::info!(user:serde = user; ...)`)
+    /// are converted to the corresponding Python structures, the same ones `json.loads` would
+    /// create (dicts, lists, numbers, strings, booleans and `None`). So handlers like
+    /// `python-json-logger` can output them as proper nested JSON. Values that fail to serialize
+    /// are passed as their `Display` representation.
+    ///
+    /// Off by default. Available with the `kv` and `serde` features.
+    #[cfg(all(feature = "kv", feature = "serde"))]
+    pub fn json_extra(mut self, enabled: bool) -> Self {
+        self.config_mut().json_extra = enabled;
+        self
+    }
+
+    /// Switches the logger to the buffered mode.
+    ///
+    /// By default, the messages are sent to Python right away from the thread that logs them.
+    /// This needs the GIL, which might be a problem when logging a lot from multiple threads
+    /// (they all fight over the GIL).
+    ///
+    /// In the buffered mode, the records that pass the filters (and the cached levels) are copied
+    /// and put into a queue of the given capacity. A background thread acquires the GIL once in a
+    /// while and sends all the queued records to Python in one go. If the queue is full, further
+    /// records are dropped (blocking the application instead would be worse).
+    ///
+    /// Records logged by a single thread reach Python in the same order they were logged in.
+    /// They carry the ID of the process they were logged in as `process`, even if they reach
+    /// Python in another one (eg. after a fork). The `processName` is filled in by Python when
+    /// the record reaches it, so it may be stale in such case.
+    ///
+    /// The [`flush`][Log::flush] waits for all the records queued so far to reach Python. It is a
+    /// good idea to call it before the program terminates (eg. through [`log::logger`]), as the
+    /// queue is not drained otherwise. It releases the GIL while waiting if it is held by the
+    /// current thread.
+    ///
+    /// The background thread starts right away and terminates once the logger (and all its
+    /// copies) are dropped.
+    ///
+    /// # Panics
+    ///
+    /// If the background thread can't be started.
+    pub fn buffered(mut self, capacity: usize) -> Self {
+        self.config_mut().buffer = Some(Arc::new(Buffer::new(capacity)));
+        self
+    }
+
+    /// Makes the logging safe to use from async tasks.
+    ///
+    /// Acquiring the GIL inline in an async task (eg. a tokio one) may block the executor thread
+    /// for a long time, stalling all the other tasks on it. When turned on, the records are handed
+    /// over to a dedicated thread instead, so logging never waits for the GIL.
+    ///
+    /// This is a shorthand for the [buffered mode][Logger::buffered] with a queue of
+    /// [`ASYNC_FRIENDLY_CAPACITY`] records (an already buffered logger is kept as it is). The same
+    /// caveats apply: records logged from one thread keep their order, but they may interleave
+    /// with records other loggers (or Python itself) send to the Python side directly. Also note
+    /// that tasks may move between threads at the `.await` points. Turning it off switches back
+    /// to logging inline.
+    ///
+    /// # Panics
+    ///
+    /// If the background thread can't be started.
+    pub fn async_friendly(mut self, enabled: bool) -> Self {
+        match (enabled, self.config.buffer.is_some()) {
+            (true, false) => self.buffered(ASYNC_FRIENDLY_CAPACITY),
+            (false, true) => {
+                self.config_mut().buffer = None;
+                self
+            }
+            _ => self,
+        }
+    }
+
+    /// Suppresses repeated messages.
+    ///
+    /// If the same message (as formatted from the arguments, without the key-value pairs) is
+    /// logged again with the same target and level within the window, it is not passed to Python.
+    /// Instead, once the window closes or a different message comes, a summary record ("last
+    /// message repeated N times") is logged. The summary can be a bit late ‒ it is logged by the
+    /// next record to come (of any target) after the window closes. Any pending summaries are
+    /// logged on [flush][Log::flush].
+    ///
+    /// This is useful to reduce the noise from tight loops, similar to what syslog daemons do.
+    /// Note that it adds some overhead to every record (the message is formatted and hashed even
+    /// if Python throws it away later).
+    pub fn dedupe_window(mut self, window: Duration) -> Self {
+        self.config_mut().dedupe = Some(Arc::new(Dedupe::new(window)));
+        self
+    }
+
+    /// Limits the number of records per second of a target.
+    ///
+    /// The limit applies to the given target and all its children in the module hierarchy
+    /// (together). If multiple limits match, the most specific one is used. Records over the limit
+    /// are dropped and counted in the [metrics][ResetHandle::metrics]. Short bursts are allowed,
+    /// up to one second worth of records at once.
+    ///
+    /// This protects the rest of the application against a misbehaving component flooding the
+    /// Python logging (and holding the GIL for that). The limiting happens after the
+    /// [filtering][Logger::filter_target] (and after checking the cached Python levels, if any),
+    /// so records thrown away anyway don't count, but before the record is sent to Python.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().rate_limit("my_crate::noisy", 100);
+    /// ```
+    pub fn rate_limit(mut self, target_prefix: impl Into<String>, max_per_sec: u32) -> Self {
+        let target_prefix = target_prefix.into();
+        let target = self.config.filter_key(&target_prefix).into_owned();
+        self.config_mut()
+            .rate_limits
+            .insert(target, Arc::new(RateLimit::new(max_per_sec)));
+        self
+    }
+
+    /// Admits only a random sample of the records of a target.
+    ///
+    /// Each record of the given target and its children in the module hierarchy is admitted with
+    /// the probability of `rate` (eg. `0.01` lets through about 1% of them). If multiple sampling
+    /// rates match, the most specific one is used. The records not admitted are counted in the
+    /// [metrics][ResetHandle::metrics].
+    ///
+    /// This allows keeping a very verbose logging (eg. for profiling) on, without paying the cost
+    /// of sending every record to Python. The decision is made without taking the GIL, using a
+    /// cheap per-thread random generator (not suitable for anything else than this).
+    ///
+    /// The sampling happens after the [filtering][Logger::filter_target] (and after checking the
+    /// cached Python levels, if any), so it is a fraction of the records that pass the level
+    /// filters. It happens before the [rate limiting][Logger::rate_limit], so the limit counts
+    /// only the admitted records.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().sample("my_crate::profiling", 0.01);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the `rate` is not in the `(0, 1]` range.
+    pub fn sample(mut self, target_prefix: impl Into<String>, rate: f64) -> Self {
+        let sample = Arc::new(Sample::new(rate));
+        let target_prefix = target_prefix.into();
+        let target = self.config.filter_key(&target_prefix).into_owned();
+        self.config_mut().samples.insert(target, sample);
+        self
+    }
+
+    /// Passes the time the record was logged in Rust to Python.
+    ///
+    /// By default, the time of the Python `LogRecord` is when it was created on the Python side.
+    /// In the [buffered mode][Logger::buffered] (or with the
+    /// [`gil_timeout`][Logger::gil_timeout]), this might be considerably later than when the
+    /// record was logged in Rust. With this turned on, the time is taken when logging and set as
+    /// the `created` (and the derived `msecs` and `relativeCreated`) attribute of the Python
+    /// record, so the formatters (including `%(asctime)s`) show it.
+    ///
+    /// Records kept while there's [no interpreter][Logger::on_missing_interpreter] also get
+    /// their original time.
+    pub fn with_timestamps(mut self, enabled: bool) -> Self {
+        self.config_mut().timestamps = enabled;
+        self
+    }
+
+    /// Attaches contextual fields to all the records logged from the current thread.
+    ///
+    /// The fields are put into the `extra` of the Python records (so they become attributes of
+    /// the `LogRecord`), until the returned guard is dropped. This is similar to Python's
+    /// `LoggerAdapter`, but without passing the adapter around. The context is per thread and
+    /// applies to all the loggers of this crate (it doesn't leak into other threads, though
+    /// records handed over to a background thread keep the context they were logged with).
+    ///
+    /// The key-value pairs of the record take precedence over the context and the inner (later
+    /// pushed) contexts take precedence over the outer ones. Keys colliding with the attributes
+    /// of the `LogRecord` (which Python refuses in `extra`) are prefixed by `kv_`, the same way
+    /// as the key-value pairs.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    ///
+    /// use pyo3::prelude::*;
+    /// use pyo3::IntoPyObjectExt;
+    /// use pyo3_log::Logger;
+    ///
+    /// fn handle_request(id: u64) -> PyResult<()> {
+    ///     let fields = Python::with_gil(|py| -> PyResult<_> {
+    ///         let mut fields = HashMap::new();
+    ///         fields.insert("request_id".to_owned(), id.into_py_any(py)?);
+    ///         Ok(fields)
+    ///     })?;
+    ///     let _context = Logger::push_context(fields);
+    ///     log::info!("Handling the request");
+    ///     Ok(())
+    /// }
+    /// # handle_request(42).unwrap();
+    /// ```
+    pub fn push_context(fields: HashMap<String, PyObject>) -> ContextGuard {
+        context::push(fields)
+    }
+
+    /// Passes the Rust thread the record was logged from to Python.
+    ///
+    /// Python fills in the `thread` and `threadName` attributes of the `LogRecord` from the Python
+    /// thread creating it. For Rust threads unknown to Python, this is some dummy thread (and in
+    /// the [buffered mode][Logger::buffered], it's the background thread). With this turned on,
+    /// these are overwritten by the Rust thread name and id. Unnamed Rust threads are called
+    /// `<unnamed>`.
+    ///
+    /// The ids are numbers assigned by this library to the Rust threads, the same on all
+    /// platforms. They are unique within the process (and stable for the lifetime of the thread),
+    /// but they don't match the OS thread ids nor the ids of the Python threads.
+    pub fn with_thread_info(mut self, enabled: bool) -> Self {
+        self.config_mut().thread_info = enabled;
+        self
+    }
+
+    /// Tags all the records of this logger with a source id.
+    ///
+    /// The id is passed to Python in the `ext_source` attribute of each record (through the
+    /// `extra` of `makeRecord`). This helps to tell which extension produced a record in case
+    /// multiple Rust extensions, each with its own logger, log into the same Python handlers.
+    ///
+    /// A key-value pair or a [context][Logger::push_context] field of the same name is passed as
+    /// `kv_ext_source` instead, so the source id can be relied on.
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// Logger::default().source_id("my_extension");
+    /// ```
+    pub fn source_id(mut self, id: impl Into<String>) -> Self {
+        self.config_mut().source_id = Some(id.into());
+        self
+    }
+
+    /// Captures the Rust backtraces of the severe records.
+    ///
+    /// For the records of the given level or more severe, a backtrace is captured when the record
+    /// is logged and passed to Python (in its `Display` form) in the `rust_backtrace` attribute of
+    /// the record. This helps to find where an error logged from Rust originated.
+    ///
+    /// The backtraces are captured with [`Backtrace::capture`][std::backtrace::Backtrace::capture],
+    /// therefore only if enabled by the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment
+    /// variables. If they are disabled, nothing is passed. The capture is expensive, it happens
+    /// only for the records that passed all the filters.
+    ///
+    /// As with the [source id][Logger::source_id], a key-value pair or a context field of the
+    /// same name is passed as `kv_rust_backtrace`.
+    ///
+    /// Available with the `backtrace` feature (which needs Rust 1.65).
+    ///
+    /// ```rust
+    /// # use log::Level;
+    /// # use pyo3_log::Logger;
+    /// Logger::default().capture_backtrace(Level::Error);
+    /// ```
+    #[cfg(feature = "backtrace")]
+    pub fn capture_backtrace(mut self, min_level: Level) -> Self {
+        self.config_mut().backtrace_level = Some(min_level);
+        self
+    }
+
+    /// Computes the levels of the Python loggers from their effective level.
+    ///
+    /// To find out (and cache) which levels a Python logger takes, it is asked about each of the
+    /// five Rust levels by `isEnabledFor`. With this turned on, it is instead asked for
+    /// `getEffectiveLevel` once and the result is mapped to the Rust levels (taking the
+    /// [custom mapping][Logger::level_map], `logging.disable` and the `disabled` flag of the
+    /// logger into account). This is faster, but doesn't work with custom logger classes that
+    /// override `isEnabledFor` in some other way.
+    ///
+    /// Off by default.
+    pub fn use_effective_level(mut self, enabled: bool) -> Self {
+        self.config_mut().use_effective_level = enabled;
+        self
+    }
+
+    /// Attaches a `NullHandler` to the Python loggers that have no handlers.
+    ///
+    /// If a record is logged before the Python application configures logging, Python falls back
+    /// to its "last resort" handler, printing the warnings and errors to stderr (or, with older
+    /// Pythons, warns that no handlers could be found). This is the usual confusion about odd
+    /// messages at import time of an extension.
+    ///
+    /// With this turned on, when a Python logger is looked up the first time and neither it nor
+    /// any of its (propagating) parents has a handler, a `logging.NullHandler` is attached to it.
+    /// The check is done once per looked-up logger (unless [caching][Caching] is turned off).
+    ///
+    /// Off by default.
+    pub fn ensure_null_handler(mut self, enabled: bool) -> Self {
+        self.config_mut().ensure_null_handler = enabled;
+        self
+    }
+
+    /// Limits how long logging may wait for the GIL.
+    ///
+    /// If a thread holds the GIL and waits for another thread that logs (see the deadlock
+    /// [example][crate#interaction-with-python-gil]), the logging would hang forever. With this
+    /// set, a thread not holding the GIL hands the record over to a background thread and waits
+    /// for it to be logged at most the given time. If it takes longer, a warning about a possible
+    /// deadlock is printed to the standard error output (once) and the thread continues without
+    /// waiting. The record stays queued and reaches Python once the GIL is released.
+    ///
+    /// The tradeoffs are:
+    ///
+    /// * Each logged record is copied and handed over to another thread, which is slower than
+    ///   logging directly.
+    /// * Records that timed out may reach Python later than records logged afterwards by other
+    ///   threads.
+    /// * At most 1024 records may be waiting for the GIL, further ones are dropped.
+    ///
+    /// Threads already holding the GIL log directly. This has no effect in the
+    /// [buffered mode][Logger::buffered], which never waits for the GIL.
+    ///
+    /// Properly releasing the GIL (with [`Python::allow_threads`]) before waiting for other
+    /// threads is still the preferred solution, this is a safety net.
+    ///
+    /// # Panics
+    ///
+    /// If the background thread can't be started.
+    pub fn gil_timeout(mut self, timeout: Duration) -> Self {
+        self.config_mut().gil_timeout = Some(Arc::new(GilTimeout {
+            timeout,
+            worker: Buffer::new(GIL_TIMEOUT_CAPACITY),
+            warned: AtomicBool::new(false),
+        }));
+        self
+    }
+
+    /// Finds a node in the cache.
+    fn lookup(&self, target: &str) -> Option<Arc<CacheNode>> {
+        self.config.lookup(&self.cache, target)
+    }
+
+    /// Logs stuff
+    ///
+    /// Returns a logger and its name to be cached, if any. If it already found a cached logger or
+    /// if caching is turned off, returns None.
+    fn log_inner(
+        &self,
+        py: Python<'_>,
+        record: &Record,
+        cache: &Option<Arc<CacheNode>>,
+        origin: Option<&Origin>,
+    ) -> PyResult<Option<FoundLoggers>> {
+        let cached = cache.as_ref().and_then(|node| node.local.as_ref());
+        let pinned = self.config.pinned.get(record.target());
+        // Unless pinned, the logger is the cached one (if any).
+        let cached_has_handlers = match pinned {
+            None => cached.map(|entry| entry.has_handlers),
+            Some(_) => None,
+        };
+        let route = self.config.route.as_deref();
+        // The handler to send the record to directly, instead of the logger.
+        let handler = match (pinned, route) {
+            (None, Some(route)) => Some(route.handler.bind(py)),
+            _ => None,
+        };
+        // On a cache hit, we only borrow the cached objects, to save on the reference counting.
+        // The pinned and routing loggers are borrowed too and never cached (as if they already
+        // were).
+        let (logger, target, tees, cached): (Cow<Bound<PyAny>>, Cow<Bound<PyString>>, Vec<_>, _) =
+            match (pinned, cached, route) {
+                (Some(pinned), _, _) => (
+                    Cow::Borrowed(pinned.logger.bind(py)),
+                    Cow::Borrowed(pinned.name.bind(py)),
+                    self.uncached_tees(py, record.target())?,
+                    true,
+                ),
+                (None, _, Some(route)) => (
+                    Cow::Borrowed(route.logger.bind(py)),
+                    Cow::Owned(PyString::new(py, &self.python_name(record.target()))),
+                    self.uncached_tees(py, record.target())?,
+                    true,
+                ),
+                (None, Some(cached), None) => (
+                    Cow::Borrowed(cached.logger.bind(py)),
+                    Cow::Borrowed(cached.name.bind(py)),
+                    cached
+                        .tees
+                        .iter()
+                        .map(|t| Cow::Borrowed(t.bind(py)))
+                        .collect(),
+                    true,
+                ),
+                (None, None, None) => {
+                    let get_logger = self.logging.bind(py)?.getattr("getLogger")?;
+                    let target = PyString::new(py, &self.python_name(record.target()));
+                    let logger = self.python_logger(&get_logger, &target)?;
+                    let tees = self.tee_loggers(&get_logger, record.target())?;
+                    (Cow::Owned(logger), Cow::Owned(target), tees, false)
+                }
+            };
+        // With the levels cached, the cache entry already admitted the record and is
+        // authoritative, so the logger doesn't have to be asked again. That holds only if the
+        // logger is the only destination the level was computed from (and the record is not let
+        // through regardless of the level, eg. by the cache validation).
+        let admitted_by_cache = pinned.is_none()
+            && route.is_none()
+            && cache
+                .as_ref()
+                .and_then(|node| node.local.as_ref())
+                .map_or(false, |entry| {
+                    entry.tees.is_empty()
+                        && record.level() <= entry.filter
+                        && self.config.caching_of(record.target()) == Caching::LoggersAndLevels
+                });
+        // We need to check for this ourselves. For some reason, the logger.handle does not check
+        // it. And besides, we can save ourselves few python calls if it's turned off.
+        let enabled = match handler {
+            Some(handler) => {
+                let level = self.config.levels[level_index(record.level())];
+                level >= handler.getattr("level")?.extract::<usize>()?
+            }
+            None if admitted_by_cache => true,
+            None => self.config.python_enabled_for(&logger, record.level())?,
+        };
+        let mut enabled_tees = Vec::new();
+        for tee in &tees {
+            if self.config.python_enabled_for(tee, record.level())? {
+                enabled_tees.push(tee);
+            }
+        }
+        let handled = enabled || !enabled_tees.is_empty();
+        // Python would use the lastResort handler (or drop the record), so it's ours to print.
+        let to_stderr = enabled
+            && handler.is_none()
+            && self.config.fallback_stderr
+            && !match cached_has_handlers {
+                Some(cached) => cached,
+                None => has_handlers(&logger)?,
+            };
+        if handled {
+            // Format only after we know it's going to be used, Display of the arguments might be
+            // expensive.
+            let mut msg = match &self.config.message_formatter {
+                Some(formatter) => formatter(record),
+                None => format!("{}", record.args()),
+            };
+            if let Some(redact) = &self.config.redact {
+                let redacted = match redact(&msg) {
+                    Cow::Borrowed(_) => None,
+                    Cow::Owned(redacted) => Some(redacted),
+                };
+                if let Some(redacted) = redacted {
+                    msg = redacted;
+                }
+            }
+            if to_stderr {
+                eprintln!("[{}] {}: {}", record.level(), record.target(), msg);
+                // Nothing else wants a Python record.
+                if enabled_tees.is_empty() {
+                    return Ok(self.loggers_to_cache(record, cached, handled, logger, target, tees));
+                }
+            }
+            #[cfg(feature = "kv")]
+            let mut extra = kv::extra(py, record.key_values(), self.config.kv_conversion())?;
+            #[cfg(not(feature = "kv"))]
+            let mut extra: Option<Bound<'_, PyDict>> = None;
+            if let Some(origin) = origin {
+                if !origin.context.is_empty() {
+                    let extra = extra.get_or_insert_with(|| PyDict::new(py));
+                    context::merge(extra, &origin.context)?;
+                }
+                #[cfg(feature = "backtrace")]
+                if let Some(backtrace) = &origin.backtrace {
+                    let extra = extra.get_or_insert_with(|| PyDict::new(py));
+                    set_own_extra(extra, RUST_BACKTRACE, backtrace)?;
+                }
+            }
+            if let Some(id) = &self.config.source_id {
+                let extra = extra.get_or_insert_with(|| PyDict::new(py));
+                set_own_extra(extra, SOURCE_ID, id)?;
+            }
+            #[cfg(feature = "kv")]
+            let exc_info = if self.config.exception_mapping {
+                kv::exc_info(py, record.key_values())?
+            } else {
+                None
+            };
+            #[cfg(not(feature = "kv"))]
+            let exc_info: Option<Bound<'_, PyTuple>> = None;
+            let exc_info = match exc_info {
+                None if self.config.current_exception && record.level() == Level::Error => {
+                    current_exception(py)?
+                }
+                exc_info => exc_info,
+            };
+            #[cfg(feature = "kv")]
+            let args = if self.config.preserve_template {
+                kv::args(py, record.key_values(), self.config.kv_conversion())?
+            } else {
+                None
+            };
+            #[cfg(not(feature = "kv"))]
+            let args: Option<Bound<'_, PyDict>> = None;
+            let (msg, args) = match args {
+                // The message becomes a template (with nothing to substitute, so it needs
+                // escaping) and the key-value pairs its arguments.
+                Some(args) => (msg.replace('%', "%%"), PyTuple::new(py, [args])?),
+                None => (msg, PyTuple::empty(py)),
+            };
+            let record_name = self
+                .config
+                .record_name
+                .as_ref()
+                .map(|name| PyString::new(py, &name(record.target())));
+            let make_record = |extra| {
+                logger.call_method1(
+                    "makeRecord",
+                    (
+                        record_name.as_ref().unwrap_or(&target),
+                        self.config.levels[level_index(record.level())],
+                        // Python itself uses these for unknown locations (a None would show up
+                        // as "None" in the output).
+                        record.file().unwrap_or_default(),
+                        record.line().unwrap_or_default(),
+                        msg.as_str(),
+                        &args,
+                        exc_info.as_ref(),
+                        record.module_path(), // func
+                        extra,
+                    ),
+                )
+            };
+            let py_record = match (make_record(extra.clone()), &extra) {
+                // The known reserved keys are prefixed already, but a custom record factory may
+                // add more. Rather than losing the record, pass all the keys prefixed.
+                (Err(e), Some(extra)) if e.is_instance_of::<PyKeyError>(py) => {
+                    if !EXTRA_REFUSED_WARNED.swap(true, Ordering::Relaxed) {
+                        eprintln!(
+                            "pyo3-log: makeRecord refused the extra keys ({}), passing them \
+                             prefixed by {}",
+                            e, RESERVED_PREFIX
+                        );
+                    }
+                    make_record(Some(prefix_extra(extra)?))?
+                }
+                (result, _) => result?,
+            };
+            // Python derives the module from the file name, which is not very useful for Rust.
+            if let Some(module) = record.module_path() {
+                py_record.setattr("module", module)?;
+            }
+            // Keep the original target around, unless the key-value pairs already set it.
+            if !py_record.hasattr(RUST_TARGET)? {
+                py_record.setattr(RUST_TARGET, record.target())?;
+            }
+            if let Some(origin) = origin {
+                if self.config.timestamps {
+                    set_created(self.logging.bind(py)?, &py_record, origin.created)?;
+                }
+                if self.config.thread_info {
+                    set_thread(&py_record, origin)?;
+                }
+                // Python fills in the current process, which is not the one the record was
+                // logged in if a buffered record crossed a fork.
+                if origin.pid != process::id() {
+                    py_record.setattr("process", origin.pid)?;
+                }
+            }
+            let method = self.config.dispatch_method.method_name();
+            if enabled && !to_stderr {
+                match handler {
+                    Some(handler) => handler.call_method1("handle", (&py_record,))?,
+                    None => logger.call_method1(method, (&py_record,))?,
+                };
+            }
+            for tee in enabled_tees {
+                tee.call_method1(method, (&py_record,))?;
+            }
+        }
+
+        Ok(self.loggers_to_cache(record, cached, handled, logger, target, tees))
+    }
+
+    /// The loggers [`log_inner`][Logger::log_inner] should return for caching, if any.
+    fn loggers_to_cache(
+        &self,
+        record: &Record,
+        cached: bool,
+        handled: bool,
+        logger: Cow<Bound<PyAny>>,
+        target: Cow<Bound<PyString>>,
+        tees: Vec<Cow<Bound<PyAny>>>,
+    ) -> Option<FoundLoggers> {
+        // Without the levels, caching a logger that doesn't log anything wouldn't save us the GIL
+        // anyway, so don't waste the cache on it.
+        let cache_logger = match self.config.caching_of(record.target()) {
+            _ if cached => false,
+            Caching::Nothing => false,
+            Caching::Loggers => handled,
+            Caching::LoggersAndLevels => true,
+        };
+        if cache_logger {
+            // Not cached, so these are owned already and into_owned doesn't clone.
+            let tees = tees.into_iter().map(|t| t.into_owned().unbind()).collect();
+            Some((
+                logger.into_owned().unbind(),
+                target.into_owned().unbind(),
+                tees,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// The filter for the target, not considering the filter function.
+    #[cfg(test)]
+    fn filter_for(&self, target: &str) -> LevelFilter {
+        let filters = self.filters.load();
+        self.config
+            .target_filter(&filters, target)
+            .or_else(|| self.config.glob_filter(&filters, target))
+            .unwrap_or(filters.top)
+    }
+
+    #[cfg(all(test, not(feature = "min-level-info")))]
+    fn filter_for_metadata(&self, metadata: &Metadata) -> LevelFilter {
+        self.config
+            .filter_for_metadata(&self.filters.load(), metadata)
+    }
+
+    /// Sends the record on its way to Python, assuming the Rust-side filters already passed.
+    fn dispatch(&self, record: &Record) {
+        let cache = self.lookup(record.target());
+        if !self.python_enabled(record.metadata(), &cache) {
+            return;
+        }
+        if let Some(sample) = self.config.sample(record.target()) {
+            if !sample.admit() {
+                Metrics::inc(&self.metrics.dropped_sampled);
+                return;
+            }
+        }
+        if let Some(limit) = self.config.rate_limit(record.target()) {
+            if !limit.take() {
+                Metrics::inc(&self.metrics.dropped_rate_limited);
+                return;
+            }
+        }
+
+        for tap in &self.config.taps {
+            tap(record);
+        }
+
+        let origin = if self.config.timestamps
+            || self.config.thread_info
+            || context::active()
+            || self.config.wants_backtrace(record.level())
+        {
+            Some(self.origin(record))
+        } else {
+            None
+        };
+        match &self.config.buffer {
+            Some(buffer) => buffer.send(self.share(), record),
+            None if !interpreter_initialized() => self.log_missing_interpreter(record),
+            None => match &self.config.gil_timeout {
+                Some(gil_timeout) if !gil_held() => self.log_timeout(gil_timeout, record),
+                _ => Python::with_gil(|py| {
+                    self.log_pending(py);
+                    self.log_locked(py, record, &cache, origin.as_ref());
+                }),
+            },
+        }
+    }
+
+    /// When and where the record is being logged.
+    fn origin(&self, record: &Record) -> Origin {
+        let origin = Origin::current();
+        #[cfg(feature = "backtrace")]
+        let origin = Origin {
+            backtrace: capture_backtrace(self.config.wants_backtrace(record.level())),
+            ..origin
+        };
+        #[cfg(not(feature = "backtrace"))]
+        let _ = record;
+        origin
+    }
+
+    /// Checks the Rust-side filters.
+    fn rust_enabled(&self, metadata: &Metadata) -> bool {
+        self.config.rust_enabled(&self.filters.load(), metadata)
+    }
+
+    /// Checks the cached Python levels, assuming the Rust-side filters already passed.
+    fn python_enabled(&self, metadata: &Metadata, cache: &Option<Arc<CacheNode>>) -> bool {
+        let cache_filter = cache
+            .as_ref()
+            .and_then(|node| node.local.as_ref())
+            .map(|local| {
+                if self.config.max_cache_entries.is_some() {
+                    local.touch(self.clock.load(Ordering::Relaxed));
+                }
+                local.filter
+            })
+            .unwrap_or_else(LevelFilter::max);
+
+        #[cfg(feature = "validate-cache")]
+        {
+            metadata.level() <= cache_filter || (self.config.validate_cache && self.cache_stale())
+        }
+        #[cfg(not(feature = "validate-cache"))]
+        {
+            metadata.level() <= cache_filter
+        }
+    }
+
+    /// Checks if the Python logging configuration changed, resetting the cache if so.
+    #[cfg(feature = "validate-cache")]
+    fn cache_stale(&self) -> bool {
+        if !interpreter_initialized() {
+            return false;
+        }
+        // Don't risk the deadlock the timeout is supposed to prevent.
+        if self.config.gil_timeout.is_some() && !gil_held() {
+            return false;
+        }
+
+        // Another interpreter has its own logging module, we can't tell anything about ours.
+        let changed = Python::with_gil(|py| {
+            current_interpreter(py) == self.config.interpreter
+                && config_changed(py, &self.logging, &self.watch)
+        });
+        if changed {
+            self.cache.store(Default::default());
+        }
+        changed
+    }
+
+    /// Handles a record logged when there's no interpreter.
+    fn log_missing_interpreter(&self, record: &Record) {
+        match self.config.missing_interpreter {
+            MissingInterpreter::Drop => Metrics::inc(&self.metrics.dropped_missing_interpreter),
+            MissingInterpreter::Buffer => {
+                let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+                if pending.len() < MISSING_INTERPRETER_CAPACITY {
+                    pending.push(OwnedRecord::new(record, self.origin(record)));
+                } else {
+                    Metrics::inc(&self.metrics.dropped_overflow);
+                }
+            }
+            MissingInterpreter::Stderr => {
+                eprintln!(
+                    "[{}] {}: {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+            }
+        }
+    }
+
+    /// Logs through the [`GilTimeout`] worker, warning if it takes too long.
+    fn log_timeout(&self, gil_timeout: &GilTimeout, record: &Record) {
+        let logged = gil_timeout
+            .worker
+            .send_wait(self.share(), record, gil_timeout.timeout);
+        if !logged && !gil_timeout.warned.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "pyo3-log: Timed out waiting for the GIL, possible deadlock (is the GIL held while \
+                 waiting for a thread that logs?). Records are queued until the GIL is released."
+            );
+        }
+    }
+
+    /// Is the current interpreter the one the logger was created in?
+    fn own_interpreter(&self, py: Python<'_>) -> bool {
+        current_interpreter(py) == self.config.interpreter
+    }
+
+    /// Sends the records kept while there was no interpreter to Python.
+    fn log_pending(&self, py: Python<'_>) {
+        if !self.own_interpreter(py) || self.logging.finalizing(py) {
+            return;
+        }
+        let pending = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if pending.is_empty() {
+                return;
+            }
+            mem::take(&mut *pending)
+        };
+        for record in pending {
+            record.with_record(|rust_record| {
+                let cache = self.lookup(rust_record.target());
+                self.log_locked(py, rust_record, &cache, Some(record.origin()));
+            });
+        }
+    }
+
+    /// Sends the record to Python, with the GIL already held.
+    ///
+    /// The record is expected to already pass the filters. The `origin` is when and where the
+    /// record was logged, if known (used only with [`with_timestamps`][Logger::with_timestamps]
+    /// and [`with_thread_info`][Logger::with_thread_info]).
+    fn log_locked(
+        &self,
+        py: Python<'_>,
+        record: &Record,
+        cache: &Option<Arc<CacheNode>>,
+        origin: Option<&Origin>,
+    ) {
+        if !self.own_interpreter(py) || self.logging.finalizing(py) {
+            self.log_missing_interpreter(record);
+            return;
+        }
+        let cache = if self.config.watch_reconfiguration
+            && config_changed(py, &self.logging, &self.watch)
+        {
+            self.cache.store(Default::default());
+            &None
+        } else {
+            cache
+        };
+        // If an exception were triggered before this attempt to log,
+        // store it to the side for now and restore it afterwards.
+        let maybe_existing_exception = PyErr::take(py);
+        match self.log_inner(py, record, cache, origin) {
+            Ok(Some(found)) => self.cache_found(py, record.target(), found),
+            Ok(None) => (),
+            Err(e) => {
+                // NOTE: If an exception was triggered _during_ logging, restore it as current Python exception
+                // (unless configured otherwise).
+                // We have to use PyErr::restore because we cannot return a PyResult from the Log trait's log method.
+                Metrics::inc(&self.metrics.dropped_errors);
+                self.report_error(py, e);
+            }
+        };
+
+        // If there was a prior exception, restore it now
+        // This ensures that the earliest thrown exception will be the one that's visible to the caller.
+        if let Some(e) = maybe_existing_exception {
+            e.restore(py);
+        }
+    }
+
+    /// Looks up the additional Python loggers of the target, if there are any configured at all.
+    fn uncached_tees<'a, 'py>(
+        &self,
+        py: Python<'py>,
+        target: &str,
+    ) -> PyResult<Vec<Cow<'a, Bound<'py, PyAny>>>> {
+        if self.config.tees.is_empty() {
+            return Ok(Vec::new());
+        }
+        let get_logger = self.logging.bind(py)?.getattr("getLogger")?;
+        self.tee_loggers(&get_logger, target)
+    }
+
+    /// Looks up the additional Python loggers of the target.
+    fn tee_loggers<'a, 'py>(
+        &self,
+        get_logger: &Bound<'py, PyAny>,
+        target: &str,
+    ) -> PyResult<Vec<Cow<'a, Bound<'py, PyAny>>>> {
+        self.config
+            .tees_for(target)
+            .map(|name| get_logger.call1((name,)).map(Cow::Owned))
+            .collect()
+    }
+
+    /// Caches the loggers found for the target.
+    fn cache_found(&self, py: Python<'_>, target: &str, found: FoundLoggers) {
+        let (logger, name, tees) = found;
+        let filter = match self.config.caching_of(target) {
+            Caching::Nothing => unreachable!(),
+            Caching::Loggers => LevelFilter::max(),
+            Caching::LoggersAndLevels => {
+                // The record goes through if any of the destinations wants it.
+                let max_level = || -> PyResult<LevelFilter> {
+                    let mut filter = self.config.python_max_level(logger.bind(py))?;
+                    for tee in &tees {
+                        let tee = self.config.python_max_level(tee.bind(py))?;
+                        filter = cmp::max(filter, tee);
+                    }
+                    Ok(filter)
+                };
+                max_level().unwrap_or_else(|e| {
+                    // We can't return the error, see the note in log_locked.
+                    self.report_error(py, e);
+                    LevelFilter::max()
+                })
+            }
+        };
+
+        let has_handlers = !self.config.fallback_stderr
+            || has_handlers(logger.bind(py)).unwrap_or_else(|e| {
+                self.report_error(py, e);
+                true
+            });
+
+        let entry = CacheEntry {
+            filter,
+            logger,
+            name,
+            tees,
+            has_handlers,
+            last_used: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+        };
+        self.store_to_cache(target, entry);
+    }
+
+    /// Looks up the Python logger of the target and caches it, including its levels.
+    ///
+    /// This allows [`enabled`][Log::enabled] to learn about disabled targets, without waiting
+    /// for a record to be logged. Does nothing if it can't be done cheaply and without the risk
+    /// of blocking (eg. in the buffered mode). In particular, it's done only if the current thread
+    /// already holds the GIL ‒ taking it here could deadlock with a thread holding the GIL and
+    /// waiting for us (the `enabled` checks don't expect to block).
+    fn fill_cache(&self, target: &str) -> Option<Arc<CacheNode>> {
+        let skip = self.config.caching_of(target) != Caching::LoggersAndLevels
+            || self.config.buffer.is_some()
+            || self.config.route.is_some()
+            || self.config.pinned.contains_key(target)
+            || !interpreter_initialized()
+            || !gil_held();
+        if skip {
+            return None;
+        }
+        Python::with_gil(|py| {
+            if !self.own_interpreter(py) {
+                return None;
+            }
+            self.log_pending(py);
+            let found = || -> PyResult<FoundLoggers> {
+                let get_logger = self.logging.bind(py)?.getattr("getLogger")?;
+                let name = PyString::new(py, &self.python_name(target));
+                let logger = self.python_logger(&get_logger, &name)?;
+                let tees = self
+                    .tee_loggers(&get_logger, target)?
+                    .into_iter()
+                    .map(|t| t.into_owned().unbind())
+                    .collect();
+                Ok((logger.unbind(), name.unbind(), tees))
+            };
+            // Not logging anything, so there's nobody to report the errors to. Let the logging
+            // itself try again.
+            let found = found().ok()?;
+            self.cache_found(py, target, found);
+            self.lookup(target)
+        })
+    }
+
+    /// Gets the Python logger of the given name.
+    fn python_logger<'py>(
+        &self,
+        get_logger: &Bound<'py, PyAny>,
+        name: &Bound<'py, PyString>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let logger = get_logger.call1((name,))?;
+        if self.config.ensure_null_handler && !has_handlers(&logger)? {
+            let handler = self
+                .logging
+                .bind(get_logger.py())?
+                .getattr("NullHandler")?
+                .call0()?;
+            logger.call_method1("addHandler", (handler,))?;
+        }
+        Ok(logger)
+    }
+
+    /// Handles an error from the Python side, see [`on_error`][Logger::on_error].
+    fn report_error(&self, py: Python<'_>, e: PyErr) {
+        match &self.config.on_error {
+            Some(on_error) => on_error(&e),
+            None => e.restore(py),
+        }
+    }
+
+    fn store_to_cache(&self, target: &str, entry: CacheEntry) {
+        let path = target.split(self.config.separator.as_str());
+
+        let orig = self.cache.load();
+        // Construct a new cache structure and insert the new root.
+        let new = orig.store_to_cache_recursive(path, entry);
+        // Note: In case of collision, the cache update is lost. This is fine, as we simply lose a
+        // tiny bit of performance and will cache the thing next time.
+        //
+        // We err on the side of losing it here (instead of overwriting), because if the cache is
+        // reset, we don't want to re-insert the old value we have.
+        let prev = self.cache.compare_and_swap(&orig, Arc::clone(&new));
+
+        if let Some(max) = self.config.max_cache_entries {
+            // Each new entry starts a new "tick", so entries used since then are newer.
+            self.clock.fetch_add(1, Ordering::Relaxed);
+            if Arc::ptr_eq(&prev, &orig) && new.len() > max {
+                self.evict(new, max);
+            }
+        }
+    }
+
+    /// Evicts the least recently used entries from the cache, so only 3/4 of the max remain.
+    ///
+    /// Evicting more than necessary makes sure we don't have to go through the whole cache on each
+    /// new entry.
+    fn evict(&self, orig: Arc<CacheNode>, max: usize) {
+        let mut last_used = Vec::new();
+        orig.last_used(&mut last_used);
+        last_used.sort_unstable();
+        let keep = max - max / 4;
+        let cutoff = match last_used.len().checked_sub(keep) {
+            Some(0) | None => return,
+            Some(evict) => last_used.get(evict).copied().unwrap_or(u64::MAX),
+        };
+        let new = orig.evict_recursive(cutoff).unwrap_or_default();
+        // As with storing, if someone else modified the cache in the meantime, we let them win.
+        // Some future store will evict again.
+        self.cache.compare_and_swap(&orig, new);
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Config {
+    /// Computes the name of the Python logger for the given Rust target.
+    fn python_name<'t>(&self, target: &'t str) -> Cow<'t, str> {
+        let name = match (&self.empty_target, &self.map_target) {
+            (Some(empty), _) if target.is_empty() => Cow::Owned(empty.clone()),
+            (_, Some(map)) => map(target),
+            (_, None) => Cow::Owned(target.replace(self.separator.as_str(), ".")),
+        };
+        match (self.prefix.is_empty(), name.is_empty()) {
+            (true, _) => name,
+            (false, true) => Cow::Owned(self.prefix.clone()),
+            (false, false) => Cow::Owned(format!("{}.{}", self.prefix, name)),
+        }
+    }
+
+    /// Names of the additional Python loggers the records of the target are sent to.
+    fn tees_for<'a>(&'a self, target: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.tees
+            .iter()
+            .filter(move |(tee_target, _)| {
+                target
+                    .strip_prefix(tee_target.as_str())
+                    .map(|rest| rest.is_empty() || rest.starts_with(self.separator.as_str()))
+                    .unwrap_or(false)
+            })
+            .flat_map(|(_, names)| names.iter().map(String::as_str))
+    }
+
+    /// Finds a node in the cache.
+    fn lookup(&self, cache: &ArcSwap<CacheNode>, target: &str) -> Option<Arc<CacheNode>> {
+        if self.caching_of(target) == Caching::Nothing {
+            return None;
+        }
+
+        let root = cache.load();
         let mut node: &Arc<CacheNode> = &root;
-        for segment in target.split("::") {
+        for segment in target.split(self.separator.as_str()) {
             match node.children.get(segment) {
                 Some(sub) => node = sub,
                 None => return None,
             }
-        }
+        }
+
+        Some(Arc::clone(node))
+    }
+
+    /// Normalizes a target for use as a key in the [`Filters`].
+    ///
+    /// A filter for `foo::bar::` would never match anything, as the targets are split at the
+    /// separators, so the trailing ones are stripped.
+    fn filter_key<'t>(&self, mut target: &'t str) -> Cow<'t, str> {
+        while let Some(stripped) = target.strip_suffix(self.separator.as_str()) {
+            target = stripped;
+        }
+        self.normalize(target)
+    }
+
+    /// Should a backtrace be captured for a record of this level?
+    #[cfg(feature = "backtrace")]
+    fn wants_backtrace(&self, level: Level) -> bool {
+        self.backtrace_level.map_or(false, |min| level <= min)
+    }
+
+    #[cfg(not(feature = "backtrace"))]
+    fn wants_backtrace(&self, _level: Level) -> bool {
+        false
+    }
+
+    /// Lowercases the target, if [case-insensitive][Logger::case_insensitive_targets].
+    fn normalize<'t>(&self, target: &'t str) -> Cow<'t, str> {
+        if self.case_insensitive && target.chars().any(char::is_uppercase) {
+            Cow::Owned(target.to_lowercase())
+        } else {
+            Cow::Borrowed(target)
+        }
+    }
+
+    fn target_rule<'f>(&self, filters: &'f Filters, target: &str) -> Option<TargetRule<'f>> {
+        // The common case, no need to scan the target.
+        if filters.targets.is_empty() && filters.level_predicates.is_empty() {
+            return None;
+        }
+
+        let rule = |prefix: &str| {
+            filters
+                .level_predicates
+                .get(prefix)
+                .map(|predicate| TargetRule::Levels(&**predicate))
+                .or_else(|| {
+                    filters
+                        .targets
+                        .get(prefix)
+                        .copied()
+                        .map(TargetRule::Threshold)
+                })
+        };
+
+        self.most_specific(target, rule)
+    }
+
+    /// Checks if the Python logger takes records of the level.
+    fn python_enabled_for(&self, logger: &Bound<'_, PyAny>, level: Level) -> PyResult<bool> {
+        match self.dispatch_method {
+            DispatchMethod::Handle => is_enabled_for(&self.levels, logger, level),
+            DispatchMethod::CallHandlers => Ok(true),
+        }
+    }
+
+    /// The most verbose Rust level the Python logger takes.
+    fn python_max_level(&self, logger: &Bound<'_, PyAny>) -> PyResult<LevelFilter> {
+        if self.dispatch_method == DispatchMethod::CallHandlers {
+            Ok(LevelFilter::max())
+        } else if self.use_effective_level {
+            effective_max_level(&self.levels, logger)
+        } else {
+            extract_max_level(&self.levels, logger)
+        }
+    }
+
+    #[cfg(all(feature = "kv", feature = "serde"))]
+    fn kv_conversion(&self) -> kv::Conversion {
+        if self.json_extra {
+            kv::Conversion::Json
+        } else {
+            kv::Conversion::Plain
+        }
+    }
+
+    #[cfg(all(feature = "kv", not(feature = "serde")))]
+    fn kv_conversion(&self) -> kv::Conversion {
+        kv::Conversion::Plain
+    }
+
+    /// Finds the most specific match for the target or any of its parents in the hierarchy.
+    fn most_specific<T, F: Fn(&str) -> Option<T>>(&self, target: &str, lookup: F) -> Option<T> {
+        let mut start = 0;
+        let mut result = None;
+        while let Some(end) = target[start..].find(self.separator.as_str()) {
+            if let Some(r) = lookup(&target[..start + end]) {
+                result = Some(r);
+            }
+            start += end + self.separator.len();
+        }
+        lookup(target).or(result)
+    }
+
+    /// What is cached for the target.
+    fn caching_of(&self, target: &str) -> Caching {
+        if self.caching_for.is_empty() {
+            return self.caching;
+        }
+        self.most_specific(target, |prefix| self.caching_for.get(prefix).copied())
+            .unwrap_or(self.caching)
+    }
+
+    fn rate_limit(&self, target: &str) -> Option<&RateLimit> {
+        if self.rate_limits.is_empty() {
+            return None;
+        }
+        self.most_specific(target, |prefix| self.rate_limits.get(prefix).map(|l| &**l))
+    }
+
+    fn sample(&self, target: &str) -> Option<&Sample> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        self.most_specific(target, |prefix| self.samples.get(prefix).map(|s| &**s))
+    }
+
+    fn target_filter(&self, filters: &Filters, target: &str) -> Option<LevelFilter> {
+        self.target_rule(filters, target).map(TargetRule::max_level)
+    }
+
+    /// The threshold for the metadata.
+    ///
+    /// For level predicates, this is only an upper bound of what passes.
+    fn filter_for_metadata(&self, filters: &Filters, metadata: &Metadata) -> LevelFilter {
+        self.target_filter(filters, metadata.target())
+            .unwrap_or_else(|| self.fallback_filter(filters, metadata))
+    }
+
+    fn glob_filter(&self, filters: &Filters, target: &str) -> Option<LevelFilter> {
+        filters
+            .globs
+            .iter()
+            .find(|(pattern, _)| glob::matches(pattern, target, &self.separator))
+            .map(|(_, filter)| *filter)
+    }
+
+    /// The filter for targets none of the target filters match.
+    fn fallback_filter(&self, filters: &Filters, metadata: &Metadata) -> LevelFilter {
+        self.glob_filter(filters, metadata.target())
+            .or_else(|| filters.filter_fn.as_ref().and_then(|f| f(metadata)))
+            .unwrap_or(filters.top)
+    }
+
+    fn rust_enabled(&self, filters: &Filters, metadata: &Metadata) -> bool {
+        if metadata.level() > SUPPORTED_MAX_LEVEL {
+            return false;
+        }
+        match self.target_rule(filters, metadata.target()) {
+            Some(rule) => rule.enabled(metadata.level()),
+            None => metadata.level() <= self.fallback_filter(filters, metadata),
+        }
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Debug for Config {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let mut fmt = fmt.debug_struct("Config");
+        fmt.field("separator", &self.separator)
+            .field("map_target", &self.map_target.as_ref().map(|_| "<fn>"))
+            .field("record_name", &self.record_name.as_ref().map(|_| "<fn>"))
+            .field("prefix", &self.prefix)
+            .field("empty_target", &self.empty_target)
+            .field("tees", &self.tees)
+            .field("pinned", &self.pinned.keys().collect::<Vec<_>>())
+            .field("route", &self.route.as_ref().map(|_| "<handler>"))
+            .field(
+                "message_formatter",
+                &self.message_formatter.as_ref().map(|_| "<fn>"),
+            )
+            .field("redact", &self.redact.as_ref().map(|_| "<fn>"))
+            .field(
+                "record_filter",
+                &self.record_filter.as_ref().map(|_| "<fn>"),
+            )
+            .field(
+                "taps",
+                &self.taps.iter().map(|_| "<fn>").collect::<Vec<_>>(),
+            )
+            .field("caching", &self.caching)
+            .field("caching_for", &self.caching_for)
+            .field("max_cache_entries", &self.max_cache_entries)
+            .field("watch_reconfiguration", &self.watch_reconfiguration)
+            .field("timestamps", &self.timestamps)
+            .field("thread_info", &self.thread_info)
+            .field("source_id", &self.source_id)
+            .field("case_insensitive", &self.case_insensitive)
+            .field("use_effective_level", &self.use_effective_level)
+            .field("ensure_null_handler", &self.ensure_null_handler)
+            .field("missing_interpreter", &self.missing_interpreter)
+            .field("fallback_stderr", &self.fallback_stderr)
+            .field("flush_policy", &self.flush_policy)
+            .field("dispatch_method", &self.dispatch_method)
+            .field("on_error", &self.on_error.as_ref().map(|_| "<fn>"))
+            .field("levels", &self.levels)
+            .field("buffer", &self.buffer)
+            .field("dedupe", &self.dedupe)
+            .field("rate_limits", &self.rate_limits)
+            .field("samples", &self.samples)
+            .field("gil_timeout", &self.gil_timeout)
+            .field("manage_max_level", &self.manage_max_level)
+            .field("reconfigurable", &self.reconfigurable)
+            .field("interpreter", &self.interpreter)
+            .field("handle_name", &self.handle_name)
+            .field("log_panics", &self.log_panics)
+            .field("current_exception", &self.current_exception);
+        #[cfg(feature = "kv")]
+        fmt.field("exception_mapping", &self.exception_mapping)
+            .field("preserve_template", &self.preserve_template);
+        #[cfg(all(feature = "kv", feature = "serde"))]
+        fmt.field("json_extra", &self.json_extra);
+        #[cfg(feature = "validate-cache")]
+        fmt.field("validate_cache", &self.validate_cache);
+        #[cfg(feature = "backtrace")]
+        fmt.field("backtrace_level", &self.backtrace_level);
+        fmt.finish()
+    }
+}
+
+/// Creates the logger with the default settings.
+///
+/// # Panics
+///
+/// If the logger can't be set up. See [`Logger::try_default`] for a fallible version.
+#[cfg(feature = "pyo3")]
+impl Default for Logger {
+    fn default() -> Self {
+        Self::try_default().expect("Failed to initialize python logging")
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Logger {
+    /// Checks if the metadata is enabled, with the target already [normalized][Config::normalize].
+    fn enabled_normalized(&self, metadata: &Metadata) -> bool {
+        // Don't bother with the cache if the Rust side filters it out already.
+        if !self.rust_enabled(metadata) {
+            return false;
+        }
+        let mut cache = self.lookup(metadata.target());
+        if cache.as_ref().map_or(true, |node| node.local.is_none()) {
+            cache = self.fill_cache(metadata.target()).or(cache);
+        }
+
+        self.python_enabled(metadata, &cache)
+    }
+
+    /// Logs the record, with the target already [normalized][Config::normalize].
+    fn log_normalized(&self, record: &Record) {
+        if !self.rust_enabled(record.metadata()) {
+            return;
+        }
+        if let Some(filter) = &self.config.record_filter {
+            if !filter(record) {
+                return;
+            }
+        }
+        match &self.config.dedupe {
+            Some(dedupe) => {
+                let mut summaries = Vec::new();
+                let pass = dedupe.check(record, &mut summaries);
+                for summary in summaries {
+                    summary.with_record(|summary| self.dispatch(summary));
+                }
+                if pass {
+                    self.dispatch(record);
+                }
+            }
+            None => self.dispatch(record),
+        }
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        match self.config.normalize(metadata.target()) {
+            Cow::Borrowed(_) => self.enabled_normalized(metadata),
+            Cow::Owned(target) => self.enabled_normalized(
+                &Metadata::builder()
+                    .level(metadata.level())
+                    .target(&target)
+                    .build(),
+            ),
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        // Only the panic hook cares and it is there only with log_panics.
+        let _guard = if self.config.log_panics {
+            Some(LoggingGuard::enter())
+        } else {
+            None
+        };
+        match self.config.normalize(record.target()) {
+            Cow::Borrowed(_) => self.log_normalized(record),
+            Cow::Owned(target) => {
+                let mut builder = Record::builder();
+                builder
+                    .level(record.level())
+                    .target(&target)
+                    .line(record.line());
+                match record.module_path_static() {
+                    Some(module_path) => builder.module_path_static(Some(module_path)),
+                    None => builder.module_path(record.module_path()),
+                };
+                match record.file_static() {
+                    Some(file) => builder.file_static(Some(file)),
+                    None => builder.file(record.file()),
+                };
+                #[cfg(feature = "kv")]
+                builder.key_values(record.key_values());
+                self.log_normalized(&builder.args(*record.args()).build())
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(dedupe) = &self.config.dedupe {
+            for summary in dedupe.drain() {
+                summary.with_record(|summary| self.dispatch(summary));
+            }
+        }
+        if let Some(buffer) = &self.config.buffer {
+            buffer.flush();
+        } else if interpreter_initialized() {
+            if let Some(gil_timeout) = &self.config.gil_timeout {
+                gil_timeout.worker.flush();
+            }
+            Python::with_gil(|py| self.log_pending(py));
+        }
+
+        if self.config.flush_policy != FlushPolicy::Nothing && interpreter_initialized() {
+            Python::with_gil(|py| {
+                if !self.own_interpreter(py) {
+                    return;
+                }
+                let result =
+                    self.logging
+                        .bind(py)
+                        .and_then(|logging| match self.config.flush_policy {
+                            FlushPolicy::Shutdown => logging.call_method0("shutdown").map(drop),
+                            _ => flush_handlers(logging),
+                        });
+                // Same as with logging, we can't return the error.
+                if let Err(e) = result {
+                    self.report_error(py, e);
+                }
+            });
+        }
+    }
+}
+
+/// Flushes all the handlers of all the Python loggers.
+#[cfg(feature = "pyo3")]
+fn flush_handlers(logging: &Bound<'_, PyModule>) -> PyResult<()> {
+    let root = logging.getattr("root")?;
+    // A copy, in case the flushing creates more loggers.
+    let loggers = root
+        .getattr("manager")?
+        .getattr("loggerDict")?
+        .call_method0("copy")?
+        .call_method0("values")?;
+    for logger in loggers.try_iter()?.chain(iter::once(Ok(root))) {
+        // There are placeholders in the dict too, these have no handlers.
+        if let Ok(handlers) = logger?.getattr("handlers") {
+            for handler in handlers.try_iter()? {
+                handler?.call_method0("flush")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sets the time the record was created on the Python record.
+///
+/// Does the same computations as the `LogRecord` constructor.
+#[cfg(feature = "pyo3")]
+fn set_created(
+    logging: &Bound<'_, PyModule>,
+    record: &Bound<'_, PyAny>,
+    created: SystemTime,
+) -> PyResult<()> {
+    let created = created
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+    record.setattr("created", created)?;
+    record.setattr("msecs", (created.fract() * 1000.0).floor())?;
+    // Not part of the public API, so skip it if it's not there.
+    if let Ok(start) = logging
+        .getattr("_startTime")
+        .and_then(|start| start.extract::<f64>())
+    {
+        record.setattr("relativeCreated", (created - start) * 1000.0)?;
+    }
+    Ok(())
+}
+
+/// Captures the backtrace, if wanted and enabled by the environment.
+#[cfg(all(feature = "pyo3", feature = "backtrace"))]
+#[allow(clippy::incompatible_msrv)] // The feature documents the higher requirement.
+fn capture_backtrace(wanted: bool) -> Option<String> {
+    use std::backtrace::{Backtrace, BacktraceStatus};
+
+    if !wanted {
+        return None;
+    }
+    let backtrace = Backtrace::capture();
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(backtrace.to_string()),
+        _ => None,
+    }
+}
+
+/// Checks if the Python logger (or the ones it propagates to) has any handlers.
+#[cfg(feature = "pyo3")]
+fn has_handlers(logger: &Bound<'_, PyAny>) -> PyResult<bool> {
+    logger.call_method0("hasHandlers")?.is_truthy()
+}
+
+/// Puts one of our own attributes into the `extra`.
+///
+/// A key of the same name (from the key-value pairs or the context) is moved out of the way, under
+/// the [`RESERVED_PREFIX`].
+#[cfg(feature = "pyo3")]
+fn set_own_extra(extra: &Bound<'_, PyDict>, key: &str, value: &str) -> PyResult<()> {
+    if let Some(existing) = extra.get_item(key)? {
+        extra.del_item(key)?;
+        extra.set_item(format!("{}{}", RESERVED_PREFIX, key), existing)?;
+    }
+    extra.set_item(key, value)
+}
+
+/// The exception currently handled by Python in this thread, as `sys.exc_info()`.
+#[cfg(feature = "pyo3")]
+fn current_exception(py: Python<'_>) -> PyResult<Option<Bound<'_, PyTuple>>> {
+    let exc_info = py
+        .import("sys")?
+        .call_method0("exc_info")?
+        .downcast_into::<PyTuple>()?;
+    if exc_info.get_item(0)?.is_none() {
+        Ok(None)
+    } else {
+        Ok(Some(exc_info))
+    }
+}
+
+/// Sets the Rust thread name and id on the Python record.
+#[cfg(feature = "pyo3")]
+fn set_thread(record: &Bound<'_, PyAny>, origin: &Origin) -> PyResult<()> {
+    record.setattr("threadName", origin.thread.name().unwrap_or("<unnamed>"))?;
+    record.setattr("thread", origin.thread_id)?;
+    Ok(())
+}
+
+/// What [`config_changed`] saw the last time.
+#[cfg(feature = "pyo3")]
+#[derive(Debug)]
+struct LoggingSnapshot {
+    /// A Python logger of our own, not used for anything else.
+    ///
+    /// Python clears the level caches of all the loggers on any level change (and on
+    /// `logging.disable`). We fill the one of this logger after each check, so finding it empty
+    /// means a change.
+    sentinel: Py<PyAny>,
+    /// The length of `logging._handlerList` (weak references to all the handlers).
+    handlers: usize,
+    /// The last item of `logging._handlerList`, to notice a handler replaced by another one.
+    last_handler: Option<Py<PyAny>>,
+}
+
+/// The state of the detection of the Python logging reconfiguration.
+#[cfg(feature = "pyo3")]
+#[derive(Debug, Default)]
+struct ReconfigurationWatch {
+    /// Was there a check already?
+    checked: AtomicBool,
+    /// The snapshot from the last check.
+    ///
+    /// Taken out while checking, because Python may switch threads during the check and we must
+    /// not hold a lock then. A concurrent check finding nothing here just reports no change,
+    /// leaving it to the one in progress.
+    snapshot: Mutex<Option<LoggingSnapshot>>,
+}
+
+/// Distinguishes the sentinel loggers of [`LoggingSnapshot`].
+#[cfg(feature = "pyo3")]
+static SENTINELS: AtomicUsize = AtomicUsize::new(0);
+
+/// Did we already warn the reconfiguration can't be detected?
+#[cfg(feature = "pyo3")]
+static RECONFIGURATION_UNDETECTABLE_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Checks if the Python logging configuration changed since the last check of this watch.
+#[cfg(feature = "pyo3")]
+fn config_changed(py: Python<'_>, logging: &LoggingModule, watch: &ReconfigurationWatch) -> bool {
+    let previous = watch
+        .snapshot
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    let first = !watch.checked.swap(true, Ordering::AcqRel);
+    if previous.is_none() && !first {
+        return false;
+    }
+    match take_snapshot(py, logging, previous.as_ref()) {
+        Ok((changed, snapshot)) => {
+            *watch.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = Some(snapshot);
+            changed
+        }
+        Err(e) => {
+            // Put it back, so the next check doesn't think it's a concurrent one.
+            *watch.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = previous;
+            if !RECONFIGURATION_UNDETECTABLE_WARNED.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "pyo3-log: can't detect changes of the Python logging configuration ({}), the \
+                     caches are not reset automatically",
+                    e
+                );
+            }
+            false
+        }
+    }
+}
+
+/// Takes a new snapshot and compares it to the previous one.
+#[cfg(feature = "pyo3")]
+fn take_snapshot(
+    py: Python<'_>,
+    logging: &LoggingModule,
+    previous: Option<&LoggingSnapshot>,
+) -> PyResult<(bool, LoggingSnapshot)> {
+    let logging = logging.bind(py)?;
+    let sentinel = match previous {
+        Some(previous) => previous.sentinel.bind(py).clone(),
+        None => {
+            let name = format!(
+                "pyo3_log.reconfiguration_watch_{}",
+                SENTINELS.fetch_add(1, Ordering::Relaxed)
+            );
+            logging.getattr("getLogger")?.call1((name,))?
+        }
+    };
+    let levels_changed = sentinel.getattr("_cache")?.len()? == 0;
+    if levels_changed {
+        // Fills the cache again.
+        sentinel.call_method1("isEnabledFor", (50,))?;
+    }
+
+    let handler_list = logging.getattr("_handlerList")?;
+    let handlers = handler_list.len()?;
+    let last_handler = match handlers {
+        0 => None,
+        len => Some(handler_list.get_item(len - 1)?.unbind()),
+    };
+    let handlers_changed = match previous {
+        Some(previous) => {
+            previous.handlers != handlers
+                || match (&previous.last_handler, &last_handler) {
+                    (Some(a), Some(b)) => !a.is(b),
+                    (None, None) => false,
+                    _ => true,
+                }
+        }
+        None => true,
+    };
+
+    let snapshot = LoggingSnapshot {
+        sentinel: sentinel.unbind(),
+        handlers,
+        last_handler,
+    };
+    Ok((levels_changed || handlers_changed, snapshot))
+}
+
+/// The attribute of the `sys` module holding the number identifying the interpreter.
+#[cfg(feature = "pyo3")]
+const INTERPRETER_ATTR: &str = "_pyo3_log_interpreter";
+
+/// The number for the next interpreter, see [`current_interpreter`].
+#[cfg(feature = "pyo3")]
+static NEXT_INTERPRETER: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies the current Python interpreter.
+///
+/// Each (sub-)interpreter has its own `sys` module, which gets tagged by a number unique in this
+/// process on the first call. Unlike addresses of the per-interpreter objects, the number can't be
+/// reused by an interpreter created after another one was finalized. 0 means the interpreter
+/// couldn't be identified, which never matches any other.
+#[cfg(feature = "pyo3")]
+fn current_interpreter(py: Python<'_>) -> u64 {
+    let identify = || -> PyResult<u64> {
+        let sys = py.import("sys")?.dict();
+        if let Some(id) = sys.get_item(INTERPRETER_ATTR)? {
+            return id.extract();
+        }
+        let id = NEXT_INTERPRETER.fetch_add(1, Ordering::Relaxed);
+        // In case some other thread got here first (importing may let go of the GIL).
+        sys.call_method1("setdefault", (INTERPRETER_ATTR, id))?
+            .extract()
+    };
+    identify().unwrap_or(0)
+}
+
+#[cfg(feature = "pyo3")]
+thread_local! {
+    /// How many of our loggers with [`Logger::log_panics`] are currently logging on this thread
+    /// (they can nest).
+    static LOGGING_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// Marks the current thread as logging, see [`LOGGING_DEPTH`].
+#[cfg(feature = "pyo3")]
+struct LoggingGuard;
+
+#[cfg(feature = "pyo3")]
+impl LoggingGuard {
+    fn enter() -> Self {
+        LOGGING_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        LoggingGuard
+    }
+
+    fn active() -> bool {
+        LOGGING_DEPTH.with(|depth| depth.get() > 0)
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Drop for LoggingGuard {
+    fn drop(&mut self) {
+        LOGGING_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// The most verbose level this crate handles at all.
+#[cfg(all(feature = "pyo3", feature = "min-level-info"))]
+const SUPPORTED_MAX_LEVEL: LevelFilter = LevelFilter::Info;
+/// The most verbose level this crate handles at all.
+#[cfg(all(feature = "pyo3", not(feature = "min-level-info")))]
+const SUPPORTED_MAX_LEVEL: LevelFilter = LevelFilter::Trace;
+
+/// The most verbose level that can get through, given both the `log` crate and our features.
+#[cfg(feature = "pyo3")]
+fn static_max_level() -> LevelFilter {
+    cmp::min(log::STATIC_MAX_LEVEL, SUPPORTED_MAX_LEVEL)
+}
+
+/// Did we already note a filter above the [`static_max_level`]?
+#[cfg(feature = "pyo3")]
+static STATIC_MAX_LEVEL_NOTED: AtomicBool = AtomicBool::new(false);
+
+/// Notes (once, in debug builds) that the filter is more verbose than the `log` crate (or the
+/// `min-level-info` feature) lets through.
+///
+/// Harmless, but likely not what the user expects when the records don't show up.
+#[cfg(feature = "pyo3")]
+fn check_static_max_level(filter: LevelFilter) {
+    if cfg!(debug_assertions)
+        && filter > static_max_level()
+        && !STATIC_MAX_LEVEL_NOTED.swap(true, Ordering::Relaxed)
+    {
+        eprintln!(
+            "pyo3-log: filter {} is more verbose than the maximum level {} compiled in, the more \
+             verbose records are compiled out",
+            filter,
+            static_max_level()
+        );
+    }
+}
+
+/// Was the panic hook of [`Logger::log_panics`] installed already?
+#[cfg(feature = "pyo3")]
+static PANIC_HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs a panic hook logging the panics, chained before the current one.
+///
+/// Only the first call installs it, the global logger can't be replaced anyway.
+#[cfg(feature = "pyo3")]
+fn install_panic_hook() {
+    if PANIC_HOOK_INSTALLED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // A panic from inside the logging would likely panic again, and panicking in the hook
+        // aborts.
+        if !LoggingGuard::active() && Level::Error <= log::max_level() {
+            let thread = thread::current();
+            let message = panic_message(info.payload());
+            let mut builder = Record::builder();
+            builder.level(Level::Error).target("panic");
+            if let Some(location) = info.location() {
+                builder
+                    .file(Some(location.file()))
+                    .line(Some(location.line()));
+            }
+            log::logger().log(
+                &builder
+                    .args(format_args!(
+                        "thread '{}' panicked: {}",
+                        thread.name().unwrap_or("<unnamed>"),
+                        message
+                    ))
+                    .build(),
+            );
+        }
+        previous(info);
+    }));
+}
+
+/// Extracts the message from a panic payload.
+#[cfg(feature = "pyo3")]
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// Name of the attribute of the Python record with the original Rust target.
+#[cfg(feature = "pyo3")]
+const RUST_TARGET: &str = "rust_target";
+
+/// Name of the attribute of the Python record with the [source id][Logger::source_id].
+#[cfg(feature = "pyo3")]
+const SOURCE_ID: &str = "ext_source";
+
+/// Name of the attribute of the Python record with the Rust backtrace.
+#[cfg(all(feature = "pyo3", feature = "backtrace"))]
+const RUST_BACKTRACE: &str = "rust_backtrace";
+
+/// Names Python's `makeRecord` refuses to take through `extra`.
+///
+/// These are either attributes of the `LogRecord` itself or ones added by formatters. Passing any
+/// of them makes `makeRecord` raise a `KeyError`, so keys colliding with them get prefixed by
+/// [`RESERVED_PREFIX`].
+#[cfg(feature = "pyo3")]
+pub(crate) const RESERVED: &[&str] = &[
+    "args",
+    "asctime",
+    "created",
+    "exc_info",
+    "exc_text",
+    "filename",
+    "funcName",
+    "levelname",
+    "levelno",
+    "lineno",
+    "message",
+    "module",
+    "msecs",
+    "msg",
+    "name",
+    "pathname",
+    "process",
+    "processName",
+    "relativeCreated",
+    "stack_info",
+    "taskName",
+    "thread",
+    "threadName",
+];
+
+/// Prefix put in front of keys that would collide with the [`RESERVED`] ones.
+#[cfg(feature = "pyo3")]
+const RESERVED_PREFIX: &str = "kv_";
+
+/// The Python `logging` module (or a compatible one) used by a [`Logger`].
+///
+/// Unless a custom one was provided, the standard module is imported on the first use, so
+/// creating a logger doesn't need the import. It's then kept, so it's not looked up again.
+#[cfg(feature = "pyo3")]
+#[derive(Default)]
+struct LoggingModule {
+    module: GILOnceCell<Py<PyModule>>,
+    /// The `sys.is_finalizing` function of the same interpreter.
+    is_finalizing: GILOnceCell<PyObject>,
+}
+
+#[cfg(feature = "pyo3")]
+impl LoggingModule {
+    fn bind<'a, 'py>(&'a self, py: Python<'py>) -> PyResult<&'a Bound<'py, PyModule>> {
+        self.module
+            .get_or_try_init(py, || py.import("logging").map(Bound::unbind))
+            .map(|module| module.bind(py))
+    }
+
+    fn clone_ref(&self, py: Python<'_>) -> Self {
+        let clone = LoggingModule::default();
+        if let Some(module) = self.module.get(py) {
+            let _ = clone.module.set(py, module.clone_ref(py));
+        }
+        clone
+    }
+
+    /// Checks if the interpreter started finalizing.
+    ///
+    /// `Py_IsInitialized` stays true until very late in `Py_Finalize`, while modules and threads
+    /// are already being torn down. Calling into Python in that window isn't safe either, so this
+    /// is checked (with the GIL held) each time the records are about to be sent to Python.
+    fn finalizing(&self, py: Python<'_>) -> bool {
+        self.is_finalizing
+            .get_or_try_init(py, || {
+                py.import("sys")?
+                    .getattr("is_finalizing")
+                    .map(Bound::unbind)
+            })
+            .and_then(|is_finalizing| is_finalizing.call0(py)?.extract(py))
+            // If we can't even ask, something is badly broken. Better not to touch it.
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Debug for LoggingModule {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        // Looking into the cell needs the GIL.
+        fmt.debug_struct("LoggingModule").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl From<Bound<'_, PyModule>> for LoggingModule {
+    fn from(module: Bound<'_, PyModule>) -> Self {
+        let logging = LoggingModule::default();
+        let _ = logging.module.set(module.py(), module.unbind());
+        logging
+    }
+}
+
+/// Did we already warn about `makeRecord` refusing the `extra` keys?
+#[cfg(feature = "pyo3")]
+static EXTRA_REFUSED_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Prefixes all the keys of the `extra` by the [`RESERVED_PREFIX`].
+///
+/// The last resort when `makeRecord` refuses some of the keys not known in advance (eg. ones a
+/// custom record factory adds).
+#[cfg(feature = "pyo3")]
+fn prefix_extra<'py>(extra: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyDict>> {
+    let prefixed = PyDict::new(extra.py());
+    for (key, value) in extra.iter() {
+        prefixed.set_item(format!("{}{}", RESERVED_PREFIX, key.str()?), value)?;
+    }
+    Ok(prefixed)
+}
+
+/// The default Python level values, indexed by the Rust [`Level`] (see [`level_index`]).
+const DEFAULT_LEVELS: [usize; 5] = [40, 30, 20, 10, 5];
+
+/// Index of the level in the [`DEFAULT_LEVELS`] (and the configured levels).
+fn level_index(level: Level) -> usize {
+    // Error is 1, Trace is 5
+    level as usize - 1
+}
+
+#[cfg(feature = "pyo3")]
+fn is_enabled_for(levels: &[usize; 5], logger: &Bound<'_, PyAny>, level: Level) -> PyResult<bool> {
+    let level = levels[level_index(level)];
+    logger.call_method1("isEnabledFor", (level,))?.is_truthy()
+}
+
+/// The levels to probe the Python loggers for, from the most verbose one.
+#[cfg(all(feature = "pyo3", not(feature = "min-level-info")))]
+const PROBED_LEVELS: &[Level] = &[
+    Level::Trace,
+    Level::Debug,
+    Level::Info,
+    Level::Warn,
+    Level::Error,
+];
+/// The levels to probe the Python loggers for, from the most verbose one.
+#[cfg(all(feature = "pyo3", feature = "min-level-info"))]
+const PROBED_LEVELS: &[Level] = &[Level::Info, Level::Warn, Level::Error];
+
+#[cfg(feature = "pyo3")]
+fn extract_max_level(levels: &[usize; 5], logger: &Bound<'_, PyAny>) -> PyResult<LevelFilter> {
+    for l in PROBED_LEVELS {
+        if is_enabled_for(levels, logger, *l)? {
+            return Ok(l.to_level_filter());
+        }
+    }
+
+    Ok(LevelFilter::Off)
+}
+
+/// The numeric Python level of a Rust level.
+///
+/// The levels are mapped to the same-named Python ones, [`Trace`][Level::Trace] (which doesn't
+/// exist in Python) is 5. This is the default mapping of a [`Logger`], which can be changed by
+/// [`Logger::level_map`].
+///
+/// ```rust
+/// # use log::Level;
+/// assert_eq!(pyo3_log::map_level(Level::Warn), 30);
+/// assert_eq!(pyo3_log::map_level(Level::Trace), 5);
+/// ```
+pub fn map_level(level: Level) -> usize {
+    DEFAULT_LEVELS[level_index(level)]
+}
+
+/// The Rust level of a numeric Python level.
+///
+/// The inverse of [`map_level`]. Python levels in between the known ones are rounded down, to the
+/// more verbose level (eg. 25 is [`Info`][Level::Info], 50 ‒ `CRITICAL` ‒ is
+/// [`Error`][Level::Error]). The ones below 5 are [`Trace`][Level::Trace] too, as there's nothing
+/// more verbose in Rust. 0 (`NOTSET`) is not a level and results in `None`.
+///
+/// ```rust
+/// # use log::Level;
+/// assert_eq!(pyo3_log::level_from_python(25), Some(Level::Info));
+/// assert_eq!(pyo3_log::level_from_python(5), Some(Level::Trace));
+/// assert_eq!(pyo3_log::level_from_python(0), None);
+/// ```
+pub fn level_from_python(value: usize) -> Option<Level> {
+    if value == 0 {
+        return None;
+    }
+    let level = Level::iter()
+        .find(|level| value >= map_level(*level))
+        .unwrap_or(Level::Trace);
+    Some(level)
+}
+
+/// The Rust filter corresponding to a Python level.
+///
+/// Accepts either the numeric Python level or its name (eg. `"WARNING"` or `"warning"`, looked up
+/// through `logging.getLevelName`, so custom levels registered with `logging.addLevelName` work
+/// too). Useful for reading the level from the Python-side configuration and passing it to
+/// [`Logger::filter`].
+///
+/// The filter lets through the same records a Python logger with that level would. Levels in
+/// between the standard ones are rounded up (eg. 25 lets through [`Warn`][Level::Warn] and
+/// more severe). `NOTSET` (0) lets everything through and levels above `ERROR` (like `CRITICAL`)
+/// nothing, as there's no more severe Rust level.
+///
+/// Names unknown to Python may still be the Rust ones (`"TRACE"` or `"OFF"`). Anything else is
+/// treated like `NOTSET`, leaving the decision to the Python side (and a warning is printed, once).
+///
+/// This uses the standard `logging` module and the default [level mapping][map_level]. See
+/// [`Logger::level_filter_from_python`] for the ones configured in a logger.
+///
+/// ```rust
+/// # use log::LevelFilter;
+/// # use pyo3::prelude::*;
+/// # use pyo3::types::PyString;
+/// Python::with_gil(|py| {
+///     let level = PyString::new(py, "WARNING");
+///     let filter = pyo3_log::level_filter_from_python(py, &level).unwrap();
+///     assert_eq!(filter, LevelFilter::Warn);
+/// });
+/// ```
+#[cfg(feature = "pyo3")]
+pub fn level_filter_from_python(py: Python<'_>, value: &Bound<'_, PyAny>) -> PyResult<LevelFilter> {
+    python_level_filter(&py.import("logging")?, &DEFAULT_LEVELS, value)
+}
+
+/// Did we already warn about an unknown level name?
+#[cfg(feature = "pyo3")]
+static UNKNOWN_LEVEL_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Implementation of [`level_filter_from_python`] with the given logging module and levels.
+#[cfg(feature = "pyo3")]
+fn python_level_filter(
+    logging: &Bound<'_, PyModule>,
+    levels: &[usize; 5],
+    value: &Bound<'_, PyAny>,
+) -> PyResult<LevelFilter> {
+    let name = match value.downcast::<PyString>() {
+        Ok(name) => name.to_str()?.to_uppercase(),
+        Err(_) => return Ok(python_threshold(levels, value.extract()?)),
+    };
+    let number = logging.getattr("getLevelName")?.call1((&name,))?;
+    if let Ok(number) = number.extract::<usize>() {
+        return Ok(python_threshold(levels, number));
+    }
+    if let Ok(filter) = name.parse::<LevelFilter>() {
+        return Ok(filter);
+    }
+    if !UNKNOWN_LEVEL_WARNED.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "pyo3-log: unknown level {}, leaving the filtering to Python",
+            name
+        );
+    }
+    Ok(LevelFilter::Trace)
+}
+
+/// Is the [`Installed`] shim registered in the `log` crate?
+///
+/// If not, either nothing or a logger without the shim is (see [`Logger::reconfigurable`]).
+#[cfg(feature = "pyo3")]
+static SHIM_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// The logger installed behind the [`Installed`] shim.
+#[cfg(feature = "pyo3")]
+static INSTALLED_LOGGER: ArcSwapOption<Logger> = ArcSwapOption::const_empty();
+
+/// The global logger registered in the `log` crate on the first install of a
+/// [reconfigurable][Logger::reconfigurable] logger.
+///
+/// The `log` crate doesn't allow replacing its logger, so this one forwards to the
+/// [`INSTALLED_LOGGER`], which can be (see [`ResetHandle::reconfigure`]).
+#[cfg(feature = "pyo3")]
+static INSTALLED_SHIM: Installed = Installed;
+
+#[cfg(feature = "pyo3")]
+struct Installed;
+
+#[cfg(feature = "pyo3")]
+impl Log for Installed {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        INSTALLED_LOGGER
+            .load()
+            .as_ref()
+            .map_or(false, |logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(logger) = INSTALLED_LOGGER.load().as_ref() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = INSTALLED_LOGGER.load().as_ref() {
+            logger.flush();
+        }
+    }
+}
+
+/// Checks if both are the same logger (or both are none).
+#[cfg(feature = "pyo3")]
+fn same_logger(a: &Option<Arc<Logger>>, b: &Option<Arc<Logger>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// The [`ResetHandle`] of the installed [`Logger`].
+#[cfg(feature = "pyo3")]
+static INSTALLED_HANDLE: ArcSwapOption<ResetHandle> = ArcSwapOption::const_empty();
+
+/// Checks if the global logger is a [`Logger`] from this crate.
+///
+/// This is true once a logger was installed by [`Logger::install`] (or any other function
+/// installing it, like [`init`]), until it is [uninstalled][ResetHandle::uninstall]. A logger
+/// passed to another logging system through [`Logger::build`] doesn't count.
+#[cfg(feature = "pyo3")]
+pub fn is_installed() -> bool {
+    INSTALLED_HANDLE.load().is_some()
+}
+
+/// The [`ResetHandle`] of the installed logger.
+///
+/// This allows resetting the caches from places far away from where the logger was installed,
+/// without passing the handle around. Returns `None` if no [`Logger`] is
+/// [installed][is_installed].
+#[cfg(feature = "pyo3")]
+pub fn global_reset_handle() -> Option<ResetHandle> {
+    INSTALLED_HANDLE.load().as_deref().cloned()
+}
+
+/// The name the shortcut functions ([`try_init`] and similar) register the handle under.
+///
+/// See [`get_handle`].
+pub const DEFAULT_HANDLE_NAME: &str = "pyo3_log";
+
+/// The registered handles, see [`register_handle`].
+#[cfg(feature = "pyo3")]
+static HANDLES: ArcSwapOption<HashMap<String, ResetHandle>> = ArcSwapOption::const_empty();
+
+/// Registers a [`ResetHandle`] under a name.
+///
+/// This allows getting the handle (by [`get_handle`]) in a different part of the program than
+/// where the logger was created, without passing it around. A handle previously registered under
+/// the same name is replaced.
+///
+/// See also [`Logger::register_handle_as`] to register the handle on install.
+///
+/// ```rust
+/// # use pyo3_log::Logger;
+/// let logger = Logger::default();
+/// pyo3_log::register_handle("my_module", logger.reset_handle());
+/// // Somewhere else
+/// if let Some(handle) = pyo3_log::get_handle("my_module") {
+///     handle.reset();
+/// }
+/// ```
+#[cfg(feature = "pyo3")]
+pub fn register_handle(name: &str, handle: ResetHandle) {
+    HANDLES.rcu(|handles| {
+        let mut handles = handles.as_deref().cloned().unwrap_or_default();
+        handles.insert(name.to_owned(), handle.clone());
+        Some(Arc::new(handles))
+    });
+}
+
+/// Looks up a handle registered by [`register_handle`].
+#[cfg(feature = "pyo3")]
+pub fn get_handle(name: &str) -> Option<ResetHandle> {
+    HANDLES.load().as_ref()?.get(name).cloned()
+}
+
+/// Same as [`extract_max_level`], but from the effective level of the logger.
+#[cfg(feature = "pyo3")]
+fn effective_max_level(levels: &[usize; 5], logger: &Bound<'_, PyAny>) -> PyResult<LevelFilter> {
+    if logger.getattr("disabled")?.is_truthy()? {
+        return Ok(LevelFilter::Off);
+    }
+    let level = logger
+        .call_method0("getEffectiveLevel")?
+        .extract::<usize>()?;
+    // Python skips everything up to (including) the disabled level.
+    let disabled = logger
+        .getattr("manager")?
+        .getattr("disable")?
+        .extract::<usize>()?;
+    Ok(python_threshold(levels, cmp::max(level, disabled + 1)))
+}
+
+/// The filter letting through the levels the Python threshold does.
+#[cfg(feature = "pyo3")]
+fn python_threshold(levels: &[usize; 5], threshold: usize) -> LevelFilter {
+    Level::iter()
+        .filter(|level| levels[level_index(*level)] >= threshold)
+        .max()
+        .map(|level| level.to_level_filter())
+        .unwrap_or(LevelFilter::Off)
+}
+
+/// Installs a default instance of the logger.
+///
+/// In case a logger is already installed, an error is returned. On success, a handle to reset the
+/// internal caches is returned.
+///
+/// The default logger has a filter set to [`Debug`][LevelFilter::Debug] and caching enabled to
+/// [`LoggersAndLevels`][Caching::LoggersAndLevels]. The handle is also
+/// [registered][register_handle] under [`DEFAULT_HANDLE_NAME`].
+#[cfg(feature = "pyo3")]
+pub fn try_init() -> Result<ResetHandle, SetLoggerError> {
+    Logger::default()
+        .register_handle_as(DEFAULT_HANDLE_NAME)
+        .install()
+}
+
+/// Similar to [`try_init`], but panics if there's a previous logger already installed.
+#[cfg(feature = "pyo3")]
+pub fn init() -> ResetHandle {
+    try_init().unwrap()
+}
+
+/// Installs an instance of the logger with the given caching and filter.
+///
+/// This is a shortcut for creating the [`Logger`] with the given [`Caching`], setting its
+/// [`filter`][Logger::filter] and [installing][Logger::install] it. If more configuration is
+/// needed, see [`init_with`].
+///
+/// ```rust
+/// use log::LevelFilter;
+/// use pyo3_log::Caching;
+///
+/// pyo3_log::try_init_with(Caching::Loggers, LevelFilter::Info)
+///     .expect("Someone installed a logger before us :-(");
+/// ```
+///
+/// # Panics
+///
+/// If the Python `logging` module can't be imported (like the [`Default`] of the [`Logger`]).
+/// Use [`Logger::new`] to handle that case.
+#[cfg(feature = "pyo3")]
+pub fn try_init_with(caching: Caching, filter: LevelFilter) -> Result<ResetHandle, SetLoggerError> {
+    Python::with_gil(|py| Logger::new(py, caching).expect("Failed to initialize python logging"))
+        .filter(filter)
+        .register_handle_as(DEFAULT_HANDLE_NAME)
+        .install()
+}
+
+/// Configures and installs an instance of the logger in one go.
+///
+/// The closure gets the default logger (see [`try_init`], including the registration of the
+/// handle) and returns the configured one, which is then installed.
+///
+/// ```rust
+/// use log::LevelFilter;
+///
+/// let handle = pyo3_log::init_with(|logger| {
+///     logger
+///         .filter(LevelFilter::Trace)
+///         .filter_target("my_module::verbose_submodule", LevelFilter::Warn)
+/// });
+/// # let _ = handle;
+/// ```
+///
+/// # Panics
+///
+/// If there's a previous logger already installed or if the Python `logging` module can't be
+/// imported.
+#[cfg(feature = "pyo3")]
+pub fn init_with<F: FnOnce(Logger) -> Logger>(configure: F) -> ResetHandle {
+    configure(Logger::default().register_handle_as(DEFAULT_HANDLE_NAME))
+        .install()
+        .unwrap()
+}
+
+#[cfg(all(feature = "pyo3", test))]
+mod tests {
+    use super::*;
+    use pyo3::exceptions::PyValueError;
+    use pyo3::types::{PyCFunction, PyList};
+
+    /// Makes the Python logger of the given name log everything into a buffer.
+    ///
+    /// Returns the list into which the records are collected.
+    fn capture<'py>(py: Python<'py>, name: &str) -> Bound<'py, PyAny> {
+        let logging = py.import("logging").unwrap();
+        let handler = py
+            .import("logging.handlers")
+            .unwrap()
+            .getattr("BufferingHandler")
+            .unwrap()
+            .call1((usize::MAX,))
+            .unwrap();
+        // Flushing a BufferingHandler throws the records away.
+        let noop = logging
+            .getattr("Handler")
+            .unwrap()
+            .getattr("flush")
+            .unwrap();
+        let noop = noop.call_method1("__get__", (&handler,)).unwrap();
+        handler.setattr("flush", noop).unwrap();
+        let logger = logging
+            .getattr("getLogger")
+            .unwrap()
+            .call1((name,))
+            .unwrap();
+        logger.call_method1("setLevel", (1,)).unwrap();
+        logger.call_method1("addHandler", (&handler,)).unwrap();
+        handler.getattr("buffer").unwrap()
+    }
+
+    #[test]
+    fn python_levels() {
+        for level in Level::iter() {
+            assert_eq!(level_from_python(map_level(level)), Some(level));
+        }
+        assert_eq!(map_level(Level::Error), 40);
+        assert_eq!(level_from_python(50), Some(Level::Error));
+        assert_eq!(level_from_python(39), Some(Level::Warn));
+        assert_eq!(level_from_python(11), Some(Level::Debug));
+        assert_eq!(level_from_python(9), Some(Level::Trace));
+        assert_eq!(level_from_python(1), Some(Level::Trace));
+        assert_eq!(level_from_python(0), None);
+    }
+
+    #[test]
+    fn python_level_filters() {
+        Python::with_gil(|py| {
+            let filter = |value: Bound<'_, PyAny>| level_filter_from_python(py, &value).unwrap();
+            let name = |name: &str| filter(PyString::new(py, name).into_any());
+            let number = |n: usize| filter(n.into_pyobject(py).unwrap().into_any());
+            assert_eq!(name("DEBUG"), LevelFilter::Debug);
+            assert_eq!(name("WARNING"), LevelFilter::Warn);
+            assert_eq!(name("CRITICAL"), LevelFilter::Off);
+            assert_eq!(name("NOTSET"), LevelFilter::Trace);
+            assert_eq!(number(20), LevelFilter::Info);
+            assert_eq!(number(25), LevelFilter::Warn);
+            assert_eq!(number(5), LevelFilter::Trace);
+            assert_eq!(number(3), LevelFilter::Trace);
+
+            let logging = py.import("logging").unwrap();
+            logging
+                .getattr("addLevelName")
+                .unwrap()
+                .call1((15, "VERBOSE_FILTER_TEST"))
+                .unwrap();
+            assert_eq!(name("VERBOSE_FILTER_TEST"), LevelFilter::Info);
+
+            // Case doesn't matter, the Rust names work too and the rest is left to Python.
+            assert_eq!(name("warning"), LevelFilter::Warn);
+            assert_eq!(name("Error"), LevelFilter::Error);
+            assert_eq!(name("trace"), LevelFilter::Trace);
+            assert_eq!(name("off"), LevelFilter::Off);
+            assert_eq!(name("NO_SUCH_LEVEL"), LevelFilter::Trace);
+
+            let err = level_filter_from_python(py, &PyList::empty(py)).unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+        });
+    }
+
+    #[test]
+    fn python_level_filters_logger() {
+        Python::with_gil(|py| {
+            let module = PyModule::new(py, "python_level_filters_logger").unwrap();
+            let logging = py.import("logging").unwrap();
+            module
+                .add("getLogger", logging.getattr("getLogger").unwrap())
+                .unwrap();
+            let names = PyDict::new(py);
+            names.set_item("NOISY", 12).unwrap();
+            module
+                .add("getLevelName", names.getattr("get").unwrap())
+                .unwrap();
+            let logger = Logger::from_module(module, Caching::Nothing)
+                .unwrap()
+                .level_map(Level::Debug, 12)
+                .level_map(Level::Info, 25);
+            let filter =
+                |value: Bound<'_, PyAny>| logger.level_filter_from_python(py, &value).unwrap();
+            let name = |name: &str| filter(PyString::new(py, name).into_any());
+            let number = |n: usize| filter(n.into_pyobject(py).unwrap().into_any());
+            assert_eq!(name("noisy"), LevelFilter::Debug);
+            // Known only to the standard logging module.
+            assert_eq!(name("WARNING"), LevelFilter::Trace);
+            assert_eq!(number(25), LevelFilter::Info);
+            assert_eq!(number(20), LevelFilter::Info);
+            assert_eq!(number(30), LevelFilter::Warn);
+        });
+    }
+
+    #[test]
+    fn handle_registry() {
+        assert!(get_handle("handle_registry").is_none());
+        let threads = (0..4)
+            .map(|i| {
+                thread::spawn(move || {
+                    let logger = Logger::default();
+                    register_handle(&format!("handle_registry_{}", i), logger.reset_handle());
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        for i in 0..4 {
+            assert!(get_handle(&format!("handle_registry_{}", i)).is_some());
+        }
+    }
+
+    #[test]
+    fn default_filter() {
+        let logger = Logger::default();
+        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn set_filter() {
+        let logger = Logger::default().filter(LevelFilter::Info);
+        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Info);
+        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn filter_specific() {
+        let logger = Logger::default()
+            .filter(LevelFilter::Warn)
+            .filter_target("hello_world".to_owned(), LevelFilter::Debug)
+            .filter_target("hello_world::sub".to_owned(), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Trace);
+        assert_eq!(
+            logger.filter_for("hello_world::sub::multi::level"),
+            LevelFilter::Trace
+        );
+        assert_eq!(
+            logger.filter_for("hello_world::another"),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            logger.filter_for("hello_world::another::level"),
+            LevelFilter::Debug
+        );
+        assert_eq!(logger.filter_for("other"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn parse_filters() {
+        let logger = Logger::default().manage_max_level(false).parse_filters(
+            "warn, parse_a=debug,parse_b,parse_c=,parse_a::x=OFF,parse_d=nope,a=b=c",
+        );
+        assert_eq!(logger.filter_for("other"), LevelFilter::Warn);
+        assert_eq!(logger.filter_for("parse_a"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("parse_a::y"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("parse_a::x"), LevelFilter::Off);
+        assert_eq!(logger.filter_for("parse_b"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("parse_c"), LevelFilter::Trace);
+        // Invalid directives are skipped
+        assert_eq!(logger.filter_for("parse_d"), LevelFilter::Warn);
+        assert_eq!(logger.filter_for("a"), LevelFilter::Warn);
+
+        // The last global level wins, the regex part is ignored
+        let logger = Logger::default()
+            .manage_max_level(false)
+            .parse_filters("error,parse_e=info,trace/foo.*bar");
+        assert_eq!(logger.filter_for("other"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("parse_e"), LevelFilter::Info);
+
+        // Nothing in it keeps the defaults
+        let logger = Logger::default()
+            .manage_max_level(false)
+            .filter(LevelFilter::Error)
+            .parse_filters(" , ");
+        assert_eq!(logger.filter_for("other"), LevelFilter::Error);
+    }
+
+    #[test]
+    fn parse_env() {
+        // Setting the variable here would race with other tests reading the environment, so the
+        // value is passed directly and only an unset variable goes through the environment.
+        let logger = Logger::default()
+            .manage_max_level(false)
+            .parse_env_value(Some("info,parse_env=trace".to_owned()))
+            .parse_env_value(None)
+            .parse_env("PYO3_LOG_TEST_PARSE_ENV_UNSET");
+        assert_eq!(logger.filter_for("other"), LevelFilter::Info);
+        assert_eq!(logger.filter_for("parse_env::inner"), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn log_record() {
+        Python::with_gil(|py| {
+            let records = capture(py, "log_record");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .filter_target("log_record::quiet", LevelFilter::Warn);
+            let handle = logger.reset_handle();
+            logger.log_record(Level::Info, "log_record", format_args!("Hello {}", 42));
+            logger.log_record(Level::Info, "log_record::quiet", format_args!("Filtered"));
+            logger.log_record(Level::Error, "log_record::quiet", format_args!("Loud"));
+
+            assert_eq!(records.len().unwrap(), 2);
+            let msg: String = records
+                .get_item(0)
+                .unwrap()
+                .call_method0("getMessage")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(msg, "Hello 42");
+            let name: String = records
+                .get_item(1)
+                .unwrap()
+                .getattr("name")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(name, "log_record.quiet");
+            // Cached the same way as through the facade.
+            let mut cached = handle.cached_targets();
+            cached.sort();
+            assert_eq!(cached, vec!["log_record", "log_record::quiet"]);
+        });
+    }
+
+    #[test]
+    fn empty_target() {
+        Python::with_gil(|py| {
+            let records = capture(py, "empty_target");
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("")
+                        .level(Level::Info)
+                        .args(format_args!("No target"))
+                        .build(),
+                );
+            };
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .empty_target_name("empty_target");
+            let handle = logger.reset_handle();
+            log(&logger);
+            log(&logger);
+            assert_eq!(handle.cached_targets(), vec![""]);
+            handle.reset_target("");
+            assert_eq!(handle.cache_len(), 0);
+            log(&logger);
+
+            // By default, it goes to the root logger, not to the one we capture.
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false);
+            assert_eq!(logger.python_name(""), "");
+            log(&logger);
+
+            assert_eq!(records.len().unwrap(), 3);
+            for record in records.try_iter().unwrap() {
+                let name: String = record.unwrap().getattr("name").unwrap().extract().unwrap();
+                assert_eq!(name, "empty_target");
+            }
+        });
+    }
+
+    #[test]
+    fn logging_module_ref() {
+        Python::with_gil(|py| {
+            let logging = py.import("logging").unwrap();
+            let logger = Logger::from_module(logging.clone(), Caching::Nothing).unwrap();
+            assert!(logger.logging_module_ref(py).unwrap().is(&logging));
+            let lazy = Logger::new(py, Caching::Nothing).unwrap();
+            assert!(lazy.logging_module_ref(py).unwrap().is(&logging));
+        });
+    }
+
+    #[test]
+    fn panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("Plain")).unwrap_err();
+        assert_eq!(super::panic_message(&*payload), "Plain");
+        let payload = std::panic::catch_unwind(|| panic!("Formatted {}", 42)).unwrap_err();
+        assert_eq!(super::panic_message(&*payload), "Formatted 42");
+        let payload = std::panic::catch_unwind(|| std::panic::panic_any(42)).unwrap_err();
+        assert_eq!(super::panic_message(&*payload), "Box<dyn Any>");
+    }
+
+    #[test]
+    fn logging_guard() {
+        assert!(!LoggingGuard::active());
+        {
+            let _outer = LoggingGuard::enter();
+            let _inner = LoggingGuard::enter();
+            assert!(LoggingGuard::active());
+        }
+        assert!(!LoggingGuard::active());
+    }
+
+    #[test]
+    fn dispatch_method() {
+        Python::with_gil(|py| {
+            let records = capture(py, "dispatch_method");
+            py.import("logging")
+                .unwrap()
+                .call_method1("getLogger", ("dispatch_method",))
+                .unwrap()
+                .call_method1("setLevel", (40,))
+                .unwrap();
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("dispatch_method")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            for caching in &[Caching::Nothing, Caching::LoggersAndLevels] {
+                let logger = Logger::new(py, *caching)
+                    .unwrap()
+                    .manage_max_level(false)
+                    .filter(LevelFilter::Info);
+                log(&logger);
+                assert_eq!(records.len().unwrap(), 0);
+
+                let logger = logger.dispatch_method(DispatchMethod::CallHandlers);
+                assert!(logger.enabled(
+                    &Metadata::builder()
+                        .target("dispatch_method")
+                        .level(Level::Info)
+                        .build()
+                ));
+                log(&logger);
+                log(&logger);
+                assert_eq!(records.len().unwrap(), 2);
+                records.call_method0("clear").unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn cache_token() {
+        Python::with_gil(|py| {
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false);
+            let shared = logger.clone_ref(py, CacheSharing::Shared);
+            let forked = logger.clone_ref(py, CacheSharing::Forked);
+            let token = logger.reset_handle().into_token();
+            assert_eq!(token, shared.reset_handle().into_token());
+            assert_ne!(token, forked.reset_handle().into_token());
+
+            logger.log(
+                &Record::builder()
+                    .target("cache_token")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            assert_eq!(logger.reset_handle().cache_len(), 1);
+            let handle = ResetHandle::from_token(token);
+            assert_eq!(handle.cache_len(), 1);
+            handle.reset();
+            assert_eq!(logger.reset_handle().cache_len(), 0);
+        });
+    }
+
+    #[test]
+    fn emit_record() {
+        Python::with_gil(|py| {
+            let records = capture(py, "emit_record");
+            let logging = py.import("logging").unwrap();
+            logging
+                .call_method1("getLogger", ("emit_record.quiet",))
+                .unwrap()
+                .call_method1("setLevel", (40,))
+                .unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false);
+            let make = |level: usize| {
+                let attrs = PyDict::new(py);
+                attrs.set_item("levelno", level).unwrap();
+                attrs.set_item("msg", "Prebuilt").unwrap();
+                attrs.set_item("custom", 42).unwrap();
+                logging.call_method1("makeLogRecord", (attrs,)).unwrap()
+            };
+
+            logger.emit_record(py, "emit_record", make(20)).unwrap();
+            logger.emit_record(py, "emit_record", make(20)).unwrap();
+            logger
+                .emit_record(py, "emit_record::quiet", make(20))
+                .unwrap();
+            logger
+                .emit_record(py, "emit_record::quiet", make(40))
+                .unwrap();
+            let err = logger
+                .emit_record(py, "emit_record", PyDict::new(py).into_any())
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyTypeError>(py));
+
+            assert_eq!(records.len().unwrap(), 3);
+            let custom: usize = records
+                .get_item(0)
+                .unwrap()
+                .getattr("custom")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(custom, 42);
+            let mut cached = logger.reset_handle().cached_targets();
+            cached.sort();
+            assert_eq!(cached, vec!["emit_record", "emit_record::quiet"]);
+        });
+    }
+
+    #[test]
+    fn static_max_level() {
+        let logger = Logger::default()
+            .manage_max_level(false)
+            .filter(LevelFilter::Trace);
+        assert_eq!(logger.max_level(), super::static_max_level());
+        let logger = logger.filter(LevelFilter::Off);
+        assert_eq!(logger.max_level(), LevelFilter::Off);
+    }
+
+    #[test]
+    #[cfg(feature = "min-level-info")]
+    fn min_level_info() {
+        let logger = Logger::default()
+            .manage_max_level(false)
+            .filter(LevelFilter::Trace);
+        assert!(logger.max_level() <= LevelFilter::Info);
+        let enabled = |level| {
+            logger.rust_enabled(
+                &Metadata::builder()
+                    .target("min_level_info")
+                    .level(level)
+                    .build(),
+            )
+        };
+        assert!(enabled(Level::Info));
+        assert!(!enabled(Level::Debug));
+        assert!(!enabled(Level::Trace));
+    }
+
+    #[test]
+    fn origin_process() {
+        Python::with_gil(|py| {
+            let records = capture(py, "origin_process");
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            let record = Record::builder()
+                .target("origin_process")
+                .level(Level::Info)
+                .args(format_args!("Hello"))
+                .build();
+            let origin = Origin::current();
+            logger.log_inner(py, &record, &None, Some(&origin)).unwrap();
+            // As if the record was logged in another process before a fork.
+            let origin = Origin {
+                pid: origin.pid + 1,
+                ..origin
+            };
+            logger.log_inner(py, &record, &None, Some(&origin)).unwrap();
+
+            let process = |idx| -> u32 {
+                records
+                    .get_item(idx)
+                    .unwrap()
+                    .getattr("process")
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            };
+            assert_eq!(process(0), process::id());
+            assert_eq!(process(1), process::id() + 1);
+        });
+    }
+
+    #[test]
+    fn cached_levels_authoritative() {
+        Python::with_gil(|py| {
+            let records = capture(py, "cached_levels");
+            let tee_records = capture(py, "cached_levels_tee");
+            let set_level = |name: &str, level: usize| {
+                py.import("logging")
+                    .unwrap()
+                    .call_method1("getLogger", (name,))
+                    .unwrap()
+                    .call_method1("setLevel", (level,))
+                    .unwrap();
+            };
+            let log = |logger: &Logger, target| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .tee_target("cached_levels::teed", "cached_levels_tee".to_owned());
+
+            log(&logger, "cached_levels");
+            assert_eq!(records.len().unwrap(), 1);
+            // The cached level admits the record, Python is not asked again.
+            set_level("cached_levels", 40);
+            log(&logger, "cached_levels");
+            assert_eq!(records.len().unwrap(), 2);
+
+            // The cached level comes from the additional logger here, so the main one still
+            // needs to be asked.
+            set_level("cached_levels.teed", 40);
+            log(&logger, "cached_levels::teed");
+            log(&logger, "cached_levels::teed");
+            assert_eq!(records.len().unwrap(), 2);
+            assert_eq!(tee_records.len().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn use_current_exception() {
+        Python::with_gil(|py| {
+            let records = capture(py, "current_exception");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .manage_max_level(false)
+                .use_current_exception(true);
+            let log = PyCFunction::new_closure(py, None, None, {
+                let logger = logger.clone_ref(py, CacheSharing::Shared);
+                move |_, _| -> PyResult<()> {
+                    for level in &[Level::Error, Level::Warn] {
+                        logger.log(
+                            &Record::builder()
+                                .target("current_exception")
+                                .level(*level)
+                                .args(format_args!("Failed"))
+                                .build(),
+                        );
+                    }
+                    Ok(())
+                }
+            })
+            .unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("log", log).unwrap();
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+try:
+    raise ValueError("Handled")
+except ValueError:
+    log()
+log()
+"#
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+
+            assert_eq!(records.len().unwrap(), 4);
+            let exc_info = |idx| records.get_item(idx).unwrap().getattr("exc_info").unwrap();
+            let exc = exc_info(0).get_item(1).unwrap();
+            assert_eq!(exc.str().unwrap(), "Handled");
+            // Only the errors get it and only while handling the exception.
+            assert!(exc_info(1).is_none());
+            assert!(exc_info(2).is_none());
+            assert!(exc_info(3).is_none());
+        });
+    }
+
+    #[test]
+    fn fallback_stderr() {
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+import logging
+
+class Counting(logging.Logger):
+    def handle(self, record):
+        self.handled.append(record)
+        super().handle(record)
+
+lonely = Counting("fallback_stderr.lonely", 1)
+lonely.handled = []
+lonely.propagate = False
+"#
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let lonely = locals.get_item("lonely").unwrap().unwrap();
+            let handled = lonely.getattr("handled").unwrap();
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .manage_max_level(false)
+                .fallback_stderr(true)
+                .pin_logger("fallback_stderr", lonely.clone())
+                .unwrap();
+            let log = || {
+                logger.log(
+                    &Record::builder()
+                        .target("fallback_stderr")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+
+            // No handlers, so it goes to stderr instead.
+            log();
+            assert_eq!(handled.len().unwrap(), 0);
+
+            let handler = py
+                .import("logging")
+                .unwrap()
+                .call_method0("NullHandler")
+                .unwrap();
+            lonely.call_method1("addHandler", (handler,)).unwrap();
+            log();
+            assert_eq!(handled.len().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn fallback_stderr_cached() {
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+import logging
+
+class Counting(logging.Filter):
+    def filter(self, record):
+        self.handled.append(record)
+        return True
+
+counting = Counting()
+counting.handled = []
+cached = logging.getLogger("fallback_stderr_cached")
+cached.propagate = False
+cached.addFilter(counting)
+"#
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let cached = locals.get_item("cached").unwrap().unwrap();
+            let handled = locals
+                .get_item("counting")
+                .unwrap()
+                .unwrap()
+                .getattr("handled")
+                .unwrap();
+            let logger = Logger::new(py, Caching::Loggers)
+                .unwrap()
+                .manage_max_level(false)
+                .fallback_stderr(true);
+            let handle = logger.reset_handle();
+            let log = || {
+                logger.log(
+                    &Record::builder()
+                        .target("fallback_stderr_cached")
+                        .level(Level::Warn)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+
+            log();
+            assert_eq!(handled.len().unwrap(), 0);
+            assert_eq!(handle.cache_len(), 1);
+
+            // The cache still remembers there are no handlers.
+            let handler = py
+                .import("logging")
+                .unwrap()
+                .call_method0("NullHandler")
+                .unwrap();
+            cached.call_method1("addHandler", (handler,)).unwrap();
+            log();
+            assert_eq!(handled.len().unwrap(), 0);
+
+            handle.reset();
+            log();
+            assert_eq!(handled.len().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn try_default() {
+        let logger = Logger::try_default().unwrap();
+        assert_eq!(logger.filter_for("try_default"), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn finalizing() {
+        Python::with_gil(|py| {
+            let records = capture(py, "finalizing");
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("finalizing")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            assert!(!logger.logging.finalizing(py));
+            log(&logger);
+            assert_eq!(records.len().unwrap(), 1);
+
+            // Pretend the interpreter is finalizing. The replacement doesn't run any Python code,
+            // so other threads (tests) don't get the GIL before it is restored.
+            let sys = py.import("sys").unwrap();
+            let is_finalizing = sys.getattr("is_finalizing").unwrap();
+            sys.setattr(
+                "is_finalizing",
+                true.into_pyobject(py).unwrap().getattr("__bool__").unwrap(),
+            )
+            .unwrap();
+            let finalizing = Logger::new(py, Caching::Nothing).unwrap();
+            log(&finalizing);
+            sys.setattr("is_finalizing", is_finalizing).unwrap();
+
+            assert_eq!(records.len().unwrap(), 1);
+            let metrics = finalizing.reset_handle().metrics();
+            assert_eq!(metrics.dropped_missing_interpreter, 1);
+            // The function is looked up only once per logger.
+            log(&logger);
+            assert_eq!(records.len().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn case_insensitive_targets() {
+        Python::with_gil(|py| {
+            let records = capture(py, "case_insensitive");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .case_insensitive_targets(true)
+                .filter(LevelFilter::Warn)
+                .filter_target("Case_Insensitive::Sub", LevelFilter::Debug);
+            let log = |target| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Debug)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            log("case_insensitive::sub");
+            log("CASE_INSENSITIVE::SUB::x");
+            log("case_insensitive::other");
+            assert_eq!(records.len().unwrap(), 2);
+            let name = |i| -> String {
+                let record = records.get_item(i).unwrap();
+                record.getattr("name").unwrap().extract().unwrap()
+            };
+            assert_eq!(name(0), "case_insensitive.sub");
+            assert_eq!(name(1), "case_insensitive.sub.x");
+
+            // Both share the same cache entries.
+            let handle = logger.reset_handle();
+            let mut targets = handle.cached_targets();
+            targets.sort();
+            assert_eq!(
+                targets,
+                ["case_insensitive::sub", "case_insensitive::sub::x"]
+            );
+            assert!(logger.enabled(
+                &Metadata::builder()
+                    .target("Case_Insensitive::SUB")
+                    .level(Level::Debug)
+                    .build()
+            ));
+            assert_eq!(
+                handle.effective_filter("CASE_insensitive::sub"),
+                LevelFilter::Debug
+            );
+            handle.reset_target("Case_Insensitive");
+            assert_eq!(handle.cache_len(), 0);
+        });
+
+        // The default stays case-sensitive.
+        let logger = Logger::default().filter_target("Case_Sensitive", LevelFilter::Trace);
+        assert_eq!(logger.filter_for("case_sensitive"), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn filter_target_into() {
+        let logger = Logger::default()
+            .filter(LevelFilter::Warn)
+            .filter_target("into::str", LevelFilter::Debug)
+            .filter_target(String::from("into::string"), LevelFilter::Info)
+            .filter_target(Cow::Borrowed("into::cow"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("into::str"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("into::string"), LevelFilter::Info);
+        assert_eq!(logger.filter_for("into::cow"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("into"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn filter_trailing_separator() {
+        let logger = Logger::default()
+            // Don't interfere with the install_ref test.
+            .manage_max_level(false)
+            .filter(LevelFilter::Warn)
+            .filter_target("trailing::sep::".to_owned(), LevelFilter::Trace)
+            .filter_target("trailing::more::::".to_owned(), LevelFilter::Info)
+            .filter_target("trailing::empty::::seg".to_owned(), LevelFilter::Error);
+        assert_eq!(logger.filter_for("trailing::sep"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("trailing::sep::sub"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("trailing::seperate"), LevelFilter::Warn);
+        assert_eq!(logger.filter_for("trailing::more"), LevelFilter::Info);
+        // Empty segments in the middle are kept as they are, they match the same targets.
+        assert_eq!(
+            logger.filter_for("trailing::empty::::seg"),
+            LevelFilter::Error
+        );
+        assert_eq!(logger.filter_for("trailing::empty::seg"), LevelFilter::Warn);
+
+        let handle = logger.reset_handle();
+        handle.set_filter("trailing::handle::", LevelFilter::Off);
+        assert_eq!(
+            handle.effective_filter("trailing::handle::x"),
+            LevelFilter::Off
+        );
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn filter_target_levels() {
+        let logger = Logger::default()
+            .filter(LevelFilter::Warn)
+            .filter_target_levels("levels", |level| {
+                matches!(level, Level::Info | Level::Error)
+            })
+            .filter_target("levels::all".to_owned(), LevelFilter::Trace)
+            // The predicate wins over the threshold of the same target.
+            .filter_target("levels".to_owned(), LevelFilter::Off);
+        let enabled = |target: &str, level: Level| {
+            logger.rust_enabled(&Metadata::builder().target(target).level(level).build())
+        };
+        assert!(enabled("levels", Level::Error));
+        assert!(!enabled("levels", Level::Warn));
+        assert!(enabled("levels::sub", Level::Info));
+        assert!(!enabled("levels::sub", Level::Debug));
+        assert!(enabled("levels::all", Level::Warn));
+        assert!(enabled("levels::all::sub", Level::Trace));
+        assert!(!enabled("other", Level::Info));
+        assert!(enabled("other", Level::Warn));
+
+        // The threshold is the upper bound of the predicate.
+        assert_eq!(logger.filter_for("levels"), LevelFilter::Info);
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn filter_glob() {
+        let logger = Logger::default()
+            .manage_max_level(false)
+            .filter(LevelFilter::Warn)
+            .filter_target("glob::exact".to_owned(), LevelFilter::Error)
+            .filter_glob("**::tests", LevelFilter::Trace)
+            .filter_glob("glob::**::internal::**", LevelFilter::Off)
+            .filter_glob("glob::v*_x*y", LevelFilter::Info)
+            .filter_glob("glob::*::single", LevelFilter::Debug);
+        assert_eq!(logger.filter_for("tests"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("glob::a::b::tests"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("glob::tests::sub"), LevelFilter::Warn);
+        assert_eq!(logger.filter_for("glob::internal"), LevelFilter::Off);
+        assert_eq!(
+            logger.filter_for("glob::a::b::internal::c"),
+            LevelFilter::Off
+        );
+        assert_eq!(logger.filter_for("glob::v1_xy"), LevelFilter::Info);
+        assert_eq!(logger.filter_for("glob::v12_x_abc_y"), LevelFilter::Info);
+        assert_eq!(logger.filter_for("glob::v1_x"), LevelFilter::Warn);
+        assert_eq!(logger.filter_for("glob::a::single"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("glob::a::b::single"), LevelFilter::Warn);
+        // The exact filters take precedence, including the parents.
+        assert_eq!(logger.filter_for("glob::exact::tests"), LevelFilter::Error);
+        assert!(!logger.rust_enabled(
+            &Metadata::builder()
+                .target("glob::exact::internal")
+                .level(Level::Warn)
+                .build()
+        ));
+        assert!(logger.rust_enabled(
+            &Metadata::builder()
+                .target("glob::x::tests")
+                .level(Level::Trace)
+                .build()
+        ));
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn filter_fn() {
+        let logger = Logger::default()
+            .filter(LevelFilter::Info)
+            .filter_target("noisy::sqlx".to_owned(), LevelFilter::Trace)
+            .filter_fn(Box::new(|metadata| {
+                if metadata.target().contains("sqlx") {
+                    Some(LevelFilter::Warn)
+                } else {
+                    None
+                }
+            }));
+        let meta = |target| Metadata::builder().target(target).build();
+        assert_eq!(
+            logger.filter_for_metadata(&meta("my::sqlx::pool")),
+            LevelFilter::Warn
+        );
+        // Target filters take precedence
+        assert_eq!(
+            logger.filter_for_metadata(&meta("noisy::sqlx")),
+            LevelFilter::Trace
+        );
+        // Falls through to the default
+        assert_eq!(
+            logger.filter_for_metadata(&meta("other")),
+            LevelFilter::Info
+        );
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn custom_separator() {
+        let logger = Logger::default()
+            .target_separator("/")
+            .filter(LevelFilter::Warn)
+            .filter_target("hello_world".to_owned(), LevelFilter::Debug)
+            .filter_target("hello_world/sub".to_owned(), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Debug);
+        assert_eq!(logger.filter_for("hello_world/sub/x"), LevelFilter::Trace);
+        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Warn);
+        assert_eq!(logger.filter_for("hello_world/other"), LevelFilter::Debug);
+
+        Python::with_gil(|py| {
+            let records = capture(py, "custom_separator.sub");
+            logger.log(
+                &Record::builder()
+                    .target("custom_separator/sub")
+                    .level(Level::Warn)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            let record = records.get_item(0).unwrap();
+            let name: String = record.getattr("name").unwrap().extract().unwrap();
+            assert_eq!(name, "custom_separator.sub");
+        });
+    }
+
+    #[test]
+    fn map_target_on_miss() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_inner = Arc::clone(&calls);
+        let logger = Logger::default().map_target(Box::new(move |target| {
+            calls_inner.fetch_add(1, Ordering::Relaxed);
+            assert_eq!(target, "my_crate::internal::worker");
+            Cow::Borrowed("map_target_on_miss.worker")
+        }));
+
+        Python::with_gil(|py| {
+            let records = capture(py, "map_target_on_miss.worker");
+            let record = Record::builder()
+                .target("my_crate::internal::worker")
+                .level(Level::Warn)
+                .args(format_args!("Hello"))
+                .build();
+            logger.log(&record);
+            logger.log(&record);
+            assert_eq!(records.len().unwrap(), 2);
+            let name: String = records
+                .get_item(1)
+                .unwrap()
+                .getattr("name")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(name, "map_target_on_miss.worker");
+        });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn prefix() {
+        let logger = Logger::default()
+            .set_prefix("myext")
+            .filter_target("foo".to_owned(), LevelFilter::Warn);
+        assert_eq!(logger.python_name("foo::bar"), "myext.foo.bar");
+        assert_eq!(logger.python_name(""), "myext");
+        // Filters still work with the Rust targets
+        assert_eq!(logger.filter_for("foo::bar"), LevelFilter::Warn);
+
+        let logger = logger.set_prefix("");
+        assert_eq!(logger.python_name("foo::bar"), "foo.bar");
+    }
+
+    /// Note: this is the only test that installs the global logger.
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn install_ref() {
+        let logger = Logger::default()
+            .register_handle_as("install_ref")
+            .reconfigurable(true);
+        let handle = logger.install_ref().unwrap();
+        assert!(is_installed());
+        assert!(global_reset_handle().is_some());
+        assert!(get_handle("install_ref").is_some());
+
+        Python::with_gil(|py| {
+            let records = capture(py, "install_ref");
+            log::warn!(target: "install_ref", "Hello");
+            assert_eq!(records.len().unwrap(), 1);
+
+            // Filters can be changed at runtime through the handle.
+            handle.set_filter("install_ref::quiet", LevelFilter::Off);
+            log::warn!(target: "install_ref::quiet", "Hello");
+            assert_eq!(records.len().unwrap(), 1);
+            handle.set_default_filter(LevelFilter::Trace);
+            assert_eq!(log::max_level(), LevelFilter::Trace);
+            log::trace!(target: "install_ref", "Hello");
+            assert_eq!(records.len().unwrap(), 2);
+            handle.set_default_filter(LevelFilter::Debug);
+            assert_eq!(log::max_level(), LevelFilter::Debug);
+
+            // Another logger that doesn't manage the max level leaves it alone.
+            Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .manage_max_level(false)
+                .reset_handle()
+                .set_default_filter(LevelFilter::Trace);
+            assert_eq!(log::max_level(), LevelFilter::Debug);
+        });
+        // The original is still usable and shares the cache with the installed one
+        assert_eq!(logger.filter_for("install_ref"), LevelFilter::Debug);
+        assert!(logger.lookup("install_ref").is_some());
+        handle.reset();
+        assert!(logger.lookup("install_ref").is_none());
+
+        // Only the handle of the installed logger can replace it, not one merely sharing the cache.
+        Python::with_gil(|py| {
+            let shared = logger.clone_ref(py, CacheSharing::Shared).reset_handle();
+            assert!(shared.reconfigure(Logger::default()).is_err());
+            assert!(!shared.uninstall());
+            assert!(!logger.reset_handle().uninstall());
+        });
+        assert!(is_installed());
+
+        // The whole logger can be replaced.
+        let new_handle = handle
+            .reconfigure(Logger::default().filter(LevelFilter::Warn))
+            .unwrap();
+        assert_eq!(log::max_level(), LevelFilter::Warn);
+        // The old handle is stale now.
+        assert!(handle.reconfigure(Logger::default()).is_err());
+        assert!(!handle.uninstall());
+        Python::with_gil(|py| {
+            let records = capture(py, "install_ref.reconfigured");
+            log::info!(target: "install_ref::reconfigured", "Hello");
+            log::warn!(target: "install_ref::reconfigured", "Hello");
+            assert_eq!(records.len().unwrap(), 1);
+
+            // Uninstalled, it drops everything.
+            assert!(new_handle.uninstall());
+            assert!(!is_installed());
+            assert!(global_reset_handle().is_none());
+            assert_eq!(log::max_level(), LevelFilter::Off);
+            log::warn!(target: "install_ref::reconfigured", "Hello");
+            assert_eq!(records.len().unwrap(), 1);
+
+            // But another one can be installed.
+            Logger::default().install().unwrap();
+            assert!(is_installed());
+            log::warn!(target: "install_ref::reconfigured", "Hello");
+            assert_eq!(records.len().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn custom_module() {
+        Python::with_gil(|py| {
+            let logging = py.import("logging").unwrap();
+            Logger::from_module(logging, Caching::Nothing).unwrap();
+            let sys = py.import("sys").unwrap();
+            let err = Logger::from_module(sys, Caching::Nothing).unwrap_err();
+            assert!(err.is_instance_of::<PyAttributeError>(py));
+        });
+    }
+
+    #[test]
+    fn forwards_record() {
+        Python::with_gil(|py| {
+            let records = capture(py, "forwards_record.sub");
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            logger.log(
+                &Record::builder()
+                    .target("forwards_record::sub")
+                    .level(Level::Warn)
+                    .args(format_args!("Hello {}", 42))
+                    .build(),
+            );
+            let record = records.get_item(0).unwrap();
+            let name: String = record.getattr("name").unwrap().extract().unwrap();
+            assert_eq!(name, "forwards_record.sub");
+            let level: usize = record.getattr("levelno").unwrap().extract().unwrap();
+            assert_eq!(level, 30);
+            let msg: String = record
+                .call_method0("getMessage")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(msg, "Hello 42");
+            let rust_target: String = record.getattr("rust_target").unwrap().extract().unwrap();
+            assert_eq!(rust_target, "forwards_record::sub");
+        });
+    }
+
+    #[test]
+    fn buffered() {
+        Python::with_gil(|py| {
+            let records = capture(py, "buffered");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .buffered(16);
+            for i in 0..3 {
+                logger.log(
+                    &Record::builder()
+                        .target("buffered")
+                        .level(Level::Info)
+                        .args(format_args!("Message {}", i))
+                        .build(),
+                );
+            }
+            // The flush releases the GIL so the background thread can get to it.
+            logger.flush();
+            assert_eq!(records.len().unwrap(), 3);
+            for i in 0..3 {
+                let msg: String = records
+                    .get_item(i)
+                    .unwrap()
+                    .call_method0("getMessage")
+                    .unwrap()
+                    .extract()
+                    .unwrap();
+                assert_eq!(msg, format!("Message {}", i));
+            }
+        });
+    }
+
+    #[test]
+    fn async_friendly() {
+        Python::with_gil(|py| {
+            let records = capture(py, "async_friendly");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .async_friendly(true);
+            assert!(logger.config.buffer.is_some());
+            logger.log(
+                &Record::builder()
+                    .target("async_friendly")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            logger.flush();
+            assert_eq!(records.len().unwrap(), 1);
+            let logger = logger.async_friendly(false);
+            assert!(logger.config.buffer.is_none());
+        });
+    }
+
+    #[test]
+    fn format_only_enabled() {
+        use std::fmt::Display;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Counted<'a>(&'a AtomicUsize);
+
+        impl Display for Counted<'_> {
+            fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                fmt.write_str("counted")
+            }
+        }
+
+        Python::with_gil(|py| {
+            let records = capture(py, "format_only_enabled");
+            py.import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("format_only_enabled",))
+                .unwrap()
+                .call_method1("setLevel", (40,))
+                .unwrap();
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            let count = AtomicUsize::new(0);
+            for level in [Level::Info, Level::Error] {
+                logger.log(
+                    &Record::builder()
+                        .target("format_only_enabled")
+                        .level(level)
+                        .args(format_args!("{}", Counted(&count)))
+                        .build(),
+                );
+            }
+            assert_eq!(records.len().unwrap(), 1);
+            assert_eq!(count.load(Ordering::Relaxed), 1);
+        });
+    }
+
+    #[test]
+    fn caching() {
+        Python::with_gil(|py| {
+            let records = capture(py, "caching");
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("caching")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false);
+            let handle = logger.reset_handle();
+            log(&logger);
+            assert_eq!(logger.reset_handle().cache_len(), 1);
+
+            let logger = logger.caching(Caching::Nothing);
+            assert_eq!(logger.reset_handle().cache_len(), 0);
+            log(&logger);
+            assert_eq!(logger.reset_handle().cache_len(), 0);
+            assert!(logger.lookup("caching").is_none());
+
+            let logger = logger.caching(Caching::Loggers);
+            log(&logger);
+            assert_eq!(logger.reset_handle().cache_len(), 1);
+            assert_eq!(records.len().unwrap(), 3);
+
+            // The handle from before the switch still works on the logger.
+            assert_eq!(handle.cache_len(), 1);
+            handle.reset();
+            assert_eq!(logger.reset_handle().cache_len(), 0);
+            let logger = logger.filter(LevelFilter::Warn);
+            handle.set_filter("caching", LevelFilter::Info);
+            log(&logger);
+            assert_eq!(records.len().unwrap(), 4);
+        });
+    }
+
+    #[test]
+    fn caching_for() {
+        Python::with_gil(|py| {
+            let records = capture(py, "caching_for");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .caching_for("caching_for::volatile", Caching::Nothing)
+                .caching_for("caching_for::volatile::keep", Caching::Loggers);
+            let handle = logger.reset_handle();
+            for target in &[
+                "caching_for",
+                "caching_for::volatile",
+                "caching_for::volatile::1",
+                "caching_for::volatile::2",
+                "caching_for::volatile::keep::sub",
+            ] {
+                assert!(logger.enabled(
+                    &Metadata::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .build()
+                ));
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
+
+            assert_eq!(records.len().unwrap(), 5);
+            let mut cached = handle.cached_targets();
+            cached.sort();
+            assert_eq!(
+                cached,
+                vec!["caching_for", "caching_for::volatile::keep::sub"]
+            );
+            assert!(logger.lookup("caching_for::volatile::1").is_none());
+            // Caching just the loggers, not the levels.
+            let keep = logger.lookup("caching_for::volatile::keep::sub").unwrap();
+            assert_eq!(keep.local.as_ref().unwrap().filter, LevelFilter::max());
+        });
+    }
+
+    #[test]
+    fn persistent_children() {
+        let mut children = Children::default();
+        for i in 0..1000 {
+            children.insert(Arc::from(format!("k{}", i)), i);
+        }
+        let snapshot = children.clone();
+        children.insert(Arc::from("k5"), 5000);
+        for i in (0..1000).step_by(2) {
+            children.remove(&format!("k{}", i));
+        }
+        children.remove("missing");
+        assert_eq!(children.get("k5"), Some(&5000));
+        assert_eq!(children.get("k4"), None);
+        assert_eq!(children.iter().count(), 500);
+
+        // The copy is not affected.
+        assert_eq!(snapshot.iter().count(), 1000);
+        for i in 0..1000 {
+            assert_eq!(snapshot.get(&format!("k{}", i)), Some(&i));
+        }
+
+        for i in (1..1000).step_by(2) {
+            children.remove(&format!("k{}", i));
+        }
+        assert!(children.is_empty());
+        assert_eq!(children.iter().count(), 0);
+    }
+
+    #[test]
+    fn reset_target() {
+        Python::with_gil(|py| {
+            let logger = Logger::new(py, Caching::LoggersAndLevels).unwrap();
+            for target in ["reset_target::a::b", "reset_target::a", "reset_target::c"] {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
+            let cached = |target| {
+                logger
+                    .lookup(target)
+                    .map(|node| node.local.is_some())
+                    .unwrap_or(false)
+            };
+            assert!(cached("reset_target::a::b"));
+            assert!(cached("reset_target::a"));
+            assert!(cached("reset_target::c"));
+            let handle = logger.reset_handle();
+            assert_eq!(handle.cache_len(), 3);
+            let mut targets = handle.cached_targets();
+            targets.sort();
+            assert_eq!(
+                targets,
+                ["reset_target::a", "reset_target::a::b", "reset_target::c"]
+            );
+
+            handle.reset_target("reset_target::a");
+            assert_eq!(handle.cache_len(), 1);
+            assert!(!cached("reset_target::a::b"));
+            assert!(!cached("reset_target::a"));
+            assert!(cached("reset_target::c"));
+
+            // Nothing there, nothing happens.
+            logger.reset_handle().reset_target("reset_target::x::y");
+            assert!(cached("reset_target::c"));
+        });
+    }
+
+    #[test]
+    fn cache_active_loggers() {
+        Python::with_gil(|py| {
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("cache_active_loggers",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (40,)).unwrap();
+            let logger = Logger::new(py, Caching::Loggers).unwrap();
+            let log = || {
+                logger.log(
+                    &Record::builder()
+                        .target("cache_active_loggers")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            let handle = logger.reset_handle();
+            log();
+            assert_eq!(handle.cache_len(), 0);
+            py_logger.call_method1("setLevel", (10,)).unwrap();
+            log();
+            assert_eq!(handle.cache_len(), 1);
+        });
+    }
+
+    #[test]
+    fn rust_filter_skips_cache() {
+        Python::with_gil(|py| {
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .filter_target("rust_filter_skips_cache".to_owned(), LevelFilter::Off);
+            let record = Record::builder()
+                .target("rust_filter_skips_cache::sub")
+                .level(Level::Error)
+                .args(format_args!("Hello"))
+                .build();
+            assert!(!logger.enabled(record.metadata()));
+            logger.log(&record);
+            assert_eq!(logger.reset_handle().cache_len(), 0);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn tracing_layer() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        Python::with_gil(|py| {
+            let records = capture(py, "tracing_layer");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .manage_max_level(false);
+            let subscriber = tracing_subscriber::registry().with(logger.tracing_layer());
+            tracing::subscriber::with_default(subscriber, || {
+                let outer = tracing::info_span!("outer", request = 42, user = "alice");
+                let _outer = outer.enter();
+                let inner =
+                    tracing::info_span!("inner", user = "bob", attempt = tracing::field::Empty);
+                inner.record("attempt", 2);
+                let _inner = inner.enter();
+                tracing::info!(target: "tracing_layer", status = 200, "Done {}", "now");
+                tracing::trace!(target: "tracing_layer::quiet", "Filtered out");
+            });
+
+            assert_eq!(records.len().unwrap(), 1);
+            let record = records.get_item(0).unwrap();
+            let get = |attr: &str| record.getattr(attr).unwrap();
+            assert_eq!(
+                get("getMessage")
+                    .call0()
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "Done now"
+            );
+            assert_eq!(get("name").extract::<String>().unwrap(), "tracing_layer");
+            assert_eq!(get("request").extract::<u64>().unwrap(), 42);
+            // The inner span wins.
+            assert_eq!(get("user").extract::<String>().unwrap(), "bob");
+            assert_eq!(get("attempt").extract::<u64>().unwrap(), 2);
+            assert_eq!(get("status").extract::<u64>().unwrap(), 200);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn config_serde() {
+        let config: LoggerConfig = serde_json::from_str(
+            r#"{"top_filter": "WARN", "filters": {"config_serde::net": "TRACE"}, "prefix": "ext"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.caching, Caching::LoggersAndLevels);
+        let logger = Python::with_gil(|py| Logger::from_config(py, config.clone())).unwrap();
+        assert_eq!(logger.filter_for("config_serde"), LevelFilter::Warn);
+        assert_eq!(
+            logger.filter_for("config_serde::net::a"),
+            LevelFilter::Trace
+        );
+        assert_eq!(logger.python_name("config_serde"), "ext.config_serde");
+        let snapshot = logger.config_snapshot();
+        assert_eq!(snapshot, config);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<LoggerConfig>(&json).unwrap(), config);
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn level_map() {
+        Python::with_gil(|py| {
+            let records = capture(py, "level_map");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .filter(LevelFilter::Trace)
+                .level_map(Level::Trace, 7);
+            let log = || {
+                logger.log(
+                    &Record::builder()
+                        .target("level_map")
+                        .level(Level::Trace)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+            log();
+            let level: usize = records
+                .get_item(0)
+                .unwrap()
+                .getattr("levelno")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(level, 7);
+
+            // The cached level uses the mapping too.
+            logger.reset_handle().reset();
+            py.import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("level_map",))
+                .unwrap()
+                .call_method1("setLevel", (8,))
+                .unwrap();
+            log();
+            assert!(!logger.enabled(
+                &Metadata::builder()
+                    .target("level_map")
+                    .level(Level::Trace)
+                    .build()
+            ));
+            assert_eq!(records.len().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn missing_interpreter_buffer() {
+        Python::with_gil(|py| {
+            let records = capture(py, "missing_interpreter_buffer");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .on_missing_interpreter(MissingInterpreter::Buffer);
+            let record = |msg| {
+                Record::builder()
+                    .target("missing_interpreter_buffer")
+                    .level(Level::Info)
+                    .args(msg)
+                    .build()
+            };
+            // We do have an interpreter, so pretend the record came while there was none.
+            logger.log_missing_interpreter(&record(format_args!("First")));
+            assert_eq!(records.len().unwrap(), 0);
+            // The kept record goes first, once Python is available.
+            logger.log(&record(format_args!("Second")));
+            let msgs = (0..2)
+                .map(|i| {
+                    records
+                        .get_item(i)
+                        .unwrap()
+                        .call_method0("getMessage")
+                        .unwrap()
+                        .extract::<String>()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(msgs, ["First", "Second"]);
+        });
+    }
+
+    #[test]
+    fn missing_interpreter_drained() {
+        Python::with_gil(|py| {
+            let records = capture(py, "missing_interpreter_drained");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .on_missing_interpreter(MissingInterpreter::Buffer);
+            logger.log_missing_interpreter(
+                &Record::builder()
+                    .target("missing_interpreter_drained")
+                    .level(Level::Info)
+                    .args(format_args!("Kept"))
+                    .build(),
+            );
+            // Not only logging sends the kept records, any use of Python does.
+            assert!(logger.enabled(
+                &Metadata::builder()
+                    .target("missing_interpreter_drained::other")
+                    .level(Level::Info)
+                    .build()
+            ));
+            assert_eq!(records.len().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn interpreter_identified() {
+        Python::with_gil(|py| {
+            let interpreter = current_interpreter(py);
+            assert_ne!(interpreter, 0);
+            assert_eq!(current_interpreter(py), interpreter);
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            assert!(logger.own_interpreter(py));
+        });
+    }
+
+    #[test]
+    fn other_interpreter() {
+        Python::with_gil(|py| {
+            let records = capture(py, "other_interpreter");
+            let mut logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .on_missing_interpreter(MissingInterpreter::Buffer);
+            // Pretend the logger comes from some other interpreter.
+            logger.config_mut().interpreter = 0;
+            logger.log(
+                &Record::builder()
+                    .target("other_interpreter")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            assert_eq!(records.len().unwrap(), 0);
+            assert_eq!(logger.pending.lock().unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn metrics() {
+        let logger = Logger::default();
+        let handle = logger.reset_handle();
+        assert_eq!(handle.metrics(), LoggerMetrics::default());
+        let record = Record::builder()
+            .target("metrics")
+            .level(Level::Info)
+            .args(format_args!("Hello"))
+            .build();
+        logger.log_missing_interpreter(&record);
+        let logger = logger.on_missing_interpreter(MissingInterpreter::Buffer);
+        for _ in 0..=MISSING_INTERPRETER_CAPACITY {
+            logger.log_missing_interpreter(&record);
+        }
+        let metrics = handle.metrics();
+        assert_eq!(metrics.dropped_missing_interpreter, 1);
+        assert_eq!(metrics.dropped_overflow, 1);
+        assert_eq!(metrics.dropped_errors, 0);
+    }
+
+    #[test]
+    fn rate_limit() {
+        Python::with_gil(|py| {
+            let records = capture(py, "rate_limit");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .rate_limit("rate_limit", 5)
+                .rate_limit("rate_limit::vip", 1000);
+            let log = |target| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            for _ in 0..10 {
+                log("rate_limit::sub");
+                log("rate_limit::vip");
+            }
+            // Some tokens might have been refilled in the meantime, but surely not all of them.
+            let len = records.len().unwrap();
+            assert!((15..20).contains(&len), "{}", len);
+            assert_eq!(
+                logger.reset_handle().metrics().dropped_rate_limited,
+                20 - len as u64
+            );
+        });
+    }
+
+    #[test]
+    fn sample() {
+        Python::with_gil(|py| {
+            let records = capture(py, "sample");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .filter_target("sample::off", LevelFilter::Off)
+                .sample("sample", 0.5)
+                .sample("sample::all", 1.0);
+            let log = |target| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            for _ in 0..1000 {
+                log("sample::sub");
+                log("sample::off");
+            }
+            // Extremely unlikely to be out of these bounds.
+            let len = records.len().unwrap();
+            assert!((350..650).contains(&len), "{}", len);
+            // The ones filtered out by levels don't count.
+            assert_eq!(
+                logger.reset_handle().metrics().dropped_sampled,
+                1000 - len as u64
+            );
+
+            for _ in 0..100 {
+                log("sample::all::sub");
+            }
+            assert_eq!(records.len().unwrap(), len + 100);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Sampling rate")]
+    fn sample_invalid() {
+        Logger::default().sample("sample_invalid", 0.0);
+    }
+
+    #[test]
+    fn gil_timeout() {
+        Python::with_gil(|py| {
+            let records = capture(py, "gil_timeout");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .gil_timeout(Duration::from_millis(50));
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("gil_timeout")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+
+            // We hold the GIL while waiting for the thread. This would deadlock without the
+            // timeout.
+            let shared = logger.share();
+            thread::spawn(move || log(&shared)).join().unwrap();
+            assert_eq!(records.len().unwrap(), 0);
+            // Let the worker get the GIL.
+            logger.flush();
+            assert_eq!(records.len().unwrap(), 1);
+
+            // Holding the GIL, it logs directly.
+            log(&logger);
+            assert_eq!(records.len().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn reset_if_changed() {
+        Python::with_gil(|py| {
+            let records = capture(py, "reset_if_changed");
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("reset_if_changed",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (40,)).unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels).unwrap();
+            let handle = logger.reset_handle();
+            let log = |level| {
+                logger.log(
+                    &Record::builder()
+                        .target("reset_if_changed")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+
+            handle.reset_if_changed();
+            // Caches the Error level
+            log(Level::Info);
+            assert_eq!(records.len().unwrap(), 0);
+
+            py_logger.call_method1("setLevel", (20,)).unwrap();
+            assert!(handle.reset_if_changed());
+            log(Level::Info);
+            assert_eq!(records.len().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn reset_and_sync() {
+        Python::with_gil(|py| {
+            let records = capture(py, "reset_and_sync");
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("reset_and_sync",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (40,)).unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .filter_target("reset_and_sync", LevelFilter::Error);
+            let handle = logger.reset_handle();
+            let log = |level| {
+                logger.log(
+                    &Record::builder()
+                        .target("reset_and_sync")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+
+            log(Level::Error);
+            assert_eq!(records.len().unwrap(), 1);
+            assert_eq!(handle.cache_len(), 1);
+
+            py_logger.call_method1("setLevel", (10,)).unwrap();
+            handle
+                .reset_and_sync(py, |py| {
+                    // The cache is already gone when syncing.
+                    assert_eq!(handle.cache_len(), 0);
+                    let level = py_logger.call_method0("getEffectiveLevel")?;
+                    let filter = level_filter_from_python(py, &level)?;
+                    handle.set_filter("reset_and_sync", filter);
+                    Ok(())
+                })
+                .unwrap();
+            log(Level::Debug);
+            assert_eq!(records.len().unwrap(), 2);
+
+            let err = handle
+                .reset_and_sync(py, |_| Err(PyValueError::new_err("failed")))
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+            assert_eq!(handle.cache_len(), 0);
+        });
+    }
+
+    #[test]
+    fn reconfiguration_undetectable() {
+        Python::with_gil(|py| {
+            // Only the public part of the logging module, without the internals we need.
+            let module = PyModule::new(py, "reconfiguration_undetectable").unwrap();
+            let logging = py.import("logging").unwrap();
+            module
+                .add("getLogger", logging.getattr("getLogger").unwrap())
+                .unwrap();
+            let logger = Logger::from_module(module, Caching::LoggersAndLevels).unwrap();
+            let handle = logger.reset_handle();
+            // Can't tell, so nothing is reset (instead of resetting all the time).
+            assert!(!handle.reset_if_changed());
+            assert!(!handle.reset_if_changed());
+        });
+    }
+
+    #[test]
+    fn watch_reconfiguration() {
+        Python::with_gil(|py| {
+            let records = capture(py, "watch_reconfiguration");
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("watch_reconfiguration",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (40,)).unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .watch_reconfiguration(true);
+            let log = |level| {
+                logger.log(
+                    &Record::builder()
+                        .target("watch_reconfiguration")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+
+            log(Level::Info);
+            assert_eq!(records.len().unwrap(), 0);
+
+            py_logger.call_method1("setLevel", (20,)).unwrap();
+            // This one goes to Python and notices the change.
+            log(Level::Error);
+            log(Level::Info);
+            assert_eq!(records.len().unwrap(), 2);
+        });
+    }
+
+    #[test]
+    fn message_formatter() {
+        Python::with_gil(|py| {
+            let records = capture(py, "message_formatter");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .message_formatter(Box::new(|record| {
+                    format!("[{}] {}", record.level(), record.args())
+                }));
+            logger.log(
+                &Record::builder()
+                    .target("message_formatter")
+                    .level(Level::Warn)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            let msg: String = records
+                .get_item(0)
+                .unwrap()
+                .call_method0("getMessage")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(msg, "[WARN] Hello");
+        });
+    }
+
+    #[test]
+    fn record_name_fn() {
+        Python::with_gil(|py| {
+            let records = capture(py, "record_name");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .record_name_fn(Box::new(|target| target.replace("::", "/")));
+            logger.log(
+                &Record::builder()
+                    .target("record_name::sub")
+                    .level(Level::Warn)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            // Still sent to the usual logger, only with a different name.
+            assert_eq!(records.len().unwrap(), 1);
+            let record = records.get_item(0).unwrap();
+            let name: String = record.getattr("name").unwrap().extract().unwrap();
+            assert_eq!(name, "record_name/sub");
+        });
+    }
+
+    #[test]
+    fn redact() {
+        Python::with_gil(|py| {
+            let records = capture(py, "redact");
+            let logger =
+                Logger::new(py, Caching::Nothing)
+                    .unwrap()
+                    .redact(Box::new(|msg| match msg.find("token=") {
+                        Some(pos) => Cow::Owned(format!("{}token=<redacted>", &msg[..pos])),
+                        None => Cow::Borrowed(msg),
+                    }));
+            for token in ["abc123", "xyz"] {
+                logger.log(
+                    &Record::builder()
+                        .target("redact")
+                        .level(Level::Warn)
+                        .args(format_args!("Connecting with token={}", token))
+                        .build(),
+                );
+            }
+            logger.log(
+                &Record::builder()
+                    .target("redact")
+                    .level(Level::Warn)
+                    .args(format_args!("Nothing to see here"))
+                    .build(),
+            );
+            let msg = |i| -> String {
+                let record = records.get_item(i).unwrap();
+                record
+                    .call_method0("getMessage")
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            };
+            assert_eq!(msg(0), "Connecting with token=<redacted>");
+            assert_eq!(msg(1), "Connecting with token=<redacted>");
+            assert_eq!(msg(2), "Nothing to see here");
+        });
+    }
+
+    #[test]
+    fn record_filter() {
+        Python::with_gil(|py| {
+            let records = capture(py, "record_filter");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .record_filter(Box::new(|record| {
+                    !record.args().to_string().contains("secret")
+                }));
+            for msg in ["Hello", "The secret is 42", "World"] {
+                logger.log(
+                    &Record::builder()
+                        .target("record_filter")
+                        .level(Level::Warn)
+                        .args(format_args!("{}", msg))
+                        .build(),
+                );
+            }
+            assert_eq!(records.len().unwrap(), 2);
+            let msg = |i| -> String {
+                let record = records.get_item(i).unwrap();
+                record
+                    .call_method0("getMessage")
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            };
+            assert_eq!(msg(0), "Hello");
+            assert_eq!(msg(1), "World");
+        });
+    }
+
+    #[test]
+    fn tap() {
+        Python::with_gil(|py| {
+            let records = capture(py, "tap");
+            py.import("logging")
+                .unwrap()
+                .call_method1("getLogger", ("tap",))
+                .unwrap()
+                .call_method1("setLevel", (30,))
+                .unwrap();
+            let tapped = Arc::new(Mutex::new(Vec::new()));
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false)
+                .filter(LevelFilter::Trace)
+                .record_filter(Box::new(|record| {
+                    !record.args().to_string().contains("secret")
+                }))
+                .tap(Box::new({
+                    let tapped = Arc::clone(&tapped);
+                    move |record| tapped.lock().unwrap().push(record.args().to_string())
+                }));
+            // The first one gets the Python level into the cache.
+            for (level, msg) in [
+                (Level::Warn, "Warning"),
+                (Level::Info, "Info"),
+                (Level::Error, "The secret is 42"),
+                (Level::Debug, "Debug"),
+                (Level::Error, "Error"),
+            ] {
+                logger.log(
+                    &Record::builder()
+                        .target("tap")
+                        .level(level)
+                        .args(format_args!("{}", msg))
+                        .build(),
+                );
+            }
+            let logged = records
+                .try_iter()
+                .unwrap()
+                .map(|record| {
+                    record
+                        .unwrap()
+                        .call_method0("getMessage")
+                        .unwrap()
+                        .extract::<String>()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(logged, vec!["Warning", "Error"]);
+            assert_eq!(*tapped.lock().unwrap(), logged);
+        });
+    }
 
-        Some(Arc::clone(node))
+    #[test]
+    fn captured_logs() {
+        let logger = Python::with_gil(|py| Logger::new(py, Caching::Nothing)).unwrap();
+        let records = testing::with_captured_logs(|| {
+            logger.log(
+                &Record::builder()
+                    .target("captured_logs::sub")
+                    .level(Level::Error)
+                    .file(Some("src/captured.rs"))
+                    .line(Some(42))
+                    .args(format_args!("Hello {}", "world"))
+                    .build(),
+            );
+        });
+        let record = records
+            .into_iter()
+            .find(|r| r.target == "captured_logs.sub")
+            .unwrap();
+        assert_eq!(record.level, 40);
+        assert_eq!(record.message, "Hello world");
+        assert_eq!(record.file.as_deref(), Some("src/captured.rs"));
+        assert_eq!(record.line, Some(42));
     }
 
-    /// Logs stuff
-    ///
-    /// Returns a logger to be cached, if any. If it already found a cached logger or if caching is
-    /// turned off, returns None.
-    fn log_inner(
-        &self,
-        py: Python<'_>,
-        record: &Record,
-        cache: &Option<Arc<CacheNode>>,
-    ) -> PyResult<Option<PyObject>> {
-        let msg = format!("{}", record.args());
-        let log_level = map_level(record.level());
-        let target = record.target().replace("::", ".");
-        let cached_logger = cache
-            .as_ref()
-            .and_then(|node| node.local.as_ref())
-            .map(|local| &local.logger);
-        let (logger, cached) = match cached_logger {
-            Some(cached) => (cached.bind(py).clone(), true),
-            None => (
-                self.logging
-                    .bind(py)
-                    .getattr("getLogger")?
-                    .call1((&target,))?,
-                false,
-            ),
-        };
-        // We need to check for this ourselves. For some reason, the logger.handle does not check
-        // it. And besides, we can save ourselves few python calls if it's turned off.
-        if is_enabled_for(&logger, record.level())? {
-            let none = py.None();
-            // TODO: kv pairs, if enabled as a feature?
-            let record = logger.call_method1(
-                "makeRecord",
-                (
-                    target,
-                    log_level,
-                    record.file(),
-                    record.line().unwrap_or_default(),
-                    msg,
-                    PyTuple::empty(py), // args
-                    &none,              // exc_info
-                ),
-            )?;
-            logger.call_method1("handle", (record,))?;
-        }
+    #[test]
+    fn effective_filter() {
+        Python::with_gil(|py| {
+            py.import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("effective_filter.python",))
+                .unwrap()
+                .call_method1("setLevel", (30,))
+                .unwrap();
+            capture(py, "effective_filter.rust");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .filter(LevelFilter::Trace)
+                .filter_target("effective_filter::rust".to_owned(), LevelFilter::Info);
+            let handle = logger.reset_handle();
+            assert_eq!(
+                handle.effective_filter("effective_filter::python"),
+                LevelFilter::Warn
+            );
+            assert_eq!(
+                handle.effective_filter("effective_filter::rust"),
+                LevelFilter::Info
+            );
+            // Nothing got cached by asking.
+            assert_eq!(handle.cache_len(), 0);
 
-        let cache_logger = if !cached && self.caching != Caching::Nothing {
-            Some(logger.into())
-        } else {
-            None
-        };
+            // Uses the cache if available (and stale).
+            logger.log(
+                &Record::builder()
+                    .target("effective_filter::python")
+                    .level(Level::Error)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            py.import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("effective_filter.python",))
+                .unwrap()
+                .call_method1("setLevel", (40,))
+                .unwrap();
+            assert_eq!(
+                handle.effective_filter("effective_filter::python"),
+                LevelFilter::Warn
+            );
+        });
+    }
 
-        Ok(cache_logger)
+    #[test]
+    fn effective_filter_without_python() {
+        Python::with_gil(|py| {
+            py.import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("effective_filter_without_python",))
+                .unwrap()
+                .call_method1("setLevel", (30,))
+                .unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .filter(LevelFilter::Info);
+            let mut handle = logger.reset_handle();
+            assert_eq!(
+                handle.effective_filter("effective_filter_without_python"),
+                LevelFilter::Warn
+            );
+            // Pretend the logger is from an interpreter that's no longer there.
+            Arc::make_mut(&mut handle.config).interpreter = 0;
+            assert_eq!(
+                handle.effective_filter("effective_filter_without_python"),
+                LevelFilter::Info
+            );
+        });
     }
 
-    fn filter_for(&self, target: &str) -> LevelFilter {
-        let mut start = 0;
-        let mut filter = self.top_filter;
-        while let Some(end) = target[start..].find("::") {
-            if let Some(f) = self.filters.get(&target[..start + end]) {
-                filter = *f;
+    #[test]
+    fn timestamps() {
+        Python::with_gil(|py| {
+            let records = capture(py, "timestamps");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .with_timestamps(true)
+                .buffered(16);
+            let before = SystemTime::now();
+            logger.log(
+                &Record::builder()
+                    .target("timestamps")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            let after = SystemTime::now();
+            // We hold the GIL, so the background thread can't send it yet.
+            std::thread::sleep(Duration::from_millis(100));
+            logger.flush();
+            let created: f64 = records
+                .get_item(0)
+                .unwrap()
+                .getattr("created")
+                .unwrap()
+                .extract()
+                .unwrap();
+            let secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+            assert!(created >= secs(before) - 0.001);
+            assert!(created <= secs(after) + 0.001);
+        });
+    }
+
+    #[test]
+    fn max_cache_entries() {
+        Python::with_gil(|py| {
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .max_cache_entries(4);
+            let log = |target: &str| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+            for i in 0..20 {
+                // Keep this one in use.
+                log("max_cache_entries::hot");
+                log(&format!("max_cache_entries::cold::{}", i));
+                assert!(logger.reset_handle().cache_len() <= 4);
             }
-            start += end + 2;
-        }
-        if let Some(f) = self.filters.get(target) {
-            filter = *f;
-        }
+            let cached = |target| {
+                logger
+                    .lookup(target)
+                    .map(|node| node.local.is_some())
+                    .unwrap_or(false)
+            };
+            assert!(cached("max_cache_entries::hot"));
+            assert!(!cached("max_cache_entries::cold::0"));
+            assert!(cached("max_cache_entries::cold::19"));
+        });
+    }
 
-        filter
+    #[test]
+    #[cfg(feature = "validate-cache")]
+    fn validate_cache() {
+        Python::with_gil(|py| {
+            let records = capture(py, "validate_cache");
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("validate_cache",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (40,)).unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .validate_cache(true);
+            logger.reset_handle().reset_if_changed();
+            let log = |level| {
+                logger.log(
+                    &Record::builder()
+                        .target("validate_cache")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
+
+            log(Level::Info);
+            log(Level::Info);
+            assert_eq!(records.len().unwrap(), 0);
+            py_logger.call_method1("setLevel", (20,)).unwrap();
+            // The cache says disabled, but the check notices the change.
+            log(Level::Info);
+            assert_eq!(records.len().unwrap(), 1);
+        });
     }
 
-    fn enabled_inner(&self, metadata: &Metadata, cache: &Option<Arc<CacheNode>>) -> bool {
-        let cache_filter = cache
-            .as_ref()
-            .and_then(|node| node.local.as_ref())
-            .map(|local| local.filter)
-            .unwrap_or_else(LevelFilter::max);
+    #[test]
+    fn tee_target() {
+        Python::with_gil(|py| {
+            let records = capture(py, "tee_target");
+            let audit = capture(py, "tee_target_audit");
+            py.import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("tee_target",))
+                .unwrap()
+                .call_method1("setLevel", (40,))
+                .unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .tee_target("tee_target::sub", "tee_target_audit".to_owned());
+            let log = |target, level| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                )
+            };
 
-        metadata.level() <= cache_filter && metadata.level() <= self.filter_for(metadata.target())
+            // Twice, to go through the cache too.
+            for _ in 0..2 {
+                log("tee_target::sub::deep", Level::Info);
+                log("tee_target::sub", Level::Error);
+                log("tee_target::subway", Level::Error);
+            }
+            assert_eq!(records.len().unwrap(), 4);
+            assert_eq!(audit.len().unwrap(), 4);
+        });
     }
 
-    fn store_to_cache(&self, py: Python<'_>, target: &str, entry: CacheEntry) {
-        let path = target.split("::");
+    #[test]
+    fn flush_handlers() {
+        Python::with_gil(|py| {
+            let target = py
+                .import("logging.handlers")
+                .unwrap()
+                .getattr("BufferingHandler")
+                .unwrap()
+                .call1((usize::MAX,))
+                .unwrap();
+            let memory = py
+                .import("logging.handlers")
+                .unwrap()
+                .getattr("MemoryHandler")
+                .unwrap()
+                .call1((usize::MAX, 50, &target))
+                .unwrap();
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("flush_handlers",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (1,)).unwrap();
+            py_logger.call_method1("addHandler", (&memory,)).unwrap();
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            logger.log(
+                &Record::builder()
+                    .target("flush_handlers")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            let buffered = || target.getattr("buffer").unwrap().len().unwrap();
+            assert_eq!(buffered(), 0);
+            logger.flush();
+            assert_eq!(buffered(), 1);
+        });
+    }
 
-        let orig = self.cache.load();
-        // Construct a new cache structure and insert the new root.
-        let new = orig.store_to_cache_recursive(py, path, entry);
-        // Note: In case of collision, the cache update is lost. This is fine, as we simply lose a
-        // tiny bit of performance and will cache the thing next time.
-        //
-        // We err on the side of losing it here (instead of overwriting), because if the cache is
-        // reset, we don't want to re-insert the old value we have.
-        self.cache.compare_and_swap(orig, new);
+    #[test]
+    fn pin_logger() {
+        Python::with_gil(|py| {
+            // Not registered with the logging module, so getLogger can't find it.
+            let pinned = py
+                .import("logging")
+                .unwrap()
+                .getattr("Logger")
+                .unwrap()
+                .call1(("pinned_custom",))
+                .unwrap();
+            let handler = py
+                .import("logging.handlers")
+                .unwrap()
+                .getattr("BufferingHandler")
+                .unwrap()
+                .call1((usize::MAX,))
+                .unwrap();
+            pinned.call_method1("addHandler", (&handler,)).unwrap();
+            pinned.call_method1("setLevel", (20,)).unwrap();
+            let records = handler.getattr("buffer").unwrap();
+            let looked_up = capture(py, "pinned.target");
+
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .pin_logger("pinned::target", pinned)
+                .unwrap();
+            for level in [Level::Info, Level::Debug, Level::Info] {
+                logger.log(
+                    &Record::builder()
+                        .target("pinned::target")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
+
+            assert_eq!(records.len().unwrap(), 2);
+            let name: String = records
+                .get_item(0)
+                .unwrap()
+                .getattr("name")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(name, "pinned_custom");
+            assert_eq!(looked_up.len().unwrap(), 0);
+            assert!(logger.reset_handle().cached_targets().is_empty());
+
+            let err = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .pin_logger("pinned", PyString::new(py, "x").into_any())
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyAttributeError>(py));
+        });
     }
-}
 
-impl Default for Logger {
-    fn default() -> Self {
+    #[test]
+    fn init_python() {
         Python::with_gil(|py| {
-            Self::new(py, Caching::LoggersAndLevels).expect("Failed to initialize python logging")
-        })
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .init_python(py, |_py, logging| {
+                    logging.call_method1("addLevelName", (7, "INIT_PYTHON_TEST"))?;
+                    Ok(())
+                })
+                .unwrap();
+            let name: String = py
+                .import("logging")
+                .unwrap()
+                .call_method1("getLevelName", (7,))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(name, "INIT_PYTHON_TEST");
+            drop(logger);
+
+            let err = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .init_python(py, |_py, _logging| {
+                    Err(PyValueError::new_err("Setup failed"))
+                })
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyValueError>(py));
+        });
     }
-}
 
-impl Log for Logger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        let cache = self.lookup(metadata.target());
+    #[test]
+    fn ensure_null_handler() {
+        Python::with_gil(|py| {
+            let logging = py.import("logging").unwrap();
+            let get_logger = logging.getattr("getLogger").unwrap();
+            let handlers = |name: &str| -> Vec<String> {
+                get_logger
+                    .call1((name,))
+                    .unwrap()
+                    .getattr("handlers")
+                    .unwrap()
+                    .try_iter()
+                    .unwrap()
+                    .map(|h| h.unwrap().get_type().name().unwrap().to_string())
+                    .collect()
+            };
+            // Not propagating to root, which might have handlers from other tests.
+            get_logger
+                .call1(("null_handler",))
+                .unwrap()
+                .setattr("propagate", false)
+                .unwrap();
+            capture(py, "null_handler.configured");
 
-        self.enabled_inner(metadata, &cache)
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .ensure_null_handler(true);
+            for target in ["null_handler::bare", "null_handler::configured::sub"] {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Warn)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
+            assert_eq!(handlers("null_handler.bare"), ["NullHandler"]);
+            assert!(handlers("null_handler.configured.sub").is_empty());
+        });
     }
 
-    fn log(&self, record: &Record) {
-        let cache = self.lookup(record.target());
+    #[test]
+    fn route_to_handler() {
+        Python::with_gil(|py| {
+            let handler = py
+                .import("logging.handlers")
+                .unwrap()
+                .getattr("BufferingHandler")
+                .unwrap()
+                .call1((usize::MAX,))
+                .unwrap();
+            handler.call_method1("setLevel", (20,)).unwrap();
+            let records = handler.getattr("buffer").unwrap();
+            // The Python logger would take even the debug records, but it is skipped.
+            let looked_up = capture(py, "route_to_handler");
 
-        if self.enabled_inner(record.metadata(), &cache) {
-            Python::with_gil(|py| {
-                // If an exception were triggered before this attempt to log,
-                // store it to the side for now and restore it afterwards.
-                let maybe_existing_exception = PyErr::take(py);
-                match self.log_inner(py, record, &cache) {
-                    Ok(Some(logger)) => {
-                        let filter = match self.caching {
-                            Caching::Nothing => unreachable!(),
-                            Caching::Loggers => LevelFilter::max(),
-                            Caching::LoggersAndLevels => extract_max_level(logger.bind(py))
-                                .unwrap_or_else(|e| {
-                                    // See detailed NOTE below
-                                    e.restore(py);
-                                    LevelFilter::max()
-                                }),
-                        };
-
-                        let entry = CacheEntry { filter, logger };
-                        self.store_to_cache(py, record.target(), entry);
-                    }
-                    Ok(None) => (),
-                    Err(e) => {
-                        // NOTE: If an exception was triggered _during_ logging, restore it as current Python exception.
-                        // We have to use PyErr::restore because we cannot return a PyResult from the Log trait's log method.
-                        e.restore(py);
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .route_to_handler(handler)
+                .unwrap();
+            for level in [Level::Info, Level::Debug, Level::Warn] {
+                logger.log(
+                    &Record::builder()
+                        .target("route_to_handler::sub")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
+
+            assert_eq!(records.len().unwrap(), 2);
+            let name: String = records
+                .get_item(0)
+                .unwrap()
+                .getattr("name")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(name, "route_to_handler.sub");
+            assert_eq!(looked_up.len().unwrap(), 0);
+
+            let err = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .route_to_handler(PyString::new(py, "x").into_any())
+                .unwrap_err();
+            assert!(err.is_instance_of::<PyAttributeError>(py));
+        });
+    }
+
+    #[test]
+    fn clone_ref() {
+        Python::with_gil(|py| {
+            let _records = capture(py, "clone_ref");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .manage_max_level(false);
+            let shared = logger.clone_ref(py, CacheSharing::Shared);
+            let forked = logger.clone_ref(py, CacheSharing::Forked);
+            logger.log(
+                &Record::builder()
+                    .target("clone_ref")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            assert!(shared.lookup("clone_ref").is_some());
+            assert!(forked.lookup("clone_ref").is_none());
+
+            // The filters are independent.
+            shared
+                .reset_handle()
+                .set_filter("clone_ref", LevelFilter::Off);
+            assert_eq!(shared.filter_for("clone_ref"), LevelFilter::Off);
+            assert_eq!(logger.filter_for("clone_ref"), LevelFilter::Debug);
+        });
+    }
+
+    #[test]
+    fn dedupe_window() {
+        Python::with_gil(|py| {
+            let records = capture(py, "dedupe_window");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .dedupe_window(Duration::from_secs(3600));
+            let log = |level, msg: &str| {
+                logger.log(
+                    &Record::builder()
+                        .target("dedupe_window")
+                        .level(level)
+                        .args(format_args!("{}", msg))
+                        .build(),
+                );
+            };
+            log(Level::Info, "a");
+            log(Level::Info, "a");
+            log(Level::Info, "a");
+            // Levels are tracked separately.
+            log(Level::Warn, "a");
+            log(Level::Info, "b");
+            log(Level::Info, "b");
+            logger.flush();
+
+            let messages = records
+                .try_iter()
+                .unwrap()
+                .map(|r| {
+                    r.unwrap()
+                        .call_method0("getMessage")
+                        .unwrap()
+                        .extract::<String>()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                messages,
+                [
+                    "a",
+                    "a",
+                    "last message repeated 2 times",
+                    "b",
+                    "last message repeated 1 times",
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn dedupe_window_closes() {
+        Python::with_gil(|py| {
+            let records = capture(py, "dedupe_window_closes");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .dedupe_window(Duration::from_millis(200));
+            let log = || {
+                logger.log(
+                    &Record::builder()
+                        .target("dedupe_window_closes")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            log();
+            log();
+            assert_eq!(records.len().unwrap(), 1);
+            py.allow_threads(|| std::thread::sleep(Duration::from_millis(300)));
+            log();
+            // The summary and the message itself, as a new window started.
+            assert_eq!(records.len().unwrap(), 3);
+        });
+    }
+
+    #[test]
+    // Uses the debug and trace levels.
+    #[cfg(not(feature = "min-level-info"))]
+    fn use_effective_level() {
+        Python::with_gil(|py| {
+            let logging = py.import("logging").unwrap();
+            let get_logger = logging.getattr("getLogger").unwrap();
+            let parent = get_logger.call1(("effective_level",)).unwrap();
+            parent.call_method1("setLevel", (25,)).unwrap();
+            let child = get_logger.call1(("effective_level.child",)).unwrap();
+
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .use_effective_level(true);
+            let probing = Logger::new(py, Caching::LoggersAndLevels).unwrap();
+            // The child inherits the level of the parent.
+            for py_logger in [&parent, &child] {
+                assert_eq!(
+                    logger.config.python_max_level(py_logger).unwrap(),
+                    LevelFilter::Warn
+                );
+            }
+            for level in [1, 10, 15, 20, 30, 40, 45] {
+                child.call_method1("setLevel", (level,)).unwrap();
+                assert_eq!(
+                    logger.config.python_max_level(&child).unwrap(),
+                    probing.config.python_max_level(&child).unwrap(),
+                    "{}",
+                    level,
+                );
+            }
+            child.setattr("disabled", true).unwrap();
+            assert_eq!(
+                logger.config.python_max_level(&child).unwrap(),
+                LevelFilter::Off
+            );
+        });
+    }
+
+    #[test]
+    fn enabled_fills_cache() {
+        Python::with_gil(|py| {
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("enabled_fills_cache",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (30,)).unwrap();
+            let logger = Logger::new(py, Caching::LoggersAndLevels).unwrap();
+            let metadata = |level| {
+                Metadata::builder()
+                    .target("enabled_fills_cache")
+                    .level(level)
+                    .build()
+            };
+            assert!(!logger.enabled(&metadata(Level::Info)));
+            let cached = logger.lookup("enabled_fills_cache").unwrap();
+            assert_eq!(cached.local.as_ref().unwrap().filter, LevelFilter::Warn);
+            assert!(logger.enabled(&metadata(Level::Warn)));
+
+            // Without caching the levels, there's nothing to learn.
+            let logger = Logger::new(py, Caching::Loggers).unwrap();
+            assert!(logger.enabled(&metadata(Level::Info)));
+            assert!(logger.lookup("enabled_fills_cache").is_none());
+        });
+    }
+
+    #[test]
+    fn disabled_without_gil() {
+        Python::with_gil(|py| {
+            let get_logger = py.import("logging").unwrap().getattr("getLogger").unwrap();
+            for name in ["disabled_without_gil.log", "disabled_without_gil.enabled"] {
+                let py_logger = get_logger.call1((name,)).unwrap();
+                py_logger.call_method1("setLevel", (30,)).unwrap();
+            }
+            let logger = Logger::new(py, Caching::LoggersAndLevels).unwrap();
+            let log = |logger: &Logger, target| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            let enabled = |logger: &Logger, target| {
+                logger.enabled(
+                    &Metadata::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .build(),
+                )
+            };
+
+            // The first attempts take the GIL (we have it already).
+            log(&logger, "disabled_without_gil::log");
+            assert!(!enabled(&logger, "disabled_without_gil::enabled"));
+
+            // We hold the GIL the whole time, so the thread would get stuck if it tried to take it.
+            let copy = logger.share();
+            let (sender, receiver) = std::sync::mpsc::channel();
+            std::thread::spawn(move || {
+                for target in ["disabled_without_gil::log", "disabled_without_gil::enabled"] {
+                    log(&copy, target);
+                    assert!(!enabled(&copy, target));
+                }
+                // Not cached yet, but the check must not wait for the GIL to find out.
+                enabled(&copy, "disabled_without_gil::uncached");
+                sender.send(()).unwrap();
+            });
+            receiver
+                .recv_timeout(Duration::from_secs(10))
+                .expect("The disabled records took the GIL");
+            assert!(logger.lookup("disabled_without_gil::uncached").is_none());
+        });
+    }
+
+    #[test]
+    fn thread_info() {
+        Python::with_gil(|py| {
+            let records = capture(py, "thread_info");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .with_thread_info(true);
+            py.allow_threads(|| {
+                for name in [Some("thread_info_worker"), None] {
+                    let mut builder = thread::Builder::new();
+                    if let Some(name) = name {
+                        builder = builder.name(name.to_owned());
                     }
+                    let logger = logger.share();
+                    builder
+                        .spawn(move || {
+                            logger.log(
+                                &Record::builder()
+                                    .target("thread_info")
+                                    .level(Level::Info)
+                                    .args(format_args!("Hello"))
+                                    .build(),
+                            );
+                        })
+                        .unwrap()
+                        .join()
+                        .unwrap();
+                }
+            });
+            assert_eq!(records.len().unwrap(), 2);
+            let name = |i| -> String {
+                let record = records.get_item(i).unwrap();
+                record.getattr("threadName").unwrap().extract().unwrap()
+            };
+            assert_eq!(name(0), "thread_info_worker");
+            assert_eq!(name(1), "<unnamed>");
+        });
+    }
+
+    #[test]
+    fn thread_info_ids() {
+        Python::with_gil(|py| {
+            let records = capture(py, "thread_info_ids");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .with_thread_info(true);
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("thread_info_ids")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            py.allow_threads(|| {
+                // Sequentially, so we know which record comes from which thread.
+                for _ in 0..3 {
+                    let logger = logger.share();
+                    thread::spawn(move || {
+                        log(&logger);
+                        log(&logger);
+                    })
+                    .join()
+                    .unwrap();
+                }
+            });
+            assert_eq!(records.len().unwrap(), 6);
+            let id = |i| -> u64 {
+                let record = records.get_item(i).unwrap();
+                record.getattr("thread").unwrap().extract().unwrap()
+            };
+            // Stable within a thread.
+            assert_eq!(id(0), id(1));
+            assert_eq!(id(2), id(3));
+            assert_eq!(id(4), id(5));
+            // Distinct across threads.
+            assert_ne!(id(0), id(2));
+            assert_ne!(id(0), id(4));
+            assert_ne!(id(2), id(4));
+        });
+    }
+
+    #[test]
+    fn source_id() {
+        Python::with_gil(|py| {
+            let records = capture(py, "source_id");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .source_id("my_extension");
+            for context in [false, true] {
+                let _guard = if context {
+                    let mut fields = HashMap::new();
+                    fields.insert(
+                        "ext_source".to_owned(),
+                        PyString::new(py, "context").into_any().unbind(),
+                    );
+                    Some(Logger::push_context(fields))
+                } else {
+                    None
                 };
+                logger.log(
+                    &Record::builder()
+                        .target("source_id")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
 
-                // If there was a prior exception, restore it now
-                // This ensures that the earliest thrown exception will be the one that's visible to the caller.
-                if let Some(e) = maybe_existing_exception {
-                    e.restore(py);
+            assert_eq!(records.len().unwrap(), 2);
+            let attr = |i, name| -> Option<String> {
+                let record = records.get_item(i).unwrap();
+                record.getattr(name).ok().map(|v| v.extract().unwrap())
+            };
+            assert_eq!(attr(0, "ext_source").unwrap(), "my_extension");
+            assert!(attr(0, "kv_ext_source").is_none());
+            assert_eq!(attr(1, "ext_source").unwrap(), "my_extension");
+            assert_eq!(attr(1, "kv_ext_source").unwrap(), "context");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "backtrace")]
+    fn capture_backtrace() {
+        use std::backtrace::{Backtrace, BacktraceStatus};
+
+        // We can't (reliably) change the environment, so check it matches what it says.
+        let enabled = Backtrace::capture().status() == BacktraceStatus::Captured;
+        Python::with_gil(|py| {
+            let records = capture(py, "capture_backtrace");
+            let logger = Logger::new(py, Caching::LoggersAndLevels)
+                .unwrap()
+                .capture_backtrace(Level::Warn);
+            for level in [Level::Error, Level::Warn, Level::Info] {
+                logger.log(
+                    &Record::builder()
+                        .target("capture_backtrace")
+                        .level(level)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            }
+            assert_eq!(records.len().unwrap(), 3);
+            let has_backtrace = |i| {
+                let record = records.get_item(i).unwrap();
+                match record.getattr("rust_backtrace") {
+                    Ok(backtrace) => {
+                        let backtrace: String = backtrace.extract().unwrap();
+                        assert!(backtrace.contains("capture_backtrace"), "{}", backtrace);
+                        true
+                    }
+                    Err(_) => false,
                 }
-            })
-        }
+            };
+            assert_eq!(has_backtrace(0), enabled);
+            assert_eq!(has_backtrace(1), enabled);
+            assert!(!has_backtrace(2));
+        });
     }
 
-    fn flush(&self) {}
-}
+    #[test]
+    fn reserved_extra() {
+        Python::with_gil(|py| {
+            let records = capture(py, "reserved_extra");
+            // A logger refusing one more key than the usual ones, like with a custom record
+            // factory.
+            let locals = PyDict::new(py);
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+import logging
+
+class Picky(logging.Logger):
+    def makeRecord(self, *args, **kwargs):
+        extra = args[8] if len(args) > 8 else kwargs.get("extra")
+        if extra and "custom" in extra:
+            raise KeyError("Attempt to overwrite 'custom' in LogRecord")
+        return super().makeRecord(*args, **kwargs)
+
+picky = Picky("reserved_extra.picky")
+picky.parent = logging.getLogger("reserved_extra")
+"#
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+            let picky = locals.get_item("picky").unwrap().unwrap();
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .manage_max_level(false)
+                .pin_logger("reserved_extra::picky", picky)
+                .unwrap();
+            let fields = |key: &str| {
+                let mut fields = HashMap::new();
+                fields.insert(
+                    key.to_owned(),
+                    PyString::new(py, "value").into_any().unbind(),
+                );
+                fields
+            };
+            let log = |target| {
+                logger.log(
+                    &Record::builder()
+                        .target(target)
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+
+            {
+                let _context = Logger::push_context(fields("message"));
+                log("reserved_extra");
+            }
+            {
+                let _context = Logger::push_context(fields("custom"));
+                log("reserved_extra::picky");
+            }
 
-fn map_level(level: Level) -> usize {
-    match level {
-        Level::Error => 40,
-        Level::Warn => 30,
-        Level::Info => 20,
-        Level::Debug => 10,
-        Level::Trace => 5,
+            assert_eq!(records.len().unwrap(), 2);
+            let value = |idx, attr| -> String {
+                records
+                    .get_item(idx)
+                    .unwrap()
+                    .getattr(attr)
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            };
+            assert_eq!(value(0, "kv_message"), "value");
+            assert_eq!(value(1, "kv_custom"), "value");
+        });
     }
-}
 
-fn is_enabled_for(logger: &Bound<'_, PyAny>, level: Level) -> PyResult<bool> {
-    let level = map_level(level);
-    logger.call_method1("isEnabledFor", (level,))?.is_truthy()
-}
+    #[test]
+    fn push_context() {
+        Python::with_gil(|py| {
+            let records = capture(py, "push_context");
+            let logger = Logger::new(py, Caching::LoggersAndLevels).unwrap();
+            let log = |logger: &Logger| {
+                logger.log(
+                    &Record::builder()
+                        .target("push_context")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .build(),
+                );
+            };
+            let fields = |value: &str| {
+                let mut fields = HashMap::new();
+                fields.insert(
+                    "request_id".to_owned(),
+                    PyString::new(py, value).into_any().unbind(),
+                );
+                fields
+            };
+            let outer = Logger::push_context(fields("outer"));
+            log(&logger);
+            {
+                let _inner = Logger::push_context(fields("inner"));
+                log(&logger);
+                // Other threads don't see it.
+                let shared = logger.share();
+                py.allow_threads(|| thread::spawn(move || log(&shared)).join().unwrap());
+            }
+            log(&logger);
+            drop(outer);
+            log(&logger);
 
-fn extract_max_level(logger: &Bound<'_, PyAny>) -> PyResult<LevelFilter> {
-    use Level::*;
-    for l in &[Trace, Debug, Info, Warn, Error] {
-        if is_enabled_for(logger, *l)? {
-            return Ok(l.to_level_filter());
-        }
+            let request_id = |i| -> Option<String> {
+                let record = records.get_item(i).unwrap();
+                match record.getattr("request_id") {
+                    Ok(id) => Some(id.extract().unwrap()),
+                    Err(_) => None,
+                }
+            };
+            assert_eq!(records.len().unwrap(), 5);
+            assert_eq!(request_id(0).as_deref(), Some("outer"));
+            assert_eq!(request_id(1).as_deref(), Some("inner"));
+            assert_eq!(request_id(2), None);
+            assert_eq!(request_id(3).as_deref(), Some("outer"));
+            assert_eq!(request_id(4), None);
+        });
     }
 
-    Ok(LevelFilter::Off)
-}
+    #[test]
+    fn unknown_location() {
+        Python::with_gil(|py| {
+            let records = capture(py, "unknown_location");
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            logger.log(
+                &Record::builder()
+                    .target("unknown_location")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            let record = records.get_item(0).unwrap();
+            let pathname: String = record.getattr("pathname").unwrap().extract().unwrap();
+            let filename: String = record.getattr("filename").unwrap().extract().unwrap();
+            let lineno: u32 = record.getattr("lineno").unwrap().extract().unwrap();
+            assert_eq!(pathname, "");
+            assert_eq!(filename, "");
+            assert_eq!(lineno, 0);
+        });
+    }
 
-/// Installs a default instance of the logger.
-///
-/// In case a logger is already installed, an error is returned. On success, a handle to reset the
-/// internal caches is returned.
-///
-/// The default logger has a filter set to [`Debug`][LevelFilter::Debug] and caching enabled to
-/// [`LoggersAndLevels`][Caching::LoggersAndLevels].
-pub fn try_init() -> Result<ResetHandle, SetLoggerError> {
-    Logger::default().install()
-}
+    #[test]
+    fn on_error() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-/// Similar to [`try_init`], but panics if there's a previous logger already installed.
-pub fn init() -> ResetHandle {
-    try_init().unwrap()
-}
+        Python::with_gil(|py| {
+            let py_logger = py
+                .import("logging")
+                .unwrap()
+                .getattr("getLogger")
+                .unwrap()
+                .call1(("on_error",))
+                .unwrap();
+            py_logger.call_method1("setLevel", (1,)).unwrap();
+            // The base handler raises NotImplementedError on emit.
+            let handler = py
+                .import("logging")
+                .unwrap()
+                .getattr("Handler")
+                .unwrap()
+                .call0()
+                .unwrap();
+            py_logger.call_method1("addHandler", (&handler,)).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+            let failures = Arc::new(AtomicUsize::new(0));
+            let failures_cp = Arc::clone(&failures);
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .on_error(Box::new(move |_| {
+                    failures_cp.fetch_add(1, Ordering::Relaxed);
+                }));
+            logger.log(
+                &Record::builder()
+                    .target("on_error")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .build(),
+            );
+            assert_eq!(failures.load(Ordering::Relaxed), 1);
+            assert_eq!(logger.reset_handle().metrics().dropped_errors, 1);
+            assert!(PyErr::take(py).is_none());
+            py_logger
+                .call_method1("removeHandler", (&handler,))
+                .unwrap();
+        });
+    }
 
     #[test]
-    fn default_filter() {
-        let logger = Logger::default();
-        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Debug);
-        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Debug);
+    fn module_path() {
+        Python::with_gil(|py| {
+            let records = capture(py, "module_path");
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            let record = Record::builder()
+                .target("module_path")
+                .level(Level::Warn)
+                .args(format_args!("Hello"))
+                .module_path(Some("my_crate::sub"))
+                .file(Some("src/sub.rs"))
+                .line(Some(42))
+                .build();
+            logger.log(&record);
+            logger.log(
+                &Record::builder()
+                    .module_path(None)
+                    .target("module_path")
+                    .build(),
+            );
+
+            let get = |idx: usize, attr: &str| {
+                let value = records.get_item(idx).unwrap().getattr(attr).unwrap();
+                value.str().unwrap().to_string()
+            };
+            assert_eq!(get(0, "module"), "my_crate::sub");
+            assert_eq!(get(0, "funcName"), "my_crate::sub");
+            assert_eq!(get(0, "lineno"), "42");
+            assert_eq!(get(0, "filename"), "sub.rs");
+            assert_eq!(get(1, "funcName"), "None");
+        });
     }
 
     #[test]
-    fn set_filter() {
-        let logger = Logger::default().filter(LevelFilter::Info);
-        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Info);
-        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Info);
+    fn static_paths() {
+        Python::with_gil(|py| {
+            let records = capture(py, "static_paths");
+            for buffered in [false, true] {
+                let mut logger = Logger::new(py, Caching::Nothing).unwrap();
+                if buffered {
+                    logger = logger.buffered(16);
+                }
+                let module_path = String::from("my_crate::sub");
+                let file = String::from("src/sub.rs");
+                logger.log(
+                    &Record::builder()
+                        .target("static_paths")
+                        .level(Level::Warn)
+                        .module_path_static(Some("my_crate::sub"))
+                        .file_static(Some("src/sub.rs"))
+                        .line(Some(42))
+                        .build(),
+                );
+                logger.log(
+                    &Record::builder()
+                        .target("static_paths")
+                        .level(Level::Warn)
+                        .module_path(Some(&module_path))
+                        .file(Some(&file))
+                        .line(Some(42))
+                        .build(),
+                );
+                py.allow_threads(|| logger.flush());
+            }
+
+            assert_eq!(records.len().unwrap(), 4);
+            let get = |idx: usize, attr: &str| {
+                let value = records.get_item(idx).unwrap().getattr(attr).unwrap();
+                value.str().unwrap().to_string()
+            };
+            for idx in 0..4 {
+                for attr in ["module", "funcName", "pathname", "filename", "lineno"] {
+                    assert_eq!(get(idx, attr), get(0, attr), "{} of {}", attr, idx);
+                }
+            }
+            assert_eq!(get(0, "module"), "my_crate::sub");
+            assert_eq!(get(0, "pathname"), "src/sub.rs");
+        });
     }
 
     #[test]
-    fn filter_specific() {
-        let logger = Logger::default()
-            .filter(LevelFilter::Warn)
-            .filter_target("hello_world".to_owned(), LevelFilter::Debug)
-            .filter_target("hello_world::sub".to_owned(), LevelFilter::Trace);
-        assert_eq!(logger.filter_for("hello_world"), LevelFilter::Debug);
-        assert_eq!(logger.filter_for("hello_world::sub"), LevelFilter::Trace);
-        assert_eq!(
-            logger.filter_for("hello_world::sub::multi::level"),
-            LevelFilter::Trace
-        );
-        assert_eq!(
-            logger.filter_for("hello_world::another"),
-            LevelFilter::Debug
-        );
-        assert_eq!(
-            logger.filter_for("hello_world::another::level"),
-            LevelFilter::Debug
-        );
-        assert_eq!(logger.filter_for("other"), LevelFilter::Warn);
+    #[cfg(feature = "kv")]
+    fn kv_extra() {
+        Python::with_gil(|py| {
+            let records = capture(py, "kv_extra");
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            logger.log(
+                &Record::builder()
+                    .target("kv_extra")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .key_values(&[("answer", 42), ("msg", 1)])
+                    .build(),
+            );
+            let record = records.get_item(0).unwrap();
+            let answer: i64 = record.getattr("answer").unwrap().extract().unwrap();
+            assert_eq!(answer, 42);
+            let msg: String = record.getattr("msg").unwrap().extract().unwrap();
+            assert_eq!(msg, "Hello");
+            let kv_msg: i64 = record.getattr("kv_msg").unwrap().extract().unwrap();
+            assert_eq!(kv_msg, 1);
+
+            // The key-value pair takes precedence over our own rust_target.
+            logger.log(
+                &Record::builder()
+                    .target("kv_extra")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .key_values(&[("rust_target", 7)])
+                    .build(),
+            );
+            let record = records.get_item(1).unwrap();
+            let rust_target: i64 = record.getattr("rust_target").unwrap().extract().unwrap();
+            assert_eq!(rust_target, 7);
+
+            // But not over the source id.
+            logger.source_id("kv_extra_ext").log(
+                &Record::builder()
+                    .target("kv_extra")
+                    .level(Level::Info)
+                    .args(format_args!("Hello"))
+                    .key_values(&[("ext_source", 8)])
+                    .build(),
+            );
+            let record = records.get_item(2).unwrap();
+            let source: String = record.getattr("ext_source").unwrap().extract().unwrap();
+            assert_eq!(source, "kv_extra_ext");
+            let kv_source: i64 = record.getattr("kv_ext_source").unwrap().extract().unwrap();
+            assert_eq!(kv_source, 8);
+        });
+    }
+
+    #[test]
+    #[cfg(all(feature = "kv", feature = "serde"))]
+    fn json_extra() {
+        use log::kv::Value;
+
+        Python::with_gil(|py| {
+            let records = capture(py, "json_extra");
+            let nested = serde_json::json!({"ids": [1, 2], "meta": {"ok": true, "ratio": 0.5}});
+            for buffered in [false, true] {
+                let mut logger = Logger::new(py, Caching::Nothing).unwrap().json_extra(true);
+                if buffered {
+                    logger = logger.buffered(16);
+                }
+                logger.log(
+                    &Record::builder()
+                        .target("json_extra")
+                        .level(Level::Info)
+                        .args(format_args!("Hello"))
+                        .key_values(&[
+                            ("int", Value::from(42)),
+                            ("float", Value::from(1.5)),
+                            ("string", Value::from("x")),
+                            ("nested", Value::from_serde(&nested)),
+                        ])
+                        .build(),
+                );
+                py.allow_threads(|| logger.flush());
+            }
+
+            assert_eq!(records.len().unwrap(), 2);
+            let json = py.import("json").unwrap();
+            for record in records.try_iter().unwrap() {
+                let record = record.unwrap();
+                let int: i64 = record.getattr("int").unwrap().extract().unwrap();
+                assert_eq!(int, 42);
+                let float: f64 = record.getattr("float").unwrap().extract().unwrap();
+                assert_eq!(float, 1.5);
+                let string: String = record.getattr("string").unwrap().extract().unwrap();
+                assert_eq!(string, "x");
+                let dumped: String = json
+                    .call_method1("dumps", (record.getattr("nested").unwrap(),))
+                    .unwrap()
+                    .extract()
+                    .unwrap();
+                let dumped: serde_json::Value = serde_json::from_str(&dumped).unwrap();
+                assert_eq!(dumped, nested);
+            }
+        });
+    }
+
+    #[test]
+    fn formatted_message() {
+        Python::with_gil(|py| {
+            let records = capture(py, "formatted_message");
+            let logger = Logger::new(py, Caching::Nothing).unwrap();
+            logger.log(
+                &Record::builder()
+                    .target("formatted_message")
+                    .level(Level::Info)
+                    .args(format_args!("{}% done, %s", 100))
+                    .build(),
+            );
+
+            let record = records.get_item(0).unwrap();
+            let msg: String = record.getattr("msg").unwrap().extract().unwrap();
+            assert_eq!(msg, "100% done, %s");
+            let args = record.getattr("args").unwrap();
+            assert!(args.downcast::<PyTuple>().is_ok());
+            assert!(args.is_empty().unwrap());
+            let message: String = record
+                .call_method0("getMessage")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(message, "100% done, %s");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "kv")]
+    fn preserve_template() {
+        Python::with_gil(|py| {
+            let records = capture(py, "preserve_template");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .preserve_template(true);
+            logger.log(
+                &Record::builder()
+                    .target("preserve_template")
+                    .level(Level::Info)
+                    .args(format_args!("{}% done", 100))
+                    .key_values(&[("user", "alice")])
+                    .build(),
+            );
+            logger.log(
+                &Record::builder()
+                    .target("preserve_template")
+                    .level(Level::Info)
+                    .args(format_args!("{}% done", 50))
+                    .build(),
+            );
+
+            let record = records.get_item(0).unwrap();
+            let msg: String = record.getattr("msg").unwrap().extract().unwrap();
+            assert_eq!(msg, "100%% done");
+            let user: String = record
+                .getattr("args")
+                .unwrap()
+                .get_item("user")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(user, "alice");
+            let message: String = record
+                .call_method0("getMessage")
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(message, "100% done");
+
+            let record = records.get_item(1).unwrap();
+            let msg: String = record.getattr("msg").unwrap().extract().unwrap();
+            assert_eq!(msg, "50% done");
+            assert!(record.getattr("args").unwrap().is_empty().unwrap());
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "kv")]
+    fn exception_mapping() {
+        use std::fmt::{Display, Formatter, Result as FmtResult};
+        use std::io::{Error as IoError, ErrorKind};
+
+        #[derive(Debug)]
+        struct Outer(IoError);
+
+        impl Display for Outer {
+            fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+                write!(fmt, "Failed to do stuff")
+            }
+        }
+
+        impl std::error::Error for Outer {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        Python::with_gil(|py| {
+            let records = capture(py, "exception_mapping");
+            let logger = Logger::new(py, Caching::Nothing)
+                .unwrap()
+                .with_exception_mapping(true);
+            let err = Outer(IoError::new(ErrorKind::Other, "Disk on fire"));
+            logger.log(
+                &Record::builder()
+                    .target("exception_mapping")
+                    .level(Level::Error)
+                    .args(format_args!("Oops"))
+                    .key_values(&[("error", log::kv::Value::from_dyn_error(&err))])
+                    .build(),
+            );
+            let record = records.get_item(0).unwrap();
+            let exc = record.getattr("exc_info").unwrap().get_item(1).unwrap();
+            assert_eq!(exc.str().unwrap(), "Failed to do stuff: Disk on fire");
+        });
     }
 }
@@ -0,0 +1,688 @@
+//! A stub of the crate without Python, see the `no-python` feature.
+//!
+//! It has the same public API as the real crate, with the Python types in the signatures replaced
+//! by the uninhabited stand-ins from the [`python`] module and the errors being [`NoPython`] (the
+//! [`Error`] alias points to it, where it points to `PyErr` normally). As there's no Python to send
+//! the records to, the [`Logger`] can't be created at all (the constructors fail) and the rest of
+//! the methods are therefore never really called.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Arguments, Display, Formatter, Result as FmtResult};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use self::python::{Bound, PyAny, PyModule, PyObject, Python};
+use crate::{
+    CacheSharing, Caching, DispatchMethod, FlushPolicy, LoggerConfig, LoggerMetrics,
+    MissingInterpreter,
+};
+
+/// Makes the stub types impossible to construct.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Void {}
+
+/// Stand-ins for the `pyo3` types in the signatures.
+///
+/// None of them can be constructed, so the functions taking them can't be called.
+pub mod python {
+    use std::marker::PhantomData;
+
+    use super::Void;
+
+    /// Stands in for `pyo3::Python`.
+    #[derive(Copy, Clone, Debug)]
+    pub struct Python<'py>(pub(super) Void, PhantomData<&'py ()>);
+
+    /// Stands in for `pyo3::Bound`.
+    #[derive(Debug)]
+    pub struct Bound<'py, T>(pub(super) Void, PhantomData<(&'py (), T)>);
+
+    /// Stands in for `pyo3::PyAny`.
+    #[derive(Debug)]
+    pub struct PyAny(pub(super) Void);
+
+    /// Stands in for `pyo3::types::PyModule`.
+    #[derive(Debug)]
+    pub struct PyModule(pub(super) Void);
+
+    /// Stands in for `pyo3::PyObject`.
+    #[derive(Debug)]
+    pub struct PyObject(pub(super) Void);
+}
+
+/// The error returned by the constructors of the stub [`Logger`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct NoPython;
+
+impl Display for NoPython {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(
+            fmt,
+            "pyo3-log was built without Python (the no-python feature)"
+        )
+    }
+}
+
+impl std::error::Error for NoPython {}
+
+/// The error of the fallible operations, [`NoPython`] in the stub.
+pub type Error = NoPython;
+
+type MapTarget = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+type FilterFn = dyn Fn(&Metadata) -> Option<LevelFilter> + Send + Sync;
+type ErrorHandler = dyn Fn(&Error) + Send + Sync;
+type MessageFormatter = dyn Fn(&Record) -> String + Send + Sync;
+type RecordName = dyn Fn(&str) -> String + Send + Sync;
+type Redactor = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+type RecordFilter = dyn Fn(&Record) -> bool + Send + Sync;
+type Tap = dyn Fn(&Record) + Send + Sync;
+
+/// A handle into a [`Logger`], for resetting its caches and changing its filters.
+///
+/// As the [`Logger`] can't be created, neither can this.
+#[derive(Clone, Debug)]
+pub struct ResetHandle(Void);
+
+impl ResetHandle {
+    /// Reset the internal logger caches.
+    pub fn reset(&self) {
+        match self.0 {}
+    }
+
+    /// The counters of the logger.
+    pub fn metrics(&self) -> LoggerMetrics {
+        match self.0 {}
+    }
+
+    /// Changes the filter of the given target.
+    pub fn set_filter(&self, _target: &str, _filter: LevelFilter) {
+        match self.0 {}
+    }
+
+    /// Changes the default filter.
+    pub fn set_default_filter(&self, _filter: LevelFilter) {
+        match self.0 {}
+    }
+
+    /// Resets the caches if the Python logging configuration changed.
+    pub fn reset_if_changed(&self) -> bool {
+        match self.0 {}
+    }
+
+    /// Resets the caches and runs the closure changing the Python configuration.
+    pub fn reset_and_sync<F>(&self, _py: Python<'_>, _sync: F) -> Result<(), Error>
+    where
+        F: FnOnce(Python<'_>) -> Result<(), Error>,
+    {
+        match self.0 {}
+    }
+
+    /// The filter that applies to the given target.
+    pub fn effective_filter(&self, _target: &str) -> LevelFilter {
+        match self.0 {}
+    }
+
+    /// The number of the cached targets.
+    pub fn cache_len(&self) -> usize {
+        match self.0 {}
+    }
+
+    /// The cached targets.
+    pub fn cached_targets(&self) -> Vec<String> {
+        match self.0 {}
+    }
+
+    /// Drops the cached entry of one target.
+    pub fn reset_target(&self, _target: &str) {
+        match self.0 {}
+    }
+
+    /// Replaces the installed logger by another one.
+    pub fn reconfigure(&self, _logger: Logger) -> Result<ResetHandle, Logger> {
+        match self.0 {}
+    }
+
+    /// Uninstalls the logger.
+    pub fn uninstall(&self) -> bool {
+        match self.0 {}
+    }
+
+    /// Turns the handle into an opaque token.
+    pub fn into_token(self) -> CacheToken {
+        match self.0 {}
+    }
+
+    /// Turns the token back into a handle.
+    pub fn from_token(token: CacheToken) -> Self {
+        match token.0 {}
+    }
+}
+
+/// An opaque token of a [`ResetHandle`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CacheToken(Void);
+
+/// Keeps contextual fields attached to the records of the current thread.
+///
+/// There are no records in the stub, so it does nothing.
+#[derive(Debug)]
+#[must_use = "The context is removed right away if the guard is not kept around"]
+pub struct ContextGuard {
+    _not_send: PhantomData<*const ()>,
+}
+
+/// The stub of the logger.
+///
+/// It can't be created, the constructors return [`NoPython`] (or panic, in case of the
+/// [`Default`]). If it existed, it would log nothing.
+#[derive(Debug)]
+pub struct Logger(Void);
+
+impl Logger {
+    /// Can't be called, there's no Python.
+    pub fn new(py: Python<'_>, _caching: Caching) -> Result<Self, Error> {
+        match py.0 {}
+    }
+
+    /// Fails, there's no Python.
+    pub fn try_default() -> Result<Self, Error> {
+        Err(NoPython)
+    }
+
+    /// Can't be called, there's no Python.
+    pub fn from_module(logging: Bound<'_, PyModule>, _caching: Caching) -> Result<Self, Error> {
+        match logging.0 {}
+    }
+
+    /// Installs the logger as the global one.
+    pub fn install(self) -> Result<ResetHandle, SetLoggerError> {
+        match self.0 {}
+    }
+
+    /// Configures whether the global maximum level is managed by the logger.
+    pub fn manage_max_level(self, _manage: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the installed logger can be replaced.
+    pub fn reconfigurable(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether panics are logged.
+    pub fn log_panics(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Installs a copy of the logger as the global one.
+    pub fn install_ref(&self) -> Result<ResetHandle, SetLoggerError> {
+        match self.0 {}
+    }
+
+    /// Registers the reset handle under the given name.
+    pub fn register_handle_as(self, _name: impl Into<String>) -> Self {
+        match self.0 {}
+    }
+
+    /// Creates a copy of the logger.
+    pub fn clone_ref(&self, _py: Python<'_>, _cache: CacheSharing) -> Self {
+        match self.0 {}
+    }
+
+    /// Converts the logger into a boxed [`Log`].
+    pub fn build(self) -> Box<dyn Log> {
+        match self.0 {}
+    }
+
+    /// The most verbose level the logger lets through.
+    pub fn max_level(&self) -> LevelFilter {
+        match self.0 {}
+    }
+
+    /// The Python `logging` module the logger uses.
+    pub fn logging_module_ref<'py>(&self, _py: Python<'py>) -> Result<Bound<'py, PyModule>, Error> {
+        match self.0 {}
+    }
+
+    /// Sends a Python `LogRecord` through the logger.
+    pub fn emit_record(
+        &self,
+        _py: Python<'_>,
+        _target: &str,
+        _record: Bound<'_, PyAny>,
+    ) -> Result<(), Error> {
+        match self.0 {}
+    }
+
+    /// Logs a record with the given level, target and message.
+    pub fn log_record(&self, _level: Level, _target: &str, _args: Arguments<'_>) {
+        match self.0 {}
+    }
+
+    /// Provides the reset handle of the logger.
+    pub fn reset_handle(&self) -> ResetHandle {
+        match self.0 {}
+    }
+
+    /// Configures the caching.
+    pub fn caching(self, _caching: Caching) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures the caching of the targets with the given prefix.
+    pub fn caching_for(self, _target_prefix: impl Into<String>, _caching: Caching) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the default filter.
+    pub fn filter(self, _filter: LevelFilter) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the filter of the given target.
+    pub fn filter_target(self, _target: impl Into<String>, _filter: LevelFilter) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the filters from directives like `info,my_crate=debug`.
+    pub fn parse_filters(self, _directives: &str) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the filters from the directives in an environment variable.
+    pub fn parse_env(self, _var: &str) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets which levels of the given target pass.
+    pub fn filter_target_levels<P>(self, _target: impl Into<String>, _predicate: P) -> Self
+    where
+        P: Fn(Level) -> bool + Send + Sync + 'static,
+    {
+        match self.0 {}
+    }
+
+    /// Sets the filter of the targets matching a glob pattern.
+    pub fn filter_glob(self, _pattern: &str, _filter: LevelFilter) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a function deciding the filter of the targets without a specific one.
+    pub fn filter_fn(self, _filter: Box<FilterFn>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the separator of the target components.
+    pub fn target_separator(self, _separator: &str) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the targets are matched case-insensitively.
+    pub fn case_insensitive_targets(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a function mapping the Rust targets.
+    pub fn map_target(self, _map: Box<MapTarget>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a function computing the name of the Python records.
+    pub fn record_name_fn(self, _name: Box<RecordName>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the prefix of the Python logger names.
+    pub fn set_prefix(self, _prefix: impl Into<String>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the Python logger name of the empty target.
+    pub fn empty_target_name(self, _name: impl Into<String>) -> Self {
+        match self.0 {}
+    }
+
+    /// Limits the number of the cached targets.
+    pub fn max_cache_entries(self, _max: usize) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the changes of the Python configuration reset the caches.
+    pub fn watch_reconfiguration(self, _watch: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the cached levels are validated.
+    #[cfg(feature = "validate-cache")]
+    pub fn validate_cache(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures what happens to the records logged without an interpreter.
+    pub fn on_missing_interpreter(self, _policy: MissingInterpreter) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the records that can't reach Python go to the standard error.
+    pub fn fallback_stderr(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a handler of the errors raised while logging.
+    pub fn on_error(self, _handler: Box<ErrorHandler>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the method the records are passed to the Python loggers with.
+    pub fn dispatch_method(self, _method: DispatchMethod) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets when the Python handlers are flushed.
+    pub fn flush_policy(self, _policy: FlushPolicy) -> Self {
+        match self.0 {}
+    }
+
+    /// Runs the closure configuring the Python logging.
+    pub fn init_python<F>(self, _py: Python<'_>, _setup: F) -> Result<Self, Error>
+    where
+        F: FnOnce(Python<'_>, &Bound<'_, PyModule>) -> Result<(), Error>,
+    {
+        match self.0 {}
+    }
+
+    /// Sets the Python level the given Rust level maps to.
+    pub fn level_map(self, _level: Level, _python_level: usize) -> Self {
+        match self.0 {}
+    }
+
+    /// Converts a Python level to a filter, using the level mapping of the logger.
+    pub fn level_filter_from_python(
+        &self,
+        _py: Python<'_>,
+        _value: &Bound<'_, PyAny>,
+    ) -> Result<LevelFilter, Error> {
+        match self.0 {}
+    }
+
+    /// Sets a function constructing the message.
+    pub fn message_formatter(self, _formatter: Box<MessageFormatter>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a function scrubbing the messages.
+    pub fn redact(self, _redactor: Box<Redactor>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a function deciding which records pass.
+    pub fn record_filter(self, _filter: Box<RecordFilter>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets a function observing the records.
+    pub fn tap(self, _tap: Box<Tap>) -> Self {
+        match self.0 {}
+    }
+
+    /// Sends the records of the given target to one more Python logger.
+    pub fn tee_target(self, _rust_target: &str, _extra_python_logger: String) -> Self {
+        match self.0 {}
+    }
+
+    /// Sends the records of the given target to the given Python logger.
+    pub fn pin_logger(
+        self,
+        _target: impl Into<String>,
+        _logger: Bound<'_, PyAny>,
+    ) -> Result<Self, Error> {
+        match self.0 {}
+    }
+
+    /// Sends all the records to the given Python handler.
+    pub fn route_to_handler(self, _handler: Bound<'_, PyAny>) -> Result<Self, Error> {
+        match self.0 {}
+    }
+
+    /// Configures whether the exception being handled is attached to the records.
+    pub fn use_current_exception(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the logged errors become the Python exceptions.
+    #[cfg(feature = "kv")]
+    pub fn with_exception_mapping(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the key-value pairs are passed as the `args`.
+    #[cfg(feature = "kv")]
+    pub fn preserve_template(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the key-value pairs are passed as nested structures.
+    #[cfg(all(feature = "kv", feature = "serde"))]
+    pub fn json_extra(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Sends the records to Python from a background thread.
+    pub fn buffered(self, _capacity: usize) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the records are queued when the GIL is busy.
+    pub fn async_friendly(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Suppresses the repeated records within the window.
+    pub fn dedupe_window(self, _window: Duration) -> Self {
+        match self.0 {}
+    }
+
+    /// Limits the rate of the records of the targets with the given prefix.
+    pub fn rate_limit(self, _target_prefix: impl Into<String>, _max_per_sec: u32) -> Self {
+        match self.0 {}
+    }
+
+    /// Passes only a fraction of the records of the targets with the given prefix.
+    pub fn sample(self, _target_prefix: impl Into<String>, _rate: f64) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the records carry the time they were logged at.
+    pub fn with_timestamps(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Attaches the fields to the records of the current thread, which does nothing here.
+    pub fn push_context(_fields: HashMap<String, PyObject>) -> ContextGuard {
+        ContextGuard {
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Configures whether the records carry the Rust thread.
+    pub fn with_thread_info(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Sets the identifier of the source the records come from.
+    pub fn source_id(self, _id: impl Into<String>) -> Self {
+        match self.0 {}
+    }
+
+    /// Captures the backtraces of the records of the given level and more severe.
+    #[cfg(feature = "backtrace")]
+    pub fn capture_backtrace(self, _min_level: Level) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether the effective levels of the Python loggers are used.
+    pub fn use_effective_level(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Configures whether a `NullHandler` is added to the Python loggers.
+    pub fn ensure_null_handler(self, _enabled: bool) -> Self {
+        match self.0 {}
+    }
+
+    /// Limits how long logging waits for the GIL.
+    pub fn gil_timeout(self, _timeout: Duration) -> Self {
+        match self.0 {}
+    }
+
+    /// Takes a snapshot of the configuration.
+    pub fn config_snapshot(&self) -> LoggerConfig {
+        match self.0 {}
+    }
+
+    /// Can't be called, there's no Python.
+    pub fn from_config(py: Python<'_>, _config: LoggerConfig) -> Result<Self, Error> {
+        match py.0 {}
+    }
+
+    /// Turns the logger into a `tracing` layer.
+    #[cfg(feature = "tracing")]
+    pub fn tracing_layer(self) -> TracingLayer {
+        match self.0 {}
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger::try_default().expect("Failed to initialize python logging")
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        false
+    }
+
+    fn log(&self, _record: &Record) {}
+
+    fn flush(&self) {}
+}
+
+/// The stub of the `tracing` layer, which can't be created.
+#[cfg(feature = "tracing")]
+#[derive(Debug)]
+pub struct TracingLayer(Void);
+
+#[cfg(feature = "tracing")]
+impl<S> tracing_subscriber::layer::Layer<S> for TracingLayer where
+    S: tracing_core::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>
+{
+}
+
+/// Can't be called, there's no Python.
+pub fn level_filter_from_python(
+    py: Python<'_>,
+    _value: &Bound<'_, PyAny>,
+) -> Result<LevelFilter, Error> {
+    match py.0 {}
+}
+
+/// Checks if a logger is installed, which is never the case here.
+pub fn is_installed() -> bool {
+    false
+}
+
+/// The handle of the installed logger, which is never there.
+pub fn global_reset_handle() -> Option<ResetHandle> {
+    None
+}
+
+/// Registers the handle under the given name.
+pub fn register_handle(_name: &str, handle: ResetHandle) {
+    match handle.0 {}
+}
+
+/// The handle registered under the given name, which is never there.
+pub fn get_handle(_name: &str) -> Option<ResetHandle> {
+    None
+}
+
+/// Installs a default logger, which always panics as it can't be created.
+pub fn try_init() -> Result<ResetHandle, SetLoggerError> {
+    Logger::try_default()
+        .map(Logger::install)
+        .expect("Failed to initialize python logging")
+}
+
+/// Installs a default logger, which always panics as it can't be created.
+pub fn init() -> ResetHandle {
+    try_init().unwrap()
+}
+
+/// Installs a logger with the given caching and filter, which always panics as it can't be
+/// created.
+pub fn try_init_with(
+    _caching: Caching,
+    _filter: LevelFilter,
+) -> Result<ResetHandle, SetLoggerError> {
+    try_init()
+}
+
+/// Configures and installs a logger, which always panics as it can't be created.
+pub fn init_with<F: FnOnce(Logger) -> Logger>(_configure: F) -> ResetHandle {
+    init()
+}
+
+/// The stub of the helpers for testing code that logs through this crate.
+///
+/// There's no Python to capture the records in, so the capturing can't be set up.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+    use super::python::Python;
+    use super::{Error, NoPython, Void};
+
+    /// A record captured on the Python side.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    #[non_exhaustive]
+    pub struct CapturedRecord {
+        /// The numeric Python level (eg. 30 for warnings).
+        pub level: usize,
+
+        /// The name of the Python logger.
+        pub target: String,
+
+        /// The formatted message.
+        pub message: String,
+
+        /// The source file, if known.
+        pub file: Option<String>,
+
+        /// The line in the source file, if known.
+        pub line: Option<u32>,
+    }
+
+    /// A Python logging handler capturing all the records, which can't be created.
+    #[derive(Debug)]
+    pub struct CapturingHandler(Void);
+
+    impl CapturingHandler {
+        /// Can't be called, there's no Python.
+        pub fn install(py: Python<'_>) -> Result<Self, Error> {
+            match py.0 {}
+        }
+
+        /// The records captured so far.
+        pub fn records(&self, _py: Python<'_>) -> Result<Vec<CapturedRecord>, Error> {
+            match self.0 {}
+        }
+    }
+
+    /// Always panics, as the capturing can't be set up without Python.
+    pub fn with_captured_logs<F: FnOnce()>(_f: F) -> Vec<CapturedRecord> {
+        panic!("Failed to set up capturing: {}", NoPython)
+    }
+}
@@ -0,0 +1,46 @@
+//! Limiting of the number of records per second, see
+//! [`Logger::rate_limit`][crate::Logger::rate_limit].
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// A token bucket, allowing bursts of up to one second worth of records.
+#[derive(Debug)]
+pub(crate) struct RateLimit {
+    per_sec: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimit {
+    pub(crate) fn new(max_per_sec: u32) -> Self {
+        let per_sec = f64::from(max_per_sec);
+        RateLimit {
+            per_sec,
+            bucket: Mutex::new(Bucket {
+                tokens: per_sec,
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token for one record, if there's any left.
+    pub(crate) fn take(&self) -> bool {
+        let now = Instant::now();
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.per_sec);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! Sampling of the records, see [`Logger::sample`][crate::Logger::sample].
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+thread_local! {
+    /// State of a xorshift generator.
+    ///
+    /// Not good for anything serious, but fast and without any synchronization, which is all we
+    /// need for sampling.
+    static RNG: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    // Each RandomState gets different keys, so this is different in each thread. Xorshift must
+    // not start with 0.
+    RandomState::new().build_hasher().finish() | 1
+}
+
+/// A random number in the [0, 1) range.
+fn random() -> f64 {
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        rng.set(x);
+        // The top 53 bits fit exactly into the mantissa.
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[derive(Debug)]
+pub(crate) struct Sample {
+    rate: f64,
+}
+
+impl Sample {
+    pub(crate) fn new(rate: f64) -> Self {
+        assert!(
+            rate > 0.0 && rate <= 1.0,
+            "Sampling rate must be in (0, 1], got {}",
+            rate
+        );
+        Sample { rate }
+    }
+
+    /// Decides if the record is admitted.
+    pub(crate) fn admit(&self) -> bool {
+        random() < self.rate
+    }
+}
@@ -0,0 +1,95 @@
+//! A plain-data snapshot of the logger configuration.
+
+use std::collections::HashMap;
+
+use log::LevelFilter;
+#[cfg(feature = "pyo3")]
+use pyo3::prelude::*;
+
+use crate::Caching;
+#[cfg(feature = "pyo3")]
+use crate::Logger;
+
+/// The filtering (and few other) settings of a [`Logger`], as plain data.
+///
+/// Created by [`Logger::config_snapshot`] and turned back into a logger by
+/// [`Logger::from_config`]. With the `serde` feature, it can be (de)serialized, so the logging
+/// configuration can live in eg. a config file. Missing fields take the defaults of a new
+/// [`Logger`].
+///
+/// Only the settings representable as data are included. Things like the
+/// [filter function][Logger::filter_fn] or the [target mapping][Logger::map_target] need to be
+/// set on the logger after it is created from the config.
+///
+/// ```rust
+/// use log::LevelFilter;
+/// use pyo3::prelude::*;
+/// use pyo3_log::{Logger, LoggerConfig};
+///
+/// let mut config = LoggerConfig::default();
+/// config.top_filter = LevelFilter::Warn;
+/// config.filters.insert("my_crate::net".to_owned(), LevelFilter::Trace);
+/// let logger = Python::with_gil(|py| Logger::from_config(py, config.clone())).unwrap();
+/// assert_eq!(logger.config_snapshot(), config);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde_crate::Serialize, serde_crate::Deserialize),
+    serde(crate = "serde_crate", default)
+)]
+pub struct LoggerConfig {
+    /// The [default filter][Logger::filter].
+    pub top_filter: LevelFilter,
+
+    /// The [filters of specific targets][Logger::filter_target].
+    pub filters: HashMap<String, LevelFilter>,
+
+    /// What to cache.
+    pub caching: Caching,
+
+    /// The [prefix][Logger::set_prefix] of the Python logger names.
+    pub prefix: String,
+}
+
+impl Default for LoggerConfig {
+    fn default() -> Self {
+        LoggerConfig {
+            top_filter: LevelFilter::Debug,
+            filters: HashMap::new(),
+            caching: Caching::default(),
+            prefix: String::new(),
+        }
+    }
+}
+
+#[cfg(feature = "pyo3")]
+impl Logger {
+    /// Takes a snapshot of the configuration.
+    ///
+    /// This includes the filters changed at runtime through the [`ResetHandle`][crate::ResetHandle].
+    pub fn config_snapshot(&self) -> LoggerConfig {
+        let filters = self.filters.load();
+        LoggerConfig {
+            top_filter: filters.top,
+            filters: filters.targets.clone(),
+            caching: self.config.caching,
+            prefix: self.config.prefix.clone(),
+        }
+    }
+
+    /// Creates a logger from a previously stored configuration.
+    ///
+    /// See [`LoggerConfig`].
+    pub fn from_config(py: Python<'_>, config: LoggerConfig) -> PyResult<Self> {
+        let logger = Logger::new(py, config.caching)?
+            .filter(config.top_filter)
+            .set_prefix(config.prefix);
+        Ok(config
+            .filters
+            .into_iter()
+            .fold(logger, |logger, (target, filter)| {
+                logger.filter_target(target, filter)
+            }))
+    }
+}
@@ -0,0 +1,115 @@
+//! A replacement of the [`arc_swap`] types on top of [`RwLock`], see the `std-cache` feature.
+//!
+//! Only the subset of the API we use is provided, with the same names and semantics, so the rest
+//! of the crate doesn't care which one it got. Unlike with [`arc_swap`], the readers block (and
+//! are blocked by) the writers and the loads touch the reference count, which may be slower under
+//! contention.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::mem;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+pub(crate) struct ArcSwapAny<T> {
+    inner: RwLock<T>,
+}
+
+pub(crate) type ArcSwap<T> = ArcSwapAny<Arc<T>>;
+pub(crate) type ArcSwapOption<T> = ArcSwapAny<Option<Arc<T>>>;
+
+impl<T> ArcSwapAny<T> {
+    // The lock only protects the swap of the pointer and nothing can panic while it is held
+    // (except for the closure of rcu, which doesn't leave anything half-done), so poisoning is
+    // ignored.
+    fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn load(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(&self.read())
+    }
+
+    pub(crate) fn store(&self, value: T) {
+        *self.write() = value;
+    }
+
+    /// Replaces the value with the result of the closure, returning the previous one.
+    ///
+    /// Unlike the [`arc_swap`] one, the closure is called exactly once, as the write lock is held
+    /// through the whole update.
+    pub(crate) fn rcu<R, F>(&self, mut f: F) -> T
+    where
+        F: FnMut(&T) -> R,
+        R: Into<T>,
+    {
+        let mut current = self.write();
+        let new = f(&current).into();
+        mem::replace(&mut *current, new)
+    }
+}
+
+impl<T> ArcSwap<T> {
+    pub(crate) fn from_pointee(value: T) -> Self {
+        ArcSwapAny {
+            inner: RwLock::new(Arc::new(value)),
+        }
+    }
+
+    /// Stores the new value if the current one is still `current`.
+    ///
+    /// Returns the previous value, so the success can be checked by comparing it to `current`.
+    pub(crate) fn compare_and_swap(&self, current: &Arc<T>, new: Arc<T>) -> Arc<T> {
+        let mut value = self.write();
+        if Arc::ptr_eq(&value, current) {
+            mem::replace(&mut *value, new)
+        } else {
+            Arc::clone(&value)
+        }
+    }
+}
+
+impl<T> ArcSwapOption<T> {
+    #[allow(clippy::incompatible_msrv)] // The feature documents the higher requirement.
+    pub(crate) const fn const_empty() -> Self {
+        ArcSwapAny {
+            inner: RwLock::new(None),
+        }
+    }
+
+    /// Like [`ArcSwap::compare_and_swap`], `None` is equal only to `None`.
+    pub(crate) fn compare_and_swap(
+        &self,
+        current: &Option<Arc<T>>,
+        new: Option<Arc<T>>,
+    ) -> Option<Arc<T>> {
+        let mut value = self.write();
+        let same = match (&*value, current) {
+            (Some(value), Some(current)) => Arc::ptr_eq(value, current),
+            (None, None) => true,
+            _ => false,
+        };
+        if same {
+            mem::replace(&mut *value, new)
+        } else {
+            value.clone()
+        }
+    }
+}
+
+impl<T: Default> Default for ArcSwap<T> {
+    fn default() -> Self {
+        Self::from_pointee(T::default())
+    }
+}
+
+impl<T: Debug> Debug for ArcSwapAny<T> {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_tuple("ArcSwapAny").field(&*self.read()).finish()
+    }
+}
@@ -0,0 +1,149 @@
+//! Helpers for testing code that logs through this crate.
+//!
+//! The records are captured on the Python side, after they went through the whole pipeline (the
+//! filters, the caches and the conversion into Python `LogRecord`s). Therefore, they show what
+//! the Python handlers would see.
+//!
+//! Note that the Python loggers and levels may be cached by the [`Logger`][crate::Logger]. The
+//! capturing lowers the level of the root Python logger, which the caches don't notice, so use
+//! [`Caching::Nothing`][crate::Caching::Nothing] or [reset][crate::ResetHandle::reset] the caches
+//! after starting the capture.
+//!
+//! ```rust
+//! use log::{Level, Log, Record};
+//! use pyo3::prelude::*;
+//! use pyo3_log::testing::with_captured_logs;
+//! use pyo3_log::{Caching, Logger};
+//!
+//! let logger = Python::with_gil(|py| Logger::new(py, Caching::Nothing)).unwrap();
+//! let records = with_captured_logs(|| {
+//!     logger.log(
+//!         &Record::builder()
+//!             .target("my_module::sub")
+//!             .level(Level::Warn)
+//!             .args(format_args!("Hello"))
+//!             .build(),
+//!     );
+//! });
+//! assert!(records
+//!     .iter()
+//!     .any(|r| r.target == "my_module.sub" && r.message == "Hello" && r.level == 30));
+//! ```
+//!
+//! This module is available only with the `testing` feature.
+
+use pyo3::prelude::*;
+
+/// A record captured on the Python side.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct CapturedRecord {
+    /// The numeric Python level (eg. 30 for warnings).
+    pub level: usize,
+
+    /// The name of the Python logger.
+    ///
+    /// Note that this is the Python form, after mapping from the Rust target (eg.
+    /// `my_module.submodule`).
+    pub target: String,
+
+    /// The formatted message.
+    pub message: String,
+
+    /// The source file, if known.
+    pub file: Option<String>,
+
+    /// The line in the source file, if known.
+    pub line: Option<u32>,
+}
+
+impl CapturedRecord {
+    fn from_python(record: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let line: u32 = record.getattr("lineno")?.extract()?;
+        let file: Option<String> = record.getattr("pathname")?.extract()?;
+        Ok(CapturedRecord {
+            level: record.getattr("levelno")?.extract()?,
+            target: record.getattr("name")?.extract()?,
+            message: record.call_method0("getMessage")?.extract()?,
+            // Unknown file and line are passed as empty and 0.
+            file: file.filter(|f| !f.is_empty()),
+            line: Some(line).filter(|l| *l != 0),
+        })
+    }
+}
+
+/// A Python logging handler capturing all the records.
+///
+/// It is attached to the root Python logger and the level of the root logger is lowered so all
+/// the records reach it. Dropping it detaches it and restores the original level.
+#[derive(Debug)]
+pub struct CapturingHandler {
+    handler: PyObject,
+    root: PyObject,
+    old_level: PyObject,
+}
+
+impl CapturingHandler {
+    /// Creates the handler and attaches it to the root Python logger.
+    pub fn install(py: Python<'_>) -> PyResult<Self> {
+        let root = py.import("logging")?.getattr("getLogger")?.call0()?;
+        // A buffering handler that never flushes on its own.
+        let handler = py
+            .import("logging.handlers")?
+            .getattr("BufferingHandler")?
+            .call1((usize::MAX,))?;
+        // Flushing it would throw the records away, so make the flush do nothing (as in the base
+        // class).
+        let noop = py
+            .import("logging")?
+            .getattr("Handler")?
+            .getattr("flush")?
+            .call_method1("__get__", (&handler,))?;
+        handler.setattr("flush", noop)?;
+        let old_level = root.getattr("level")?;
+        root.call_method1("setLevel", (1,))?;
+        root.call_method1("addHandler", (&handler,))?;
+        Ok(CapturingHandler {
+            handler: handler.unbind(),
+            root: root.unbind(),
+            old_level: old_level.unbind(),
+        })
+    }
+
+    /// The records captured so far.
+    pub fn records(&self, py: Python<'_>) -> PyResult<Vec<CapturedRecord>> {
+        self.handler
+            .bind(py)
+            .getattr("buffer")?
+            .try_iter()?
+            .map(|record| CapturedRecord::from_python(&record?))
+            .collect()
+    }
+}
+
+impl Drop for CapturingHandler {
+    fn drop(&mut self) {
+        Python::with_gil(|py| {
+            let root = self.root.bind(py);
+            // Nothing much to do about errors in a destructor.
+            let _ = root.call_method1("removeHandler", (&self.handler,));
+            let _ = root.call_method1("setLevel", (&self.old_level,));
+        });
+    }
+}
+
+/// Runs the closure and returns all the records logged to Python during it.
+///
+/// The GIL is not held while the closure runs, so it can log from other threads too. Note that
+/// all the records reaching Python are captured, including ones from other threads (and other
+/// tests running in parallel). Filtering them by the [`target`][CapturedRecord::target] might be
+/// needed.
+///
+/// # Panics
+///
+/// If the Python side fails to set up the capturing.
+pub fn with_captured_logs<F: FnOnce()>(f: F) -> Vec<CapturedRecord> {
+    let handler = Python::with_gil(CapturingHandler::install).expect("Failed to set up capturing");
+    f();
+    Python::with_gil(|py| handler.records(py)).expect("Failed to read captured records")
+}
@@ -0,0 +1,235 @@
+//! A [`tracing`](https://docs.rs/tracing) layer on top of the [`Logger`], see
+//! [`Logger::tracing_layer`][crate::Logger::tracing_layer].
+
+use std::error::Error;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+use log::{Level, Log, Metadata, Record};
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record as SpanRecord};
+use tracing_core::subscriber::Interest;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::kv::{OwnedError, OwnedKvs, OwnedValue};
+use crate::Logger;
+
+/// The name of the field `tracing` puts the message of an event into.
+const MESSAGE: &str = "message";
+
+/// The fields of a span, stored in its extensions.
+struct SpanFields(OwnedKvs);
+
+/// Collects the fields of a span or an event.
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    kvs: OwnedKvs,
+}
+
+impl Fields {
+    fn push(&mut self, field: &Field, value: OwnedValue) {
+        self.kvs.push((field.name().to_owned(), value));
+    }
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        let value = format!("{:?}", value);
+        if field.name() == MESSAGE {
+            self.message = Some(value);
+        } else {
+            self.push(field, OwnedValue::Str(value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == MESSAGE {
+            self.message = Some(value.to_owned());
+        } else {
+            self.push(field, OwnedValue::Str(value.to_owned()));
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, OwnedValue::F64(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, OwnedValue::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, OwnedValue::U64(value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.push(field, OwnedValue::I128(value));
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.push(field, OwnedValue::U128(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, OwnedValue::Bool(value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        self.push(field, OwnedValue::Error(OwnedError::new(value)));
+    }
+}
+
+/// Converts the level of `tracing` to the one of `log`.
+fn level(level: tracing_core::Level) -> Level {
+    match level {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warn,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        tracing_core::Level::TRACE => Level::Trace,
+    }
+}
+
+/// A [`tracing_subscriber`](https://docs.rs/tracing-subscriber) layer sending the events to
+/// Python.
+///
+/// Created by [`Logger::tracing_layer`][crate::Logger::tracing_layer].
+pub struct TracingLayer {
+    logger: Logger,
+}
+
+impl Debug for TracingLayer {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct("TracingLayer")
+            .field("logger", &self.logger)
+            .finish()
+    }
+}
+
+impl TracingLayer {
+    fn metadata<'a>(metadata: &'a tracing_core::Metadata<'_>) -> Metadata<'a> {
+        Metadata::builder()
+            .level(level(*metadata.level()))
+            .target(metadata.target())
+            .build()
+    }
+}
+
+impl<S> Layer<S> for TracingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static tracing_core::Metadata<'static>) -> Interest {
+        // The Python levels (and our filters) may change at runtime, so this can't be decided
+        // once for all.
+        if metadata.is_event() {
+            Interest::sometimes()
+        } else {
+            Interest::always()
+        }
+    }
+
+    fn enabled(&self, metadata: &tracing_core::Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        // The spans are always kept, for their fields.
+        !metadata.is_event() || self.logger.enabled(&Self::metadata(metadata))
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(message) = fields.message {
+            fields
+                .kvs
+                .push((MESSAGE.to_owned(), OwnedValue::Str(message)));
+        }
+        span.extensions_mut().insert(SpanFields(fields.kvs));
+    }
+
+    fn on_record(&self, id: &Id, values: &SpanRecord<'_>, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(message) = fields.message {
+            fields
+                .kvs
+                .push((MESSAGE.to_owned(), OwnedValue::Str(message)));
+        }
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanFields>() {
+            // The later values win once turned into the extra, so they can be just appended.
+            Some(SpanFields(kvs)) => kvs.extend(fields.kvs),
+            None => extensions.insert(SpanFields(fields.kvs)),
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // From the outermost span in, so the fields of the inner ones (and of the event itself)
+        // take precedence.
+        let mut kvs = OwnedKvs::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    kvs.extend(fields.iter().cloned());
+                }
+            }
+        }
+        let mut fields = Fields { message: None, kvs };
+        event.record(&mut fields);
+
+        let metadata = event.metadata();
+        self.logger.log(
+            &Record::builder()
+                .metadata(Self::metadata(metadata))
+                .args(format_args!(
+                    "{}",
+                    fields.message.as_deref().unwrap_or_default()
+                ))
+                .file(metadata.file())
+                .line(metadata.line())
+                .module_path(metadata.module_path())
+                .key_values(&fields.kvs)
+                .build(),
+        );
+    }
+}
+
+impl Logger {
+    /// Turns the logger into a [`tracing`](https://docs.rs/tracing) layer.
+    ///
+    /// The layer sends the events through the logger to Python, the same way as the `log`
+    /// records. The fields of the event and of the spans it happened in are passed as the
+    /// key-value pairs, which end up in the `extra` of the Python record (the ones of the inner
+    /// spans and of the event take precedence over the outer ones). The `message` field becomes
+    /// the message.
+    ///
+    /// The layer is meant to be put into the
+    /// [`Registry`](https://docs.rs/tracing-subscriber/*/tracing_subscriber/registry/struct.Registry.html)
+    /// (or something else implementing the `LookupSpan` trait), where the span fields are kept.
+    /// The logger doesn't need to be [installed][Logger::install] for this, but it can still be
+    /// configured the usual way (the filters, the caching, etc.) and [reset][Logger::reset_handle].
+    ///
+    /// ```rust
+    /// # use pyo3_log::Logger;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    ///
+    /// let subscriber = tracing_subscriber::registry().with(Logger::default().tracing_layer());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("request", id = 42);
+    ///     let _entered = span.enter();
+    ///     // Reaches Python with `id` in the extra.
+    ///     tracing::info!(status = 200, "Done");
+    /// });
+    /// ```
+    pub fn tracing_layer(self) -> TracingLayer {
+        TracingLayer { logger: self }
+    }
+}